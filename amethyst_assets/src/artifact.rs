@@ -0,0 +1,555 @@
+//! Offline import of assets into engine-ready binary artifacts.
+//!
+//! Decoding source files (images, meshes, prefabs) can make up a large part of the startup
+//! time of a game. An `ArtifactCache` stores the `Asset::Data` produced by a format as
+//! `bincode`, keyed by a hash of the source bytes, the format options and the importer
+//! version. The `AssetPipeline` fills the cache ahead of time, while `Loader::load_cached`
+//! prefers an up-to-date artifact over running the format's import logic.
+//!
+//! Only the main file of an asset is hashed, so formats which read additional files
+//! (like glTF buffers) are not invalidated when only those files change.
+//!
+//! The `amethyst_pipeline` binary runs an `AssetPipeline` with the mesh and texture formats of
+//! `amethyst_renderer`. Games with other formats can build their own pipeline binary:
+//!
+//! ### Example
+//!
+//! ```rust,ignore
+//! // In a separate binary, run as part of the content build:
+//! let report = AssetPipeline::new("assets", ArtifactCache::new("assets/.artifacts"))
+//!     .with_importer::<Mesh, _>("obj", ObjFormat, ())
+//!     .with_importer::<Texture, _>("png", PngFormat, TextureMetadata::srgb())
+//!     .run();
+//!
+//! // In the game:
+//! loader.set_artifact_cache(ArtifactCache::new("assets/.artifacts"));
+//! let mesh = loader.load_cached("mesh/cube.obj", ObjFormat, (), (), &mesh_storage);
+//! ```
+
+use std::{
+    fs,
+    hash::Hasher,
+    path::{Path, PathBuf},
+    process,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
+};
+
+use fnv::FnvHasher;
+use log::{debug, warn};
+use rayon::prelude::*;
+use serde::{de::DeserializeOwned, Serialize};
+
+use amethyst_error::{format_err, Error, ResultExt};
+
+use crate::{error, Asset, Directory, Format, FormatValue, Reload, SingleFile, Source};
+
+/// Identifies artifact files, followed by the importer version.
+const MAGIC: &[u8; 4] = b"AMAF";
+
+/// Counter making the names of temporary artifact files unique within the process.
+static TMP_COUNTER: AtomicUsize = AtomicUsize::new(0);
+
+/// A `Format` whose imported data can be stored in an `ArtifactCache`.
+pub trait ArtifactFormat<A: Asset>: Format<A> {
+    /// Version of the import logic. This has to be increased whenever `import` produces
+    /// different data for the same input, which invalidates all existing artifacts.
+    const VERSION: u32;
+
+    /// Converts freshly imported data into a form which can be serialized.
+    ///
+    /// The returned data is used both for the artifact and for the current load.
+    /// The default implementation returns the data unchanged.
+    fn prepare_artifact(data: A::Data) -> A::Data {
+        data
+    }
+}
+
+/// On-disk storage of imported asset data.
+#[derive(Debug)]
+pub struct ArtifactCache {
+    dir: PathBuf,
+}
+
+impl ArtifactCache {
+    /// Creates a cache storing its artifacts in `dir`.
+    /// The directory is created when the first artifact is written.
+    pub fn new<P>(dir: P) -> Self
+    where
+        P: Into<PathBuf>,
+    {
+        ArtifactCache { dir: dir.into() }
+    }
+
+    /// Returns the directory the artifacts are stored in.
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    /// Computes the key of the artifact for the given source bytes and options.
+    pub fn key<A, F>(bytes: &[u8], options: &F::Options) -> Result<u64, Error>
+    where
+        A: Asset,
+        A::Data: Serialize + DeserializeOwned,
+        F: ArtifactFormat<A>,
+        F::Options: Serialize,
+    {
+        let options = bincode::serialize(options)
+            .with_context(|_| format_err!("Failed to serialize format options"))?;
+
+        let mut hasher = FnvHasher::default();
+        hasher.write(A::NAME.as_bytes());
+        hasher.write(F::NAME.as_bytes());
+        hasher.write_u32(F::VERSION);
+        hasher.write(&options);
+        hasher.write(bytes);
+        Ok(hasher.finish())
+    }
+
+    /// Returns `true` if an artifact with the given key exists.
+    pub fn contains<A: Asset>(&self, key: u64) -> bool {
+        self.path::<A>(key).is_file()
+    }
+
+    /// Reads the artifact with the given key.
+    ///
+    /// Returns `Ok(None)` if there is no artifact for `key`, or if it
+    /// was written by another version of the importer.
+    pub fn load<A, F>(&self, key: u64) -> Result<Option<A::Data>, Error>
+    where
+        A: Asset,
+        A::Data: Serialize + DeserializeOwned,
+        F: ArtifactFormat<A>,
+        F::Options: Serialize,
+    {
+        let path = self.path::<A>(key);
+        if !path.is_file() {
+            return Ok(None);
+        }
+
+        let bytes =
+            fs::read(&path).with_context(|_| format_err!("Failed to read artifact {:?}", path))?;
+        if bytes.len() < 8 || &bytes[..4] != MAGIC || bytes[4..8] != F::VERSION.to_le_bytes() {
+            debug!("Ignoring outdated artifact {:?}", path);
+            return Ok(None);
+        }
+
+        bincode::deserialize(&bytes[8..])
+            .map(Some)
+            .with_context(|_| format_err!("Failed to deserialize artifact {:?}", path))
+    }
+
+    /// Writes `data` as the artifact with the given key.
+    pub fn store<A, F>(&self, key: u64, data: &A::Data) -> Result<(), Error>
+    where
+        A: Asset,
+        A::Data: Serialize + DeserializeOwned,
+        F: ArtifactFormat<A>,
+        F::Options: Serialize,
+    {
+        let path = self.path::<A>(key);
+        let mut bytes = MAGIC.to_vec();
+        bytes.extend_from_slice(&F::VERSION.to_le_bytes());
+        bincode::serialize_into(&mut bytes, data)
+            .with_context(|_| format_err!("Failed to serialize artifact {:?}", path))?;
+
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)
+                .with_context(|_| format_err!("Failed to create directory {:?}", parent))?;
+        }
+        // Write to a temporary file first, so readers never observe a partial artifact.
+        // The name is unique, so concurrent writers of the same artifact don't interfere.
+        let tmp = path.with_extension(format!(
+            "{}.{}.tmp",
+            process::id(),
+            TMP_COUNTER.fetch_add(1, Ordering::Relaxed)
+        ));
+        fs::write(&tmp, &bytes).with_context(|_| format_err!("Failed to write {:?}", tmp))?;
+        fs::rename(&tmp, &path).with_context(|_| format_err!("Failed to write {:?}", path))?;
+
+        Ok(())
+    }
+
+    fn path<A: Asset>(&self, key: u64) -> PathBuf {
+        let type_dir = A::NAME
+            .chars()
+            .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+            .collect::<String>();
+
+        self.dir.join(type_dir).join(format!("{:016x}.bin", key))
+    }
+
+    /// Returns the cached data for `bytes`, importing and storing it with `format` if necessary.
+    fn import<A, F>(
+        &self,
+        format: &F,
+        name: &str,
+        bytes: Vec<u8>,
+        source: Arc<dyn Source>,
+        options: F::Options,
+    ) -> Result<(A::Data, bool), Error>
+    where
+        A: Asset,
+        A::Data: Serialize + DeserializeOwned,
+        F: ArtifactFormat<A>,
+        F::Options: Serialize,
+    {
+        let key = Self::key::<A, F>(&bytes, &options)?;
+        match self.load::<A, F>(key) {
+            Ok(Some(data)) => return Ok((data, false)),
+            Ok(None) => {}
+            Err(e) => warn!(
+                "{:?}: Ignoring broken artifact for {:?}: {}",
+                A::NAME,
+                name,
+                e
+            ),
+        }
+
+        // The format reads the file itself, so we pass it a source serving the loaded bytes.
+        let source = Arc::new(Preloaded {
+            name: name.to_owned(),
+            bytes,
+            source,
+        }) as Arc<dyn Source>;
+        let data = format
+            .import(name.to_owned(), source, options, false)
+            .with_context(|_| error::Error::Format(F::NAME))?
+            .data;
+        let data = F::prepare_artifact(data);
+
+        if let Err(e) = self.store::<A, F>(key, &data) {
+            warn!(
+                "{:?}: Failed to store artifact for {:?}: {}",
+                A::NAME,
+                name,
+                e
+            );
+        }
+
+        Ok((data, true))
+    }
+}
+
+/// A source serving the already loaded bytes of a single file to a format.
+/// Any other path is loaded from the original source.
+struct Preloaded {
+    name: String,
+    bytes: Vec<u8>,
+    source: Arc<dyn Source>,
+}
+
+impl Source for Preloaded {
+    fn modified(&self, path: &str) -> Result<u64, Error> {
+        self.source.modified(path)
+    }
+
+    fn load(&self, path: &str) -> Result<Vec<u8>, Error> {
+        if path == self.name {
+            Ok(self.bytes.clone())
+        } else {
+            self.source.load(path)
+        }
+    }
+
+    fn size(&self, path: &str) -> Option<u64> {
+        if path == self.name {
+            Some(self.bytes.len() as u64)
+        } else {
+            self.source.size(path)
+        }
+    }
+}
+
+/// Format which imports using `F`, but prefers an up-to-date artifact from an `ArtifactCache`.
+///
+/// This is usually created by `Loader::load_cached`.
+#[derive(Clone)]
+pub struct CachedFormat<F> {
+    format: F,
+    cache: Arc<ArtifactCache>,
+}
+
+impl<F> CachedFormat<F> {
+    /// Wraps `format`, reading and writing artifacts from `cache`.
+    pub fn new(format: F, cache: Arc<ArtifactCache>) -> Self {
+        CachedFormat { format, cache }
+    }
+}
+
+impl<A, F> Format<A> for CachedFormat<F>
+where
+    A: Asset,
+    A::Data: Serialize + DeserializeOwned,
+    F: ArtifactFormat<A> + Clone + Sync,
+    F::Options: Serialize + Clone + Sync,
+{
+    const NAME: &'static str = F::NAME;
+    type Options = F::Options;
+
+    fn import(
+        &self,
+        name: String,
+        source: Arc<dyn Source>,
+        options: Self::Options,
+        create_reload: bool,
+    ) -> Result<FormatValue<A>, Error> {
+        let (bytes, modified) = if create_reload {
+            source.load_with_metadata(&name)
+        } else {
+            source.load(&name).map(|b| (b, 0))
+        }
+        .with_context(|_| error::Error::Source)?;
+
+        let (data, _) = self.cache.import::<A, F>(
+            &self.format,
+            &name,
+            bytes,
+            source.clone(),
+            options.clone(),
+        )?;
+
+        let reload = if create_reload {
+            let reload = SingleFile::new(self.clone(), modified, options, name, source);
+            Some(Box::new(reload) as Box<dyn Reload<A>>)
+        } else {
+            None
+        };
+
+        Ok(FormatValue { data, reload })
+    }
+}
+
+/// Result of `AssetPipeline::run`.
+#[derive(Debug, Default)]
+pub struct PipelineReport {
+    /// Files which have been imported and written to the cache.
+    pub imported: Vec<String>,
+    /// Files whose artifacts were already up to date.
+    pub up_to_date: Vec<String>,
+    /// Files which could not be imported.
+    pub failed: Vec<(String, Error)>,
+}
+
+type Importer = Box<
+    dyn Fn(&ArtifactCache, &str, Vec<u8>, Arc<dyn Source>) -> Result<bool, Error> + Send + Sync,
+>;
+
+/// Imports all source files of a directory into an `ArtifactCache`.
+///
+/// Importers are registered per file extension; files with other extensions are skipped.
+/// The files are imported in parallel using rayon.
+pub struct AssetPipeline {
+    source: Arc<dyn Source>,
+    root: PathBuf,
+    cache: ArtifactCache,
+    importers: Vec<(String, Importer)>,
+}
+
+impl AssetPipeline {
+    /// Creates a pipeline importing the files below `root` into `cache`.
+    pub fn new<P>(root: P, cache: ArtifactCache) -> Self
+    where
+        P: Into<PathBuf>,
+    {
+        let root = root.into();
+        AssetPipeline {
+            source: Arc::new(Directory::new(root.clone())),
+            root,
+            cache,
+            importers: Vec::new(),
+        }
+    }
+
+    /// Imports files with the given extension as asset `A` using `format` and `options`.
+    ///
+    /// The options have to match the ones used at runtime, otherwise the artifacts won't be used.
+    pub fn with_importer<A, F>(mut self, extension: &str, format: F, options: F::Options) -> Self
+    where
+        A: Asset,
+        A::Data: Serialize + DeserializeOwned,
+        F: ArtifactFormat<A> + Sync,
+        F::Options: Serialize + Clone + Sync,
+    {
+        let importer =
+            move |cache: &ArtifactCache, name: &str, bytes: Vec<u8>, source: Arc<dyn Source>| {
+                cache
+                    .import::<A, F>(&format, name, bytes, source, options.clone())
+                    .map(|(_, imported)| imported)
+            };
+        self.importers
+            .push((extension.to_lowercase(), Box::new(importer)));
+        self
+    }
+
+    /// Imports all files below the root directory which have an importer registered.
+    pub fn run(&self) -> PipelineReport {
+        let mut files = Vec::new();
+        // The cache may be inside the root, its artifacts are not imported again.
+        let cache_dir = fs::canonicalize(self.cache.dir()).ok();
+        if let Err(e) = collect_files(
+            &self.root,
+            "",
+            cache_dir.as_ref().map(PathBuf::as_path),
+            &mut files,
+        ) {
+            let mut report = PipelineReport::default();
+            report.failed.push((self.root.display().to_string(), e));
+            return report;
+        }
+
+        let results = files
+            .par_iter()
+            .filter_map(|name| {
+                let extension = Path::new(name)
+                    .extension()
+                    .and_then(|e| e.to_str())
+                    .map(str::to_lowercase)?;
+                let importers = self.importers.iter().filter(|(e, _)| *e == extension);
+                let results = importers
+                    .map(|(_, importer)| {
+                        self.source.load(name).and_then(|bytes| {
+                            importer(&self.cache, name, bytes, self.source.clone())
+                        })
+                    })
+                    .collect::<Vec<_>>();
+                Some((name.clone(), results))
+            })
+            .collect::<Vec<_>>();
+
+        let mut report = PipelineReport::default();
+        for (name, results) in results {
+            for result in results {
+                match result {
+                    Ok(true) => report.imported.push(name.clone()),
+                    Ok(false) => report.up_to_date.push(name.clone()),
+                    Err(e) => report.failed.push((name.clone(), e)),
+                }
+            }
+        }
+        report
+    }
+}
+
+/// Recursively collects the paths of all files below `dir`, using `/` as separator.
+///
+/// The directory `skip` is left out, it has to be canonicalized.
+fn collect_files(
+    dir: &Path,
+    prefix: &str,
+    skip: Option<&Path>,
+    files: &mut Vec<String>,
+) -> Result<(), Error> {
+    let entries =
+        fs::read_dir(dir).with_context(|_| format_err!("Failed to read directory {:?}", dir))?;
+    for entry in entries {
+        let entry = entry.with_context(|_| format_err!("Failed to read directory {:?}", dir))?;
+        let name = format!("{}{}", prefix, entry.file_name().to_string_lossy());
+        let path = entry.path();
+        if path.is_dir() {
+            let skipped = match skip {
+                Some(skip) => fs::canonicalize(&path).map(|p| p == skip).unwrap_or(false),
+                None => false,
+            };
+            if skipped {
+                continue;
+            }
+            collect_files(&path, &format!("{}/", name), skip, files)?;
+        } else {
+            files.push(name);
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{fs, sync::Arc};
+
+    use rayon::prelude::*;
+
+    use amethyst_core::ecs::prelude::VecStorage;
+
+    use crate::{test_util::Files, Format, Handle, RonFormat, Source};
+
+    use super::{collect_files, ArtifactCache, CachedFormat};
+
+    struct Numbers;
+
+    impl crate::Asset for Numbers {
+        const NAME: &'static str = "test::Numbers";
+        type Data = Vec<u32>;
+        type HandleStorage = VecStorage<Handle<Self>>;
+    }
+
+    #[test]
+    fn cached_format_writes_and_reuses_artifacts() {
+        let dir = std::env::temp_dir().join(format!(
+            "amethyst_assets_artifact_test_{}",
+            std::process::id()
+        ));
+        let _ = fs::remove_dir_all(&dir);
+        let cache = Arc::new(ArtifactCache::new(&dir));
+        let format = CachedFormat::new(RonFormat, cache.clone());
+
+        let source = Arc::new(Files::new(vec![("numbers.ron", "[1, 2, 3]")])) as Arc<dyn Source>;
+        let value: crate::FormatValue<Numbers> = format
+            .import("numbers.ron".into(), source, (), false)
+            .expect("Failed to import");
+        assert_eq!(vec![1, 2, 3], value.data);
+
+        let key = ArtifactCache::key::<Numbers, RonFormat>(b"[1, 2, 3]", &()).unwrap();
+        assert!(cache.contains::<Numbers>(key));
+        assert_eq!(
+            Some(vec![1, 2, 3]),
+            cache.load::<Numbers, RonFormat>(key).unwrap()
+        );
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn concurrent_stores_of_an_artifact_succeed() {
+        let dir = std::env::temp_dir().join(format!(
+            "amethyst_assets_artifact_store_test_{}",
+            std::process::id()
+        ));
+        let _ = fs::remove_dir_all(&dir);
+        let cache = ArtifactCache::new(&dir);
+        let key = ArtifactCache::key::<Numbers, RonFormat>(b"[1]", &()).unwrap();
+
+        (0..16u32)
+            .into_par_iter()
+            .map(|_| cache.store::<Numbers, RonFormat>(key, &vec![1]))
+            .collect::<Result<Vec<_>, _>>()
+            .expect("Failed to store artifact");
+        assert_eq!(
+            Some(vec![1]),
+            cache.load::<Numbers, RonFormat>(key).unwrap()
+        );
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn collecting_files_skips_the_cache() {
+        let dir = std::env::temp_dir().join(format!(
+            "amethyst_assets_artifact_collect_test_{}",
+            std::process::id()
+        ));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(dir.join("mesh")).unwrap();
+        fs::create_dir_all(dir.join(".artifacts/test__Numbers")).unwrap();
+        fs::write(dir.join("mesh/cube.ron"), "[1]").unwrap();
+        fs::write(dir.join(".artifacts/test__Numbers/0.bin"), "").unwrap();
+
+        let skip = fs::canonicalize(dir.join(".artifacts")).unwrap();
+        let mut files = Vec::new();
+        collect_files(&dir, "", Some(&skip), &mut files).unwrap();
+        assert_eq!(vec!["mesh/cube.ron".to_owned()], files);
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+}
@@ -0,0 +1,328 @@
+use std::{
+    fmt::Write as _,
+    fs,
+    path::Path,
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use parking_lot::Mutex;
+
+use amethyst_error::{format_err, Error, ResultExt};
+
+use crate::Source;
+
+/// Timing of a single asset load, recorded by `LoadDiagnostics`.
+#[derive(Clone, Debug)]
+pub struct AssetTiming {
+    /// Name of the asset type, see `Asset::NAME`.
+    pub asset: &'static str,
+    /// Name of the asset, usually its path.
+    pub name: String,
+    /// Name of the format, see `Format::NAME`.
+    pub format: &'static str,
+    /// Time the request waited in the queue before a worker thread started it, including the
+    /// time waiting for other loads to finish when `Loader::set_max_concurrent_loads` is used.
+    pub queued: Duration,
+    /// Time spent reading from the source.
+    pub io: Duration,
+    /// Time spent in `Format::import`, not counting the time spent reading.
+    pub import: Duration,
+    /// Time spent in `AssetStorage::process` turning the data into the asset.
+    pub processing: Duration,
+    /// Number of bytes read from the source.
+    pub bytes: u64,
+    /// The error if loading failed.
+    pub error: Option<String>,
+}
+
+impl AssetTiming {
+    /// Time spent loading the asset, not counting the time waiting in the queue.
+    pub fn cost(&self) -> Duration {
+        self.io + self.import + self.processing
+    }
+}
+
+/// Resource recording the timing of every asset loaded by the `Loader`.
+///
+/// The `Loader` only records timings after `Loader::set_diagnostics` has been called with a
+/// clone of this resource, which `ApplicationBuilder::with_load_diagnostics` does. Timings are
+/// kept until `clear` is called.
+///
+/// ### Example
+///
+/// ```rust,ignore
+/// let diagnostics = world.read_resource::<LoadDiagnostics>();
+/// diagnostics.write_report("load_report.txt")?;
+/// ```
+#[derive(Clone, Default)]
+pub struct LoadDiagnostics {
+    timings: Arc<Mutex<Vec<AssetTiming>>>,
+}
+
+impl LoadDiagnostics {
+    /// Creates an empty `LoadDiagnostics`.
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// Returns the timings recorded so far, in the order the assets finished loading.
+    pub fn timings(&self) -> Vec<AssetTiming> {
+        self.timings.lock().clone()
+    }
+
+    /// Returns the number of failed loads recorded so far.
+    pub fn num_failed(&self) -> usize {
+        self.timings
+            .lock()
+            .iter()
+            .filter(|t| t.error.is_some())
+            .count()
+    }
+
+    /// Removes all recorded timings.
+    pub fn clear(&self) {
+        self.timings.lock().clear();
+    }
+
+    /// Formats a report of all recorded loads, sorted by cost with the most expensive first.
+    pub fn report(&self) -> String {
+        let mut timings = self.timings();
+        timings.sort_by(|a, b| b.cost().cmp(&a.cost()));
+        let total = timings.iter().map(AssetTiming::cost).sum::<Duration>();
+        let failed = timings.iter().filter(|t| t.error.is_some()).count();
+
+        let mut report = String::new();
+        let _ = writeln!(
+            report,
+            "Loaded {} assets ({} failed) in {:.2} ms",
+            timings.len(),
+            failed,
+            millis(total),
+        );
+        let _ = writeln!(
+            report,
+            "{:>10} {:>10} {:>10} {:>10} {:>10} {:>10}  {:<16} {:<12} name",
+            "cost ms", "queued ms", "io ms", "import ms", "process ms", "bytes", "asset", "format",
+        );
+        for timing in &timings {
+            let _ = write!(
+                report,
+                "{:>10.2} {:>10.2} {:>10.2} {:>10.2} {:>10.2} {:>10}  {:<16} {:<12} {}",
+                millis(timing.cost()),
+                millis(timing.queued),
+                millis(timing.io),
+                millis(timing.import),
+                millis(timing.processing),
+                timing.bytes,
+                timing.asset,
+                timing.format,
+                timing.name,
+            );
+            if let Some(ref error) = timing.error {
+                let _ = write!(report, " (failed: {})", error);
+            }
+            report.push('\n');
+        }
+        report
+    }
+
+    /// Writes the `report` to a file.
+    pub fn write_report<P: AsRef<Path>>(&self, path: P) -> Result<(), Error> {
+        let path = path.as_ref();
+        fs::write(path, self.report())
+            .with_context(|_| format_err!("Failed writing load report to {:?}", path))?;
+        Ok(())
+    }
+
+    fn record(&self, timing: AssetTiming) {
+        self.timings.lock().push(timing);
+    }
+}
+
+fn millis(duration: Duration) -> f64 {
+    duration.as_secs() as f64 * 1000.0 + f64::from(duration.subsec_nanos()) / 1_000_000.0
+}
+
+/// Timing of an asset which hasn't finished loading yet.
+pub(crate) struct PendingTiming {
+    diagnostics: LoadDiagnostics,
+    timing: AssetTiming,
+}
+
+impl PendingTiming {
+    pub(crate) fn new(diagnostics: LoadDiagnostics, timing: AssetTiming) -> Self {
+        PendingTiming {
+            diagnostics,
+            timing,
+        }
+    }
+
+    pub(crate) fn add_processing(&mut self, duration: Duration) {
+        self.timing.processing += duration;
+    }
+
+    /// Records the timing, with the error if loading failed.
+    pub(crate) fn finish(mut self, error: Option<String>) {
+        self.timing.error = error;
+        self.diagnostics.record(self.timing);
+    }
+}
+
+/// `Source` measuring the time spent reading and the number of bytes read.
+pub(crate) struct TimingSource {
+    source: Arc<dyn Source>,
+    read: Mutex<(Duration, u64)>,
+}
+
+impl TimingSource {
+    pub(crate) fn new(source: Arc<dyn Source>) -> Self {
+        TimingSource {
+            source,
+            read: Mutex::new((Duration::default(), 0)),
+        }
+    }
+
+    /// Returns the time spent reading and the number of bytes read.
+    pub(crate) fn read(&self) -> (Duration, u64) {
+        *self.read.lock()
+    }
+
+    fn measure<T, F>(&self, f: F, bytes: fn(&T) -> u64) -> Result<T, Error>
+    where
+        F: FnOnce(&dyn Source) -> Result<T, Error>,
+    {
+        let start = Instant::now();
+        let result = f(&*self.source);
+        let mut read = self.read.lock();
+        read.0 += start.elapsed();
+        if let Ok(ref value) = result {
+            read.1 += bytes(value);
+        }
+        result
+    }
+}
+
+impl Source for TimingSource {
+    fn modified(&self, path: &str) -> Result<u64, Error> {
+        self.measure(|s| s.modified(path), |_| 0)
+    }
+
+    fn load(&self, path: &str) -> Result<Vec<u8>, Error> {
+        self.measure(|s| s.load(path), |bytes| bytes.len() as u64)
+    }
+
+    fn size(&self, path: &str) -> Option<u64> {
+        self.source.size(path)
+    }
+
+    fn load_with_metadata(&self, path: &str) -> Result<(Vec<u8>, u64), Error> {
+        self.measure(
+            |s| s.load_with_metadata(path),
+            |(bytes, _)| bytes.len() as u64,
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{sync::Arc, thread, time::Duration};
+
+    use rayon::ThreadPoolBuilder;
+
+    use amethyst_error::Error;
+
+    use crate::{
+        test_util::{Files, Text, Utf8},
+        AssetStorage, Completion, Loader, ProcessingState, ProgressCounter,
+    };
+
+    use super::{AssetTiming, LoadDiagnostics};
+
+    fn timing(name: &str, millis: u64, error: Option<&str>) -> AssetTiming {
+        AssetTiming {
+            asset: "Mesh",
+            name: name.to_owned(),
+            format: "OBJ",
+            queued: Duration::from_millis(100),
+            io: Duration::from_millis(millis),
+            import: Duration::from_millis(millis),
+            processing: Duration::default(),
+            bytes: 0,
+            error: error.map(str::to_owned),
+        }
+    }
+
+    #[test]
+    fn report_sorts_by_cost() {
+        let diagnostics = LoadDiagnostics::new();
+        diagnostics.record(timing("small.obj", 1, None));
+        diagnostics.record(timing("broken.obj", 2, Some("invalid face")));
+        diagnostics.record(timing("large.obj", 50, None));
+
+        assert_eq!(1, diagnostics.num_failed());
+        let report = diagnostics.report();
+        let names = report
+            .lines()
+            .skip(2)
+            .map(|line| line.split_whitespace().nth(8).unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(names, ["large.obj", "broken.obj", "small.obj"]);
+        assert!(report.starts_with("Loaded 3 assets (1 failed) in 106.00 ms"));
+    }
+
+    #[test]
+    fn loader_records_timings() {
+        let pool = Arc::new(ThreadPoolBuilder::new().num_threads(1).build().unwrap());
+        let files = Files::new(vec![("a.txt", "hello"), ("broken.txt", "broken")]);
+        let diagnostics = LoadDiagnostics::new();
+        let mut loader = Loader::with_default_source(files, pool.clone());
+        loader.set_diagnostics(diagnostics.clone());
+        let mut storage = AssetStorage::<Text>::new();
+
+        let mut progress = ProgressCounter::new();
+        let _handles = ["a.txt", "broken.txt", "missing.txt"]
+            .iter()
+            .map(|name| loader.load_from(*name, Utf8, (), "", &mut progress, &storage))
+            .collect::<Vec<_>>();
+        for frame in 0..1000 {
+            storage.process(
+                |text| {
+                    thread::sleep(Duration::from_millis(5));
+                    if text == "broken" {
+                        Err(Error::from_string("Broken text"))
+                    } else {
+                        Ok(ProcessingState::Loaded(text))
+                    }
+                },
+                frame,
+                &pool,
+                None,
+            );
+            if progress.complete() != Completion::Loading {
+                break;
+            }
+            thread::sleep(Duration::from_millis(1));
+        }
+        assert_eq!(Completion::Failed, progress.complete());
+
+        let mut timings = diagnostics.timings();
+        timings.sort_by(|a, b| a.name.cmp(&b.name));
+        let names = timings.iter().map(|t| t.name.as_str()).collect::<Vec<_>>();
+        assert_eq!(names, ["a.txt", "broken.txt", "missing.txt"]);
+        assert!(timings.iter().all(|t| t.asset == "test::Text"));
+        assert!(timings.iter().all(|t| t.format == "Utf8"));
+
+        let (loaded, broken, missing) = (&timings[0], &timings[1], &timings[2]);
+        assert!(loaded.error.is_none());
+        assert_eq!(5, loaded.bytes);
+        assert!(loaded.processing >= Duration::from_millis(5));
+        assert!(broken.error.as_ref().unwrap().contains("broken.txt"));
+        assert_eq!(6, broken.bytes);
+        assert!(broken.processing >= Duration::from_millis(5));
+        assert!(missing.error.is_some());
+        assert_eq!(0, missing.bytes);
+        assert!(missing.processing < Duration::from_millis(5));
+        assert_eq!(2, diagnostics.num_failed());
+    }
+}
@@ -1,4 +1,5 @@
 use err_derive::Error;
+use uuid::Uuid;
 
 #[derive(Debug, Error)]
 pub enum Error {
@@ -10,6 +11,8 @@ pub enum Error {
     Format(&'static str),
     #[error(display = "Asset was loaded but no handle to it was saved.")]
     UnusedHandle,
+    #[error(display = "No asset with id {} in the manifest", _0)]
+    UnknownId(Uuid),
     #[error(display = "Some error has occurred")]
     #[doc(hidden)]
     __Nonexhaustive,
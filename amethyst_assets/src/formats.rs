@@ -1,6 +1,6 @@
-use serde::{Deserialize, Serialize};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
 
-use crate::{Asset, SimpleFormat};
+use crate::{ArtifactFormat, Asset, SimpleFormat};
 use amethyst_error::{format_err, Error, ResultExt};
 
 /// Format for loading from Ron files.
@@ -28,6 +28,14 @@ where
     }
 }
 
+impl<T> ArtifactFormat<T> for RonFormat
+where
+    T: Asset,
+    T::Data: Serialize + DeserializeOwned,
+{
+    const VERSION: u32 = 1;
+}
+
 /// Format for loading from Json files.
 #[cfg(feature = "json")]
 #[derive(Default, Clone, Debug)]
@@ -53,3 +61,12 @@ where
         Ok(val)
     }
 }
+
+#[cfg(feature = "json")]
+impl<T> ArtifactFormat<T> for JsonFormat
+where
+    T: Asset,
+    T::Data: Serialize + DeserializeOwned,
+{
+    const VERSION: u32 = 1;
+}
@@ -0,0 +1,251 @@
+use std::sync::{
+    atomic::{AtomicUsize, Ordering},
+    Arc,
+};
+
+use fnv::FnvHashMap;
+use parking_lot::RwLock;
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+
+use amethyst_error::{format_err, Error, ResultExt};
+
+use crate::{Asset, SimpleFormat, Source};
+
+/// A procedural recipe producing asset data from parameters, e.g. a noise texture or a
+/// generated mesh.
+///
+/// Generators are loaded through the `Loader` with a `GeneratorFormat`, which reads the
+/// parameters like any other asset file. The parameters can either be stored in RON files, or
+/// be set from code using a `GeneratorSource`.
+pub trait Generator<A: Asset>: Clone + Send + Sync + 'static {
+    /// A unique identifier for this generator.
+    const NAME: &'static str;
+
+    /// Parameters of the recipe.
+    type Params: Serialize + DeserializeOwned;
+
+    /// Produces asset data from the parameters.
+    fn generate(&self, params: Self::Params) -> Result<A::Data, Error>;
+}
+
+/// Format loading assets by running a `Generator` with the parameters read from the source.
+///
+/// Since the asset is loaded by name like a file, it is cached, named and reloaded like any other
+/// asset: changing the parameters, either by editing the parameter file or with
+/// `GeneratorSource::set`, regenerates the asset when hot reloading is enabled.
+///
+/// In a prefab, the generator is written in place of the format:
+///
+/// ```rust,ignore
+/// mesh: File("terrain/island.ron", TerrainGenerator(detail: 4), ()),
+/// ```
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+#[serde(transparent)]
+pub struct GeneratorFormat<G> {
+    generator: G,
+}
+
+impl<G> GeneratorFormat<G> {
+    /// Creates a format running `generator`.
+    pub fn new(generator: G) -> Self {
+        GeneratorFormat { generator }
+    }
+}
+
+impl<A, G> SimpleFormat<A> for GeneratorFormat<G>
+where
+    A: Asset,
+    G: Generator<A>,
+{
+    const NAME: &'static str = G::NAME;
+    type Options = ();
+
+    fn import(&self, bytes: Vec<u8>, _: ()) -> Result<A::Data, Error> {
+        let params = ron::de::from_bytes(&bytes)
+            .with_context(|_| format_err!("Failed parsing parameters of generator {}", G::NAME))?;
+        self.generator.generate(params)
+    }
+}
+
+/// Source holding the parameters of generated assets in memory.
+///
+/// Each name is assigned parameters with `set`, and the asset is loaded by that name from this
+/// source using a `GeneratorFormat`. Setting new parameters changes the modification time
+/// reported for the name, so the asset is regenerated by hot reloading.
+///
+/// Names without parameters are loaded from the fallback source, if there is one. This allows
+/// using a `GeneratorSource` as the default source of the `Loader`, so generated assets can be
+/// referenced from prefabs like files.
+///
+/// The source is cheap to clone, clones share the parameters. Keep a clone after adding the
+/// source to the `Loader` to change the parameters later.
+///
+/// ### Example
+///
+/// ```rust,ignore
+/// let generated = GeneratorSource::new();
+/// generated.set::<Texture, _>("noise", &NoiseGenerator, NoiseParams { octaves: 4 })?;
+/// loader.add_source("generated", generated.clone());
+///
+/// let handle = loader.load_from(
+///     "noise",
+///     GeneratorFormat::new(NoiseGenerator),
+///     (),
+///     "generated",
+///     (),
+///     &storage,
+/// );
+/// ```
+#[derive(Clone, Default)]
+pub struct GeneratorSource {
+    inner: Arc<GeneratorSourceInner>,
+}
+
+#[derive(Default)]
+struct GeneratorSourceInner {
+    recipes: RwLock<FnvHashMap<String, Recipe>>,
+    version: AtomicUsize,
+    fallback: Option<Arc<dyn Source>>,
+}
+
+struct Recipe {
+    params: Vec<u8>,
+    version: u64,
+}
+
+impl GeneratorSource {
+    /// Creates an empty source without a fallback.
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// Creates an empty source, loading all names without parameters from `fallback`.
+    pub fn with_fallback<S>(fallback: S) -> Self
+    where
+        S: Source,
+    {
+        GeneratorSource {
+            inner: Arc::new(GeneratorSourceInner {
+                fallback: Some(Arc::new(fallback)),
+                ..Default::default()
+            }),
+        }
+    }
+
+    /// Sets the parameters of the generated asset `name`.
+    ///
+    /// The generator is only used to select the parameter type, the asset is generated when it
+    /// is loaded with a `GeneratorFormat`.
+    pub fn set<A, G>(&self, name: &str, _: &G, params: G::Params) -> Result<(), Error>
+    where
+        A: Asset,
+        G: Generator<A>,
+    {
+        let params = ron::ser::to_string(&params)
+            .with_context(|_| format_err!("Failed serializing parameters of {:?}", name))?;
+        let version = self.inner.version.fetch_add(1, Ordering::Relaxed) as u64 + 1;
+        self.inner.recipes.write().insert(
+            name.to_owned(),
+            Recipe {
+                params: params.into_bytes(),
+                version,
+            },
+        );
+        Ok(())
+    }
+
+    /// Removes the parameters of `name`, returning whether there were any.
+    pub fn remove(&self, name: &str) -> bool {
+        self.inner.recipes.write().remove(name).is_some()
+    }
+
+    /// Returns `true` if parameters were set for `name`.
+    pub fn contains(&self, name: &str) -> bool {
+        self.inner.recipes.read().contains_key(name)
+    }
+
+    fn fallback(&self, path: &str) -> Result<&Arc<dyn Source>, Error> {
+        match self.inner.fallback {
+            Some(ref fallback) => Ok(fallback),
+            None => Err(format_err!("No generator parameters set for {:?}", path)),
+        }
+    }
+}
+
+impl Source for GeneratorSource {
+    fn modified(&self, path: &str) -> Result<u64, Error> {
+        if let Some(recipe) = self.inner.recipes.read().get(path) {
+            return Ok(recipe.version);
+        }
+        self.fallback(path)?.modified(path)
+    }
+
+    fn load(&self, path: &str) -> Result<Vec<u8>, Error> {
+        if let Some(recipe) = self.inner.recipes.read().get(path) {
+            return Ok(recipe.params.clone());
+        }
+        self.fallback(path)?.load(path)
+    }
+
+    fn size(&self, path: &str) -> Option<u64> {
+        if let Some(recipe) = self.inner.recipes.read().get(path) {
+            return Some(recipe.params.len() as u64);
+        }
+        self.inner
+            .fallback
+            .as_ref()
+            .and_then(|fallback| fallback.size(path))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use amethyst_error::Error;
+
+    use crate::{test_util::Text, Format, FormatValue, Source};
+
+    use super::{Generator, GeneratorFormat, GeneratorSource};
+
+    /// Repeats a string `n` times.
+    #[derive(Clone)]
+    struct Repeat;
+
+    impl Generator<Text> for Repeat {
+        const NAME: &'static str = "Repeat";
+        type Params = (String, usize);
+
+        fn generate(&self, (text, n): (String, usize)) -> Result<String, Error> {
+            Ok(text.repeat(n))
+        }
+    }
+
+    fn generate(source: &GeneratorSource) -> Result<String, Error> {
+        let value: FormatValue<Text> = GeneratorFormat::new(Repeat).import(
+            "text".into(),
+            Arc::new(source.clone()),
+            (),
+            false,
+        )?;
+        Ok(value.data)
+    }
+
+    #[test]
+    fn regenerates_with_new_params() {
+        let source = GeneratorSource::new();
+        assert!(generate(&source).is_err());
+
+        source
+            .set::<Text, _>("text", &Repeat, ("a".to_owned(), 2))
+            .unwrap();
+        let modified = source.modified("text").unwrap();
+        assert_eq!("aa", generate(&source).unwrap());
+
+        source
+            .set::<Text, _>("text", &Repeat, ("b".to_owned(), 3))
+            .unwrap();
+        assert_ne!(modified, source.modified("text").unwrap());
+        assert_eq!("bbb", generate(&source).unwrap());
+    }
+}
@@ -12,26 +12,48 @@
 #[cfg(feature = "json")]
 pub use crate::formats::JsonFormat;
 pub use crate::{
+    artifact::{ArtifactCache, ArtifactFormat, AssetPipeline, CachedFormat, PipelineReport},
     asset::{Asset, Format, FormatValue, SimpleFormat},
     cache::Cache,
+    diagnostics::{AssetTiming, LoadDiagnostics},
     formats::RonFormat,
+    generator::{Generator, GeneratorFormat, GeneratorSource},
     helper::AssetLoaderSystemData,
     loader::Loader,
-    prefab::{AssetPrefab, Prefab, PrefabData, PrefabLoader, PrefabLoaderSystem},
-    progress::{Completion, Progress, ProgressCounter, Tracker},
+    localized::{localized_paths, AssetLanguages, LocalizedFormat},
+    manifest::AssetManifest,
+    memory::AssetSize,
+    meta::{meta_path, MetaFormat},
+    prefab::{
+        AssetPrefab, ExtractPrefabData, Prefab, PrefabData, PrefabExtractor, PrefabLoader,
+        PrefabLoaderSystem, PrefabReference, PrefabVariantFormat,
+    },
+    progress::{Completion, LoadDetails, LoadStage, Progress, ProgressCounter, Tracker},
+    queue::Priority,
     reload::{HotReloadBundle, HotReloadStrategy, HotReloadSystem, Reload, SingleFile},
     source::{Directory, Source},
     storage::{AssetStorage, Handle, ProcessingState, Processor, WeakHandle},
 };
+pub use uuid::Uuid;
 
+mod artifact;
 mod asset;
 mod cache;
+mod diagnostics;
 mod error;
 mod formats;
+mod generator;
 mod helper;
 mod loader;
+mod localized;
+mod manifest;
+mod memory;
+mod meta;
 mod prefab;
 mod progress;
+mod queue;
 mod reload;
 mod source;
 mod storage;
+#[cfg(test)]
+mod test_util;
@@ -1,23 +1,33 @@
-use std::{borrow::Borrow, hash::Hash, path::PathBuf, sync::Arc};
+use std::{any::TypeId, borrow::Borrow, hash::Hash, path::PathBuf, sync::Arc, time::Instant};
 
 use fnv::FnvHashMap;
 use log::debug;
 use rayon::ThreadPool;
+use serde::{de::DeserializeOwned, Serialize};
+use uuid::Uuid;
 
-use amethyst_error::ResultExt;
+use amethyst_error::{Error as AmethystError, ResultExt};
 #[cfg(feature = "profiler")]
 use thread_profiler::profile_scope;
 
 use crate::{
+    diagnostics::{AssetTiming, PendingTiming, TimingSource},
     error::Error,
-    storage::{AssetStorage, Handle, Processed},
-    Asset, Directory, Format, FormatValue, Progress, Source,
+    progress::{LoadStage, ReportingSource},
+    queue::{LoadQueue, Priority},
+    storage::{AssetStorage, Handle, Processed, DATA_NAME},
+    ArtifactCache, ArtifactFormat, Asset, AssetManifest, CachedFormat, Directory, Format,
+    FormatValue, LoadDiagnostics, MetaFormat, Progress, Source,
 };
 
 /// The asset loader, holding the sources and a reference to the `ThreadPool`.
 pub struct Loader {
+    artifacts: Option<Arc<ArtifactCache>>,
+    diagnostics: Option<LoadDiagnostics>,
     hot_reload: bool,
+    manifest: AssetManifest,
     pool: Arc<ThreadPool>,
+    queue: Arc<LoadQueue>,
     sources: FnvHashMap<String, Arc<dyn Source>>,
 }
 
@@ -37,8 +47,12 @@ impl Loader {
         S: Source,
     {
         let mut loader = Loader {
+            artifacts: None,
+            diagnostics: None,
             hot_reload: true,
+            manifest: AssetManifest::new(),
             pool,
+            queue: Default::default(),
             sources: Default::default(),
         };
 
@@ -73,6 +87,69 @@ impl Loader {
         self.hot_reload = value;
     }
 
+    /// Sets the `ArtifactCache` used by `load_cached` and `load_from_cached`.
+    pub fn set_artifact_cache(&mut self, cache: ArtifactCache) {
+        self.artifacts = Some(Arc::new(cache));
+    }
+
+    /// Returns the `ArtifactCache` of this `Loader`, if one was set.
+    pub fn artifact_cache(&self) -> Option<&Arc<ArtifactCache>> {
+        self.artifacts.as_ref()
+    }
+
+    /// Records the timing of all assets loaded from now on in `diagnostics`.
+    pub fn set_diagnostics(&mut self, diagnostics: LoadDiagnostics) {
+        self.diagnostics = Some(diagnostics);
+    }
+
+    /// Returns the `LoadDiagnostics` of this `Loader`, if they were set.
+    pub fn diagnostics(&self) -> Option<&LoadDiagnostics> {
+        self.diagnostics.as_ref()
+    }
+
+    /// Limits the number of assets loaded at the same time. `None` removes the limit.
+    ///
+    /// This is a cap on whole loads, not only on reading from the sources: a load holds its slot
+    /// while reading and while decoding, since formats interleave both. Pending loads wait in
+    /// the queue until a slot is free, so no thread of the `ThreadPool` is blocked waiting.
+    ///
+    /// It can be used to avoid thrashing a hard disk when streaming many assets, at the cost of
+    /// also limiting how many assets are decoded at the same time.
+    pub fn set_max_concurrent_loads(&mut self, limit: Option<usize>) {
+        LoadQueue::set_limit(&self.queue, &self.pool, limit);
+    }
+
+    /// Changes the priority of a load request which hasn't been started yet.
+    ///
+    /// Returns `false` if there is no pending load for `handle`, e.g. because
+    /// a worker thread already started loading it.
+    pub fn reprioritize<A: Asset>(&self, handle: &Handle<A>, priority: Priority) -> bool {
+        self.queue
+            .reprioritize((TypeId::of::<A>(), handle.id()), priority)
+    }
+
+    /// Returns the number of load requests which haven't been started yet.
+    pub fn num_pending(&self) -> usize {
+        self.queue.len()
+    }
+
+    /// Sets the `AssetManifest` used to resolve asset ids in `load_by_id`.
+    pub fn set_manifest(&mut self, manifest: AssetManifest) {
+        self.manifest = manifest;
+    }
+
+    /// Loads the `AssetManifest` with the given name from the default source,
+    /// and sets it as the manifest of this `Loader`.
+    pub fn load_manifest(&mut self, name: &str) -> Result<(), AmethystError> {
+        self.manifest = AssetManifest::load(&*self.source(""), name)?;
+        Ok(())
+    }
+
+    /// Returns the `AssetManifest` of this `Loader`.
+    pub fn manifest(&self) -> &AssetManifest {
+        &self.manifest
+    }
+
     /// Loads an asset with a given format from the default (directory) source.
     /// If you want to load from a custom source instead, use `load_from`.
     ///
@@ -106,17 +183,76 @@ impl Loader {
     /// * `format`: A format struct which loads bytes from a `source` and produces `Asset::Data`
     ///   with them
     /// * `options`: Additional parameter to `format` to configure how exactly the data will
-    ///   be created. This could e.g. be mipmap levels for textures.
+    ///   be created. This could e.g. be mipmap levels for textures. To read them from a
+    ///   sidecar file next to the asset instead, use `load_with_meta`.
     /// * `source`: An identifier for a source which has previously been added using `with_source`
     /// * `progress`: A tracker which will be notified of assets which have been imported
     /// * `storage`: The asset storage which can be fetched from the ECS `World` using
     ///   `read_resource`.
+    ///
+    /// The request uses `Priority::Normal`, see `load_from_prioritized` for details.
     pub fn load_from<A, F, N, P, S>(
         &self,
         name: N,
         format: F,
         options: F::Options,
         source: &S,
+        progress: P,
+        storage: &AssetStorage<A>,
+    ) -> Handle<A>
+    where
+        A: Asset,
+        F: Format<A> + 'static,
+        N: Into<String>,
+        P: Progress,
+        S: AsRef<str> + Eq + Hash + ?Sized,
+        String: Borrow<S>,
+    {
+        self.load_from_prioritized(
+            name,
+            format,
+            options,
+            source,
+            Priority::default(),
+            progress,
+            storage,
+        )
+    }
+
+    /// Like `load`, but with a given `Priority`.
+    ///
+    /// See `load_from_prioritized` for more information.
+    pub fn load_prioritized<A, F, N, P>(
+        &self,
+        name: N,
+        format: F,
+        options: F::Options,
+        priority: Priority,
+        progress: P,
+        storage: &AssetStorage<A>,
+    ) -> Handle<A>
+    where
+        A: Asset,
+        F: Format<A>,
+        N: Into<String>,
+        P: Progress,
+    {
+        self.load_from_prioritized(name, format, options, "", priority, progress, storage)
+    }
+
+    /// Like `load_from`, but with a given `Priority`.
+    ///
+    /// Load requests are queued and started by the worker threads in order of their
+    /// priority; the priority of a pending request can be changed with `reprioritize`.
+    /// If all handles to the asset are dropped before a worker started loading it,
+    /// the request is cancelled and reported to the `Tracker` with `Tracker::cancel`.
+    pub fn load_from_prioritized<A, F, N, P, S>(
+        &self,
+        name: N,
+        format: F,
+        options: F::Options,
+        source: &S,
+        priority: Priority,
         mut progress: P,
         storage: &AssetStorage<A>,
     ) -> Handle<A>
@@ -156,29 +292,233 @@ impl Loader {
         let tracker = progress.create_tracker();
 
         let source = self.source(source);
-        let handle_clone = handle.clone();
+        let id = handle.id();
+        // Only keep a weak handle until the job starts, so we notice if the asset is dropped.
+        let weak_handle = handle.downgrade();
         let processed = storage.processed.clone();
 
         let hot_reload = self.hot_reload;
+        let diagnostics = self.diagnostics.clone();
+        let queued_at = Instant::now();
 
         let cl = move || {
             #[cfg(feature = "profiler")]
             profile_scope!("load_asset_from_worker");
+            let started = Instant::now();
+            let tracker = Box::new(tracker) as Box<dyn Tracker>;
+            let handle = match weak_handle.upgrade() {
+                Some(handle) => handle,
+                None => {
+                    debug!(
+                        "{:?}: Cancelled loading asset {:?} (handle id: {:?}), it is not used anymore",
+                        A::NAME,
+                        name,
+                        id,
+                    );
+                    tracker.cancel(id, A::NAME, name);
+                    processed.push(Processed::Cancelled { id });
+                    return;
+                }
+            };
+
+            let reporting = tracker.details().map(|details| {
+                details.stage(LoadStage::Reading);
+                Arc::new(ReportingSource::new(source.clone(), details))
+            });
+            let import_source = match reporting {
+                Some(ref reporting) => reporting.clone() as Arc<dyn Source>,
+                None => source,
+            };
+            let timing_source = diagnostics
+                .as_ref()
+                .map(|_| Arc::new(TimingSource::new(import_source.clone())));
+            let import_source = match timing_source {
+                Some(ref timing_source) => timing_source.clone() as Arc<dyn Source>,
+                None => import_source,
+            };
+
             let data = format
-                .import(name.clone(), source, options, hot_reload)
+                .import(name.clone(), import_source, options, hot_reload)
                 .with_context(|_| Error::Format(F::NAME));
-            let tracker = Box::new(tracker) as Box<dyn Tracker>;
+
+            let timing = match (diagnostics, timing_source) {
+                (Some(diagnostics), Some(timing_source)) => {
+                    let (io, bytes) = timing_source.read();
+                    let timing = AssetTiming {
+                        asset: A::NAME,
+                        name: name.clone(),
+                        format: F::NAME,
+                        queued: started.duration_since(queued_at),
+                        io,
+                        import: started.elapsed().checked_sub(io).unwrap_or_default(),
+                        processing: Default::default(),
+                        bytes,
+                        error: None,
+                    };
+                    Some(PendingTiming::new(diagnostics, timing))
+                }
+                _ => None,
+            };
+
+            if let Some(reporting) = reporting {
+                reporting.finish();
+            }
+            if let Some(details) = tracker.details() {
+                details.stage(LoadStage::Processing);
+            }
 
             processed.push(Processed::NewAsset {
                 data,
                 handle,
                 name,
                 tracker,
+                timing,
             });
         };
-        self.pool.spawn(cl);
+        let key = (TypeId::of::<A>(), id);
+        LoadQueue::push(&self.queue, &self.pool, priority, key, cl);
+
+        handle
+    }
+
+    /// Like `load`, but reads the options from a sidecar file next to the asset if it exists.
+    ///
+    /// See `load_from_with_meta` for more information.
+    pub fn load_with_meta<A, F, N, P>(
+        &self,
+        name: N,
+        format: F,
+        options: F::Options,
+        progress: P,
+        storage: &AssetStorage<A>,
+    ) -> Handle<A>
+    where
+        A: Asset,
+        F: Format<A> + Clone + Sync,
+        F::Options: DeserializeOwned + Clone + Sync,
+        N: Into<String>,
+        P: Progress,
+    {
+        self.load_from_with_meta::<A, F, _, _, _>(name, format, options, "", progress, storage)
+    }
+
+    /// Like `load_from`, but reads the options from a sidecar file next to the asset if it
+    /// exists, e.g. `hero.png.meta.ron` for `hero.png`. `options` are used otherwise.
+    ///
+    /// This wraps `format` in a `MetaFormat`, see there for details.
+    pub fn load_from_with_meta<A, F, N, P, S>(
+        &self,
+        name: N,
+        format: F,
+        options: F::Options,
+        source: &S,
+        progress: P,
+        storage: &AssetStorage<A>,
+    ) -> Handle<A>
+    where
+        A: Asset,
+        F: Format<A> + Clone + Sync,
+        F::Options: DeserializeOwned + Clone + Sync,
+        N: Into<String>,
+        P: Progress,
+        S: AsRef<str> + Eq + Hash + ?Sized,
+        String: Borrow<S>,
+    {
+        let format = MetaFormat::new(format);
+        self.load_from(name, format, options, source, progress, storage)
+    }
+
+    /// Like `load`, but prefers an up-to-date artifact from the `ArtifactCache`.
+    ///
+    /// See `load_from_cached` for more information.
+    pub fn load_cached<A, F, N, P>(
+        &self,
+        name: N,
+        format: F,
+        options: F::Options,
+        progress: P,
+        storage: &AssetStorage<A>,
+    ) -> Handle<A>
+    where
+        A: Asset,
+        A::Data: Serialize + DeserializeOwned,
+        F: ArtifactFormat<A> + Clone + Sync,
+        F::Options: Serialize + Clone + Sync,
+        N: Into<String>,
+        P: Progress,
+    {
+        self.load_from_cached::<A, F, _, _, _>(name, format, options, "", progress, storage)
+    }
+
+    /// Like `load_from`, but prefers an up-to-date artifact from the `ArtifactCache`
+    /// set with `set_artifact_cache` over importing the asset with `format`.
+    ///
+    /// If the artifact is missing or outdated, the asset is imported as usual and
+    /// the result is written to the cache. Without an `ArtifactCache`, this is
+    /// the same as `load_from`.
+    pub fn load_from_cached<A, F, N, P, S>(
+        &self,
+        name: N,
+        format: F,
+        options: F::Options,
+        source: &S,
+        progress: P,
+        storage: &AssetStorage<A>,
+    ) -> Handle<A>
+    where
+        A: Asset,
+        A::Data: Serialize + DeserializeOwned,
+        F: ArtifactFormat<A> + Clone + Sync,
+        F::Options: Serialize + Clone + Sync,
+        N: Into<String>,
+        P: Progress,
+        S: AsRef<str> + Eq + Hash + ?Sized,
+        String: Borrow<S>,
+    {
+        match self.artifacts {
+            Some(ref cache) => {
+                let format = CachedFormat::new(format, cache.clone());
+                self.load_from(name, format, options, source, progress, storage)
+            }
+            None => self.load_from(name, format, options, source, progress, storage),
+        }
+    }
+
+    /// Loads the asset with the given id from the default source, looking up its path
+    /// in the `AssetManifest`.
+    ///
+    /// If the manifest doesn't contain the id, loading fails and the error is reported
+    /// to `progress`. See `load_from` for more information.
+    pub fn load_by_id<A, F, P>(
+        &self,
+        id: &Uuid,
+        format: F,
+        options: F::Options,
+        mut progress: P,
+        storage: &AssetStorage<A>,
+    ) -> Handle<A>
+    where
+        A: Asset,
+        F: Format<A>,
+        P: Progress,
+    {
+        match self.manifest.path(id) {
+            Some(name) => self.load(name, format, options, progress, storage),
+            None => {
+                progress.add_assets(1);
+                let tracker = progress.create_tracker();
+                let handle = storage.allocate();
+                storage.processed.push(Processed::NewAsset {
+                    data: Err(Error::UnknownId(*id).into()),
+                    handle: handle.clone(),
+                    name: id.to_string(),
+                    tracker: Box::new(tracker),
+                    timing: None,
+                });
 
-        handle_clone
+                handle
+            }
+        }
     }
 
     /// Load an asset from data and return a handle.
@@ -199,8 +539,9 @@ impl Loader {
         storage.processed.push(Processed::NewAsset {
             data: Ok(FormatValue::data(data)),
             handle: handle.clone(),
-            name: "<Data>".into(),
+            name: DATA_NAME.into(),
             tracker,
+            timing: None,
         });
 
         handle
@@ -0,0 +1,332 @@
+//! Language-specific variants of assets.
+
+use std::sync::{
+    atomic::{AtomicUsize, Ordering},
+    Arc,
+};
+
+use parking_lot::RwLock;
+
+#[cfg(feature = "profiler")]
+use thread_profiler::profile_scope;
+
+use amethyst_error::Error;
+
+use crate::{Asset, Format, FormatValue, Reload, Source};
+
+/// Returns the paths of the variants of the asset `name` for `language`, in the order they are
+/// looked up.
+///
+/// For `"voice/intro.ogg"` and `"ja"` these are `"voice/intro.ja.ogg"` and
+/// `"ja/voice/intro.ogg"`.
+pub fn localized_paths(name: &str, language: &str) -> [String; 2] {
+    let file_start = name.rfind('/').map(|i| i + 1).unwrap_or(0);
+    let suffixed = match name[file_start..].rfind('.') {
+        Some(i) if i > 0 => {
+            let (stem, extension) = name.split_at(file_start + i);
+            format!("{}.{}{}", stem, language, extension)
+        }
+        _ => format!("{}.{}", name, language),
+    };
+    [suffixed, format!("{}/{}", language, name)]
+}
+
+/// Resource holding the languages used to pick the variants of localized assets.
+///
+/// The languages are shared by all clones, so updating the resource affects every
+/// `LocalizedFormat` created from it. `amethyst_locale` keeps it in sync with the fallback chain
+/// of the active language.
+#[derive(Clone, Debug, Default)]
+pub struct AssetLanguages {
+    languages: Arc<RwLock<Vec<String>>>,
+    /// Incremented whenever the languages change.
+    generation: Arc<AtomicUsize>,
+}
+
+impl AssetLanguages {
+    /// Creates an empty list of languages, which always picks the neutral assets.
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// Returns the languages, in order of preference.
+    pub fn languages(&self) -> Vec<String> {
+        self.languages.read().clone()
+    }
+
+    /// Sets the languages, in order of preference.
+    ///
+    /// If they differ from the previous languages, assets which were already loaded with a
+    /// `LocalizedFormat` switch to the variant of the new languages the next time their storage
+    /// is processed.
+    pub fn set_languages<I, S>(&self, languages: I)
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        let languages = languages.into_iter().map(Into::into).collect::<Vec<_>>();
+        let mut current = self.languages.write();
+        if *current != languages {
+            *current = languages;
+            self.generation.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    fn generation(&self) -> usize {
+        self.generation.load(Ordering::Relaxed)
+    }
+
+    /// Returns the path of the first variant of `name` which exists in `source`, or `name` itself
+    /// if there is no variant for any of the languages.
+    ///
+    /// A file exists if `Source::modified` succeeds for it.
+    pub fn resolve(&self, name: &str, source: &dyn Source) -> String {
+        self.languages
+            .read()
+            .iter()
+            .flat_map(|language| localized_paths(name, language).to_vec())
+            .find(|path| source.modified(path).is_ok())
+            .unwrap_or_else(|| name.to_owned())
+    }
+}
+
+/// Format loading the variant of an asset for the current language, e.g. voice-over or textures
+/// with text.
+///
+/// For an asset `voice/intro.ogg` and the language `ja`, the variants `voice/intro.ja.ogg` and
+/// `ja/voice/intro.ogg` are tried, then the same for the next language of the `AssetLanguages`,
+/// and finally the neutral `voice/intro.ogg` is loaded.
+///
+/// When the `AssetLanguages` change, the asset is reloaded if another variant should be used,
+/// independent of the `HotReloadStrategy` and without reloading any other asset. Other changes,
+/// like an added variant or a modified file, are only picked up by hot reloading.
+///
+/// ### Example
+///
+/// ```rust,ignore
+/// let languages = world.read_resource::<AssetLanguages>().clone();
+/// let intro = loader.load(
+///     "voice/intro.ogg",
+///     LocalizedFormat::new(OggFormat, languages),
+///     (),
+///     (),
+///     &source_storage,
+/// );
+/// ```
+#[derive(Clone, Debug)]
+pub struct LocalizedFormat<F> {
+    format: F,
+    languages: AssetLanguages,
+}
+
+impl<F> LocalizedFormat<F> {
+    /// Wraps `format`, loading the variants for `languages`.
+    pub fn new(format: F, languages: AssetLanguages) -> Self {
+        LocalizedFormat { format, languages }
+    }
+}
+
+impl<A, F> Format<A> for LocalizedFormat<F>
+where
+    A: Asset,
+    F: Format<A> + Clone + Sync,
+    F::Options: Clone + Sync,
+{
+    const NAME: &'static str = F::NAME;
+    type Options = F::Options;
+
+    fn import(
+        &self,
+        name: String,
+        source: Arc<dyn Source>,
+        options: Self::Options,
+        create_reload: bool,
+    ) -> Result<FormatValue<A>, Error> {
+        #[cfg(feature = "profiler")]
+        profile_scope!("import_localized_asset");
+
+        // Read before resolving, so a concurrent change of the languages is noticed later.
+        let generation = self.languages.generation();
+        let path = self.languages.resolve(&name, &*source);
+        let FormatValue { data, reload } =
+            self.format
+                .import(path.clone(), source.clone(), options.clone(), create_reload)?;
+
+        // Always created, so a change of the languages is noticed without hot reloading.
+        let reload = LocalizedReload {
+            format: self.clone(),
+            name,
+            path,
+            source,
+            options,
+            checked_generation: AtomicUsize::new(generation),
+            inner: reload,
+        };
+
+        Ok(FormatValue {
+            data,
+            reload: Some(Box::new(reload)),
+        })
+    }
+}
+
+/// Reload instructions of `LocalizedFormat`, which reload the asset if another variant should be
+/// used or the loaded variant changed.
+struct LocalizedReload<A: Asset, F: Format<A>> {
+    format: LocalizedFormat<F>,
+    name: String,
+    /// The path of the loaded variant.
+    path: String,
+    source: Arc<dyn Source>,
+    options: F::Options,
+    /// The generation of the `AssetLanguages` last checked by `needs_immediate_reload`.
+    checked_generation: AtomicUsize,
+    /// The reload instructions of the wrapped format, if hot reloading is enabled.
+    inner: Option<Box<dyn Reload<A>>>,
+}
+
+impl<A, F> Clone for LocalizedReload<A, F>
+where
+    A: Asset,
+    F: Clone + Format<A>,
+    F::Options: Clone,
+{
+    fn clone(&self) -> Self {
+        LocalizedReload {
+            format: self.format.clone(),
+            name: self.name.clone(),
+            path: self.path.clone(),
+            source: self.source.clone(),
+            options: self.options.clone(),
+            checked_generation: AtomicUsize::new(self.checked_generation.load(Ordering::Relaxed)),
+            inner: self.inner.clone(),
+        }
+    }
+}
+
+impl<A, F> Reload<A> for LocalizedReload<A, F>
+where
+    A: Asset,
+    F: Format<A> + Clone + Sync,
+    F::Options: Clone + Sync,
+{
+    fn needs_reload(&self) -> bool {
+        self.format.languages.resolve(&self.name, &*self.source) != self.path
+            || self
+                .inner
+                .as_ref()
+                .map(|r| r.needs_reload())
+                .unwrap_or(false)
+    }
+
+    fn needs_immediate_reload(&self) -> bool {
+        let generation = self.format.languages.generation();
+        self.checked_generation.swap(generation, Ordering::Relaxed) != generation
+            && self.format.languages.resolve(&self.name, &*self.source) != self.path
+    }
+
+    fn reloads_immediately(&self) -> bool {
+        true
+    }
+
+    fn name(&self) -> String {
+        self.name.clone()
+    }
+
+    fn format(&self) -> &'static str {
+        F::NAME
+    }
+
+    fn reload(self: Box<Self>) -> Result<FormatValue<A>, Error> {
+        #[cfg(feature = "profiler")]
+        profile_scope!("reload_localized_asset");
+
+        let LocalizedReload {
+            format,
+            name,
+            source,
+            options,
+            ..
+        } = *self;
+
+        format.import(name, source, options, true)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use crate::{
+        test_util::{Files, Text, Utf8},
+        Format, FormatValue, Reload,
+    };
+
+    use super::{localized_paths, AssetLanguages, LocalizedFormat};
+
+    #[test]
+    fn variant_paths() {
+        assert_eq!(
+            localized_paths("voice/intro.ogg", "ja"),
+            ["voice/intro.ja.ogg", "ja/voice/intro.ogg"]
+        );
+        assert_eq!(
+            localized_paths("LICENSE", "ja"),
+            ["LICENSE.ja", "ja/LICENSE"]
+        );
+    }
+
+    #[test]
+    fn loads_first_variant_of_fallback_chain() {
+        let source = Arc::new(Files::named(vec![
+            "intro.txt",
+            "pt/intro.txt",
+            "intro.en.txt",
+        ]));
+        let languages = AssetLanguages::new();
+        let format = LocalizedFormat::new(Utf8, languages.clone());
+        let import = |format: &LocalizedFormat<Utf8>| {
+            let value: FormatValue<Text> = format
+                .import("intro.txt".into(), source.clone(), (), false)
+                .unwrap();
+            value.data
+        };
+
+        assert_eq!("intro.txt", import(&format));
+        languages.set_languages(vec!["pt-BR", "pt", "en"]);
+        assert_eq!("pt/intro.txt", import(&format));
+        languages.set_languages(vec!["ja", "en"]);
+        assert_eq!("intro.en.txt", import(&format));
+    }
+
+    #[test]
+    fn reloads_immediately_only_for_other_variants() {
+        let source = Arc::new(Files::named(vec![
+            "intro.txt",
+            "pt/intro.txt",
+            "intro.en.txt",
+        ]));
+        let languages = AssetLanguages::new();
+        languages.set_languages(vec!["pt", "en"]);
+        let value: FormatValue<Text> = LocalizedFormat::new(Utf8, languages.clone())
+            .import("intro.txt".into(), source, (), false)
+            .unwrap();
+        let reload = value
+            .reload
+            .expect("no reload instructions without hot reloading");
+        assert!(reload.reloads_immediately());
+        assert!(!reload.needs_immediate_reload());
+
+        // The same languages, or other languages picking the same variant
+        languages.set_languages(vec!["pt", "en"]);
+        assert!(!reload.needs_immediate_reload());
+        languages.set_languages(vec!["pt-BR", "pt", "en"]);
+        assert!(!reload.needs_immediate_reload());
+
+        languages.set_languages(vec!["ja", "en"]);
+        assert!(reload.needs_immediate_reload());
+        // Checked once per change
+        assert!(!reload.needs_immediate_reload());
+        assert!(reload.needs_reload());
+    }
+}
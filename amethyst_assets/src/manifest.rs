@@ -0,0 +1,145 @@
+//! Stable identifiers for assets.
+
+use std::collections::BTreeMap;
+
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use amethyst_error::{format_err, Error, ResultExt};
+
+use crate::{error, Source};
+
+/// Maps stable asset identifiers to the paths of the assets.
+///
+/// Referencing assets by `Uuid` instead of their path means that files can be moved or
+/// renamed by updating a single manifest entry (see `rename`), instead of every prefab
+/// pointing to them. The manifest is usually stored as `manifest.ron` in the asset directory
+/// and set on the `Loader` with `Loader::set_manifest`.
+///
+/// ### Example
+///
+/// ```rust,ignore
+/// (
+///     assets: {
+///         "1a7b0e48-5cf0-4b6e-9a51-6d2f1f9f8a31": "texture/hero.png",
+///         "c0e9a5b4-2f8e-4a64-8d6a-44c5e2f2d7a0": "mesh/cube.obj",
+///     },
+/// )
+/// ```
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+pub struct AssetManifest {
+    assets: BTreeMap<Uuid, String>,
+}
+
+impl AssetManifest {
+    /// Creates an empty manifest.
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// Loads a manifest in RON format from `source`.
+    pub fn load(source: &dyn Source, name: &str) -> Result<Self, Error> {
+        let bytes = source.load(name).with_context(|_| error::Error::Source)?;
+        Self::from_ron(&bytes).with_context(|_| format_err!("Failed to load manifest {:?}", name))
+    }
+
+    /// Parses a manifest from RON.
+    pub fn from_ron(bytes: &[u8]) -> Result<Self, Error> {
+        use ron::de::Deserializer;
+        let mut d = Deserializer::from_bytes(bytes)
+            .with_context(|_| format_err!("Failed deserializing Ron file"))?;
+        let val =
+            Self::deserialize(&mut d).with_context(|_| format_err!("Failed parsing Ron file"))?;
+        d.end()
+            .with_context(|_| format_err!("Failed parsing Ron file"))?;
+
+        Ok(val)
+    }
+
+    /// Serializes the manifest to RON.
+    pub fn to_ron(&self) -> Result<String, Error> {
+        use ron::ser::to_string_pretty;
+        to_string_pretty(self, Default::default())
+            .with_context(|_| format_err!("Failed serializing manifest"))
+    }
+
+    /// Returns the path of the asset with the given id.
+    pub fn path(&self, id: &Uuid) -> Option<&str> {
+        self.assets.get(id).map(String::as_str)
+    }
+
+    /// Returns the id of the asset at `path`.
+    pub fn id(&self, path: &str) -> Option<Uuid> {
+        self.assets
+            .iter()
+            .find(|(_, p)| *p == path)
+            .map(|(id, _)| *id)
+    }
+
+    /// Inserts an asset with a given id and returns the previous path (if any).
+    pub fn insert<P: Into<String>>(&mut self, id: Uuid, path: P) -> Option<String> {
+        self.assets.insert(id, path.into())
+    }
+
+    /// Returns the id of the asset at `path`, generating a new one if it has none yet.
+    pub fn assign<P: Into<String>>(&mut self, path: P) -> Uuid {
+        let path = path.into();
+        match self.id(&path) {
+            Some(id) => id,
+            None => {
+                let id = Uuid::new_v4();
+                self.assets.insert(id, path);
+                id
+            }
+        }
+    }
+
+    /// Moves the asset with the given id to `path`.
+    ///
+    /// Returns `false` if there is no asset with that id.
+    pub fn rename<P: Into<String>>(&mut self, id: &Uuid, path: P) -> bool {
+        match self.assets.get_mut(id) {
+            Some(old) => {
+                *old = path.into();
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Removes the asset with the given id and returns its path.
+    pub fn remove(&mut self, id: &Uuid) -> Option<String> {
+        self.assets.remove(id)
+    }
+
+    /// Iterates over all ids and their paths.
+    pub fn iter(&self) -> impl Iterator<Item = (&Uuid, &str)> {
+        self.assets.iter().map(|(id, path)| (id, path.as_str()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::AssetManifest;
+
+    #[test]
+    fn renamed_asset_keeps_its_id() {
+        let mut manifest = AssetManifest::new();
+        let id = manifest.assign("texture/hero.png");
+        assert_eq!(id, manifest.assign("texture/hero.png"));
+
+        assert!(manifest.rename(&id, "texture/characters/hero.png"));
+        assert_eq!(Some("texture/characters/hero.png"), manifest.path(&id));
+        assert_eq!(None, manifest.id("texture/hero.png"));
+    }
+
+    #[test]
+    fn manifest_round_trips_through_ron() {
+        let mut manifest = AssetManifest::new();
+        let id = manifest.assign("mesh/cube.obj");
+
+        let ron = manifest.to_ron().unwrap();
+        let manifest = AssetManifest::from_ron(ron.as_bytes()).unwrap();
+        assert_eq!(Some("mesh/cube.obj"), manifest.path(&id));
+    }
+}
@@ -0,0 +1,107 @@
+//! Memory accounting for asset storages.
+
+use derivative::Derivative;
+use fnv::FnvHashMap;
+
+/// Reports how much memory an asset occupies.
+///
+/// Implementing this trait is optional. It allows an `AssetStorage` to report
+/// the memory used by its assets and to enforce a memory budget, see
+/// `AssetStorage::track_memory` and `AssetStorage::set_memory_budget`.
+pub trait AssetSize {
+    /// Returns the approximate number of bytes this asset occupies,
+    /// including the heap allocations it owns.
+    fn asset_size(&self) -> usize;
+}
+
+/// Keeps track of the size of every asset in a storage.
+#[derive(Derivative)]
+#[derivative(Default(bound = ""))]
+pub(crate) struct MemoryTracker<A> {
+    size_fn: Option<fn(&A) -> usize>,
+    sizes: FnvHashMap<u32, usize>,
+    usage: usize,
+    budget: Option<usize>,
+}
+
+impl<A> MemoryTracker<A> {
+    /// Starts measuring assets using `size_fn`.
+    /// Assets inserted before tracking was enabled are not accounted for.
+    pub fn enable(&mut self, size_fn: fn(&A) -> usize) {
+        self.size_fn = Some(size_fn);
+    }
+
+    pub fn enabled(&self) -> bool {
+        self.size_fn.is_some()
+    }
+
+    pub fn budget(&self) -> Option<usize> {
+        self.budget
+    }
+
+    pub fn set_budget(&mut self, budget: Option<usize>) {
+        self.budget = budget;
+    }
+
+    /// Total size of all tracked assets, in bytes.
+    pub fn usage(&self) -> Option<usize> {
+        self.size_fn.map(|_| self.usage)
+    }
+
+    pub fn size(&self, id: u32) -> Option<usize> {
+        self.sizes.get(&id).cloned()
+    }
+
+    /// Measures `asset` and records it under `id`, replacing the previous size if any.
+    pub fn insert(&mut self, id: u32, asset: &A) {
+        if let Some(size_fn) = self.size_fn {
+            let size = size_fn(asset);
+            self.remove(id);
+            self.sizes.insert(id, size);
+            self.usage += size;
+        }
+    }
+
+    pub fn remove(&mut self, id: u32) {
+        if let Some(size) = self.sizes.remove(&id) {
+            self.usage -= size;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{AssetSize, MemoryTracker};
+
+    struct Blob(usize);
+
+    impl AssetSize for Blob {
+        fn asset_size(&self) -> usize {
+            self.0
+        }
+    }
+
+    #[test]
+    fn tracker_reports_nothing_until_enabled() {
+        let mut tracker = MemoryTracker::<Blob>::default();
+        tracker.insert(0, &Blob(10));
+        assert_eq!(None, tracker.usage());
+        assert_eq!(None, tracker.size(0));
+    }
+
+    #[test]
+    fn tracker_sums_and_replaces_sizes() {
+        let mut tracker = MemoryTracker::<Blob>::default();
+        tracker.enable(Blob::asset_size);
+        tracker.insert(0, &Blob(10));
+        tracker.insert(1, &Blob(5));
+        assert_eq!(Some(15), tracker.usage());
+
+        tracker.insert(0, &Blob(2));
+        assert_eq!(Some(7), tracker.usage());
+
+        tracker.remove(1);
+        assert_eq!(Some(2), tracker.usage());
+        assert_eq!(None, tracker.size(1));
+    }
+}
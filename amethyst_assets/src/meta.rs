@@ -0,0 +1,271 @@
+//! Sidecar files providing the import options of an asset.
+
+use std::sync::Arc;
+
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+
+#[cfg(feature = "profiler")]
+use thread_profiler::profile_scope;
+
+use amethyst_error::{format_err, Error, ResultExt};
+
+use crate::{Asset, Format, FormatValue, Reload, Source};
+
+/// Returns the path of the sidecar file for the asset `name`,
+/// e.g. `"hero.png.meta.ron"` for `"hero.png"`.
+pub fn meta_path(name: &str) -> String {
+    format!("{}.meta.ron", name)
+}
+
+/// Format which reads the options for `F` from a sidecar file next to the asset.
+///
+/// For an asset `hero.png`, the options are deserialized from `hero.png.meta.ron`
+/// if that file exists in the same source; otherwise the options passed to the
+/// `Loader` are used. This allows tweaking e.g. the sampling or mipmaps of a texture
+/// without code changes. When reload instructions are requested, changing, adding or
+/// removing the sidecar file also triggers a hot reload.
+///
+/// `Loader::load_with_meta` wraps the format for you. The `Loader` doesn't look for sidecars
+/// on every `load`, because that requires the options of the format to be deserializable,
+/// which the options of many formats are not, e.g. because they hold callbacks.
+///
+/// To combine this with an `ArtifactCache`, wrap the cached format (`MetaFormat<CachedFormat<F>>`),
+/// so the artifacts are keyed by the options read from the sidecar.
+///
+/// ### Example
+///
+/// ```rust,ignore
+/// let texture = loader.load(
+///     "texture/hero.png",
+///     MetaFormat::new(PngFormat),
+///     TextureMetadata::srgb(),
+///     (),
+///     &texture_storage,
+/// );
+/// ```
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(transparent)]
+pub struct MetaFormat<F> {
+    format: F,
+}
+
+impl<F> MetaFormat<F> {
+    /// Wraps `format`, reading its options from sidecar files.
+    pub fn new(format: F) -> Self {
+        MetaFormat { format }
+    }
+}
+
+impl<A, F> Format<A> for MetaFormat<F>
+where
+    A: Asset,
+    F: Format<A> + Clone + Sync,
+    F::Options: DeserializeOwned + Clone + Sync,
+{
+    const NAME: &'static str = F::NAME;
+    type Options = F::Options;
+
+    fn import(
+        &self,
+        name: String,
+        source: Arc<dyn Source>,
+        options: Self::Options,
+        create_reload: bool,
+    ) -> Result<FormatValue<A>, Error> {
+        #[cfg(feature = "profiler")]
+        profile_scope!("import_asset_with_meta");
+
+        let meta = meta_path(&name);
+        // A sidecar exists if its modification time can be read; errors loading an
+        // existing sidecar fail the import instead of silently using `options`.
+        let (meta_options, meta_modified) = match source.modified(&meta) {
+            Ok(modified) => {
+                let bytes = source.load(&meta).with_context(|_| {
+                    format_err!("Failed to load import options from {:?}", meta)
+                })?;
+                let meta_options =
+                    deserialize_options::<F::Options>(&bytes).with_context(|_| {
+                        format_err!("Failed to read import options from {:?}", meta)
+                    })?;
+                (Some(meta_options), modified)
+            }
+            Err(_) => (None, 0),
+        };
+
+        let FormatValue { data, reload } = self.format.import(
+            name.clone(),
+            source.clone(),
+            meta_options.unwrap_or_else(|| options.clone()),
+            create_reload,
+        )?;
+
+        let reload = if create_reload {
+            let reload = MetaReload {
+                format: self.clone(),
+                name,
+                source,
+                options,
+                inner: reload,
+                meta_modified,
+            };
+            Some(Box::new(reload) as Box<dyn Reload<A>>)
+        } else {
+            None
+        };
+
+        Ok(FormatValue { data, reload })
+    }
+}
+
+fn deserialize_options<O: DeserializeOwned>(bytes: &[u8]) -> Result<O, Error> {
+    use ron::de::Deserializer;
+    let mut d = Deserializer::from_bytes(bytes)
+        .with_context(|_| format_err!("Failed deserializing Ron file"))?;
+    let val = O::deserialize(&mut d).with_context(|_| format_err!("Failed parsing Ron file"))?;
+    d.end()
+        .with_context(|_| format_err!("Failed parsing Ron file"))?;
+
+    Ok(val)
+}
+
+/// Reload instructions of `MetaFormat`, which reload the asset if either the
+/// asset itself or its sidecar file changed.
+struct MetaReload<A: Asset, F: Format<A>> {
+    format: MetaFormat<F>,
+    name: String,
+    source: Arc<dyn Source>,
+    /// The options passed to the `Loader`, used if the sidecar file is removed.
+    options: F::Options,
+    inner: Option<Box<dyn Reload<A>>>,
+    meta_modified: u64,
+}
+
+impl<A, F> Clone for MetaReload<A, F>
+where
+    A: Asset,
+    F: Clone + Format<A>,
+    F::Options: Clone,
+{
+    fn clone(&self) -> Self {
+        MetaReload {
+            format: self.format.clone(),
+            name: self.name.clone(),
+            source: self.source.clone(),
+            options: self.options.clone(),
+            inner: self.inner.clone(),
+            meta_modified: self.meta_modified,
+        }
+    }
+}
+
+impl<A, F> Reload<A> for MetaReload<A, F>
+where
+    A: Asset,
+    F: Format<A> + Clone + Sync,
+    F::Options: DeserializeOwned + Clone + Sync,
+{
+    fn needs_reload(&self) -> bool {
+        let meta_modified = self.source.modified(&meta_path(&self.name)).unwrap_or(0);
+        meta_modified != self.meta_modified
+            || self
+                .inner
+                .as_ref()
+                .map(|r| r.needs_reload())
+                .unwrap_or(false)
+    }
+
+    fn name(&self) -> String {
+        self.name.clone()
+    }
+
+    fn format(&self) -> &'static str {
+        F::NAME
+    }
+
+    fn reload(self: Box<Self>) -> Result<FormatValue<A>, Error> {
+        #[cfg(feature = "profiler")]
+        profile_scope!("reload_with_meta");
+
+        let MetaReload {
+            format,
+            name,
+            source,
+            options,
+            ..
+        } = *self;
+
+        format.import(name, source, options, true)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use amethyst_error::Error;
+
+    use crate::{
+        test_util::{Files, Text},
+        Format, FormatValue, SimpleFormat, Source,
+    };
+
+    use super::MetaFormat;
+
+    /// Repeats the file contents `n` times.
+    #[derive(Clone)]
+    struct Repeat;
+
+    impl SimpleFormat<Text> for Repeat {
+        const NAME: &'static str = "Repeat";
+        type Options = usize;
+
+        fn import(&self, bytes: Vec<u8>, n: usize) -> Result<String, Error> {
+            Ok(String::from_utf8(bytes)?.repeat(n))
+        }
+    }
+
+    /// Source whose files exist, but can't be read.
+    struct Unreadable(Files);
+
+    impl Source for Unreadable {
+        fn modified(&self, path: &str) -> Result<u64, Error> {
+            self.0.modified(path)
+        }
+
+        fn load(&self, path: &str) -> Result<Vec<u8>, Error> {
+            self.0
+                .load(path)
+                .and_then(|_| Err(Error::from_string("Permission denied")))
+        }
+    }
+
+    fn import<S: Source>(files: S) -> Result<String, Error> {
+        let value: FormatValue<Text> =
+            MetaFormat::new(Repeat).import("a.txt".into(), Arc::new(files), 1, false)?;
+        Ok(value.data)
+    }
+
+    #[test]
+    fn uses_loader_options_without_sidecar() {
+        let files = Files::new(vec![("a.txt", "a")]);
+        assert_eq!("a", import(files).unwrap());
+    }
+
+    #[test]
+    fn sidecar_overrides_loader_options() {
+        let files = Files::new(vec![("a.txt", "a"), ("a.txt.meta.ron", "3")]);
+        assert_eq!("aaa", import(files).unwrap());
+    }
+
+    #[test]
+    fn invalid_sidecar_fails_import() {
+        let files = Files::new(vec![("a.txt", "a"), ("a.txt.meta.ron", "three")]);
+        assert!(import(files).is_err());
+    }
+
+    #[test]
+    fn unreadable_sidecar_fails_import() {
+        let files = Files::new(vec![("a.txt", "a"), ("a.txt.meta.ron", "3")]);
+        assert!(import(Unreadable(files)).is_err());
+    }
+}
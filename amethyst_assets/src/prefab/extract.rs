@@ -0,0 +1,268 @@
+use shred_derive::SystemData;
+
+use amethyst_core::{
+    ecs::prelude::{Entity, ReadExpect, SystemData},
+    ParentHierarchy,
+};
+use amethyst_error::Error;
+
+use super::{Prefab, PrefabData};
+
+/// Trait for extracting prefab data from the components of an existing entity.
+///
+/// This is the inverse of `PrefabData::add_to_entity`, and is used by `PrefabExtractor` to turn
+/// entities of a live `World` back into a `Prefab`, e.g. for level editing.
+pub trait ExtractPrefabData<'a>: PrefabData<'a> + Sized {
+    /// Extract the data from the components of `entity`.
+    ///
+    /// ### Parameters:
+    ///
+    /// - `entity`: `Entity` to extract the data from
+    /// - `system_data`: `SystemData` needed to do the extraction
+    /// - `entities`: All entities that are part of the extracted prefab, in the order they
+    ///               will have in the prefab, for data linking to other entities.
+    ///
+    /// ### Returns
+    ///
+    /// - `Ok(None)` - if the entity has none of the components the data adds
+    /// - `Ok(Some(data))` - the data which would recreate the components of the entity
+    fn extract_from_entity(
+        entity: Entity,
+        system_data: &Self::SystemData,
+        entities: &[Entity],
+    ) -> Result<Option<Self>, Error>;
+
+    /// The data to use when this is part of a larger data type, e.g. a tuple, and the entity
+    /// has some of the components of the larger type, but none of the components this adds.
+    ///
+    /// Returns `None` by default, which makes the extraction of the larger type fail.
+    /// `Option<T>` returns `Some(None)`.
+    fn extract_missing() -> Option<Self> {
+        None
+    }
+}
+
+/// Helper structure for extracting prefabs from the `World`.
+///
+/// ### Example
+///
+/// ```rust,ignore
+/// let prefab = world.exec(|extractor: PrefabExtractor<MyPrefabData>| {
+///     extractor.extract(root_entity)
+/// })?;
+/// let ron = prefab.to_ron()?;
+/// ```
+#[derive(SystemData)]
+pub struct PrefabExtractor<'a, T>
+where
+    T: ExtractPrefabData<'a>,
+{
+    hierarchy: ReadExpect<'a, ParentHierarchy>,
+    system_data: T::SystemData,
+}
+
+impl<'a, T> PrefabExtractor<'a, T>
+where
+    T: ExtractPrefabData<'a>,
+{
+    /// Extract a `Prefab` from `root` and all of its children in the `Parent` hierarchy.
+    ///
+    /// `root` becomes the main entity of the prefab. Entities without any of the components
+    /// handled by `T` are kept, so the hierarchy stays intact.
+    pub fn extract(&self, root: Entity) -> Result<Prefab<T>, Error> {
+        let mut entities = vec![root];
+        let mut parents = vec![None];
+        let mut next = 0;
+        while next < entities.len() {
+            for child in self.hierarchy.children(entities[next]) {
+                entities.push(*child);
+                parents.push(Some(next));
+            }
+            next += 1;
+        }
+
+        let mut prefab = Prefab::new();
+        prefab.main(T::extract_from_entity(root, &self.system_data, &entities)?);
+        for (entity, parent) in entities.iter().zip(parents).skip(1) {
+            let data = T::extract_from_entity(*entity, &self.system_data, &entities)?;
+            prefab.add(parent, data);
+        }
+
+        Ok(prefab)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{sync::Arc, thread, time::Duration};
+
+    use rayon::{ThreadPool, ThreadPoolBuilder};
+    use serde::{Deserialize, Serialize};
+
+    use amethyst_core::{
+        ecs::prelude::{
+            Builder, Dispatcher, DispatcherBuilder, Entity, SystemData, VecStorage, World,
+        },
+        Named, Parent, SystemBundle, Transform, TransformBundle,
+    };
+    use amethyst_error::Error;
+
+    use crate::{
+        test_util::Files, Asset, AssetPrefab, AssetStorage, Completion, Handle, Loader, Prefab,
+        PrefabData, ProcessingState, ProgressCounter, SimpleFormat,
+    };
+
+    use super::PrefabExtractor;
+
+    struct Number(u32);
+
+    impl Asset for Number {
+        const NAME: &'static str = "test::Number";
+        type Data = u32;
+        type HandleStorage = VecStorage<Handle<Self>>;
+    }
+
+    /// Imports the length of the file, multiplied by the options.
+    #[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
+    struct LengthFormat;
+
+    impl SimpleFormat<Number> for LengthFormat {
+        const NAME: &'static str = "Length";
+        type Options = u32;
+
+        fn import(&self, bytes: Vec<u8>, factor: u32) -> Result<u32, Error> {
+            Ok(bytes.len() as u32 * factor)
+        }
+    }
+
+    type Data = (
+        Option<Named>,
+        Option<Transform>,
+        Option<AssetPrefab<Number, LengthFormat>>,
+    );
+
+    fn setup() -> (World, Dispatcher<'static, 'static>) {
+        let pool = Arc::new(ThreadPoolBuilder::new().num_threads(1).build().unwrap());
+        let mut world = World::new();
+        world.add_resource(Loader::with_default_source(
+            Files::new(vec![("number", "abc")]),
+            pool.clone(),
+        ));
+        world.add_resource(pool);
+        let mut builder = DispatcherBuilder::new();
+        TransformBundle::new().build(&mut builder).unwrap();
+        let mut dispatcher = builder.build();
+        dispatcher.setup(&mut world.res);
+        <Data as PrefabData<'_>>::SystemData::setup(&mut world.res);
+        (world, dispatcher)
+    }
+
+    fn process(world: &World, progress: &ProgressCounter) {
+        let pool = world.read_resource::<Arc<ThreadPool>>().clone();
+        for frame in 0..1000 {
+            world.write_resource::<AssetStorage<Number>>().process(
+                |number| Ok(ProcessingState::Loaded(Number(number))),
+                frame,
+                &pool,
+                None,
+            );
+            if progress.complete() != Completion::Loading {
+                return;
+            }
+            thread::sleep(Duration::from_millis(1));
+        }
+        panic!("Loading the asset didn't finish");
+    }
+
+    fn save_and_load(world: &World, root: Entity) -> Result<Prefab<Data>, Error> {
+        let ron = world
+            .exec(|extractor: PrefabExtractor<'_, Data>| extractor.extract(root))?
+            .to_ron()?;
+        Ok(ron::de::from_str(&ron).unwrap())
+    }
+
+    #[test]
+    fn extracted_prefab_survives_save_and_load() {
+        let (mut world, mut dispatcher) = setup();
+        let mut transform = Transform::default();
+        transform.set_translation_x(2.0);
+        let root = world
+            .create_entity()
+            .with(Named::new("root"))
+            .with(transform.clone())
+            .build();
+        let child = world
+            .create_entity()
+            .with(Named::new("child"))
+            .with(Parent::new(root))
+            .build();
+        world.create_entity().with(Parent::new(child)).build();
+        dispatcher.dispatch(&world.res);
+
+        let prefab = save_and_load(&world, root).unwrap();
+        let entities = prefab.entities().collect::<Vec<_>>();
+        assert_eq!(3, entities.len());
+        let main = entities[0].data().unwrap();
+        assert_eq!("root", main.0.as_ref().unwrap().name);
+        assert_eq!(Some(&transform), main.1.as_ref());
+        // Entities with only some of the components are extracted, the rest is `None`
+        let child = entities[1].data().unwrap();
+        assert_eq!("child", child.0.as_ref().unwrap().name);
+        assert!(child.1.is_none());
+        assert_eq!(Some(0), entities[1].parent);
+        assert!(entities[2].data().is_none());
+        assert_eq!(Some(1), entities[2].parent);
+    }
+
+    fn load_number(world: &mut World) -> Entity {
+        let entity = world.create_entity().build();
+        let mut progress = ProgressCounter::new();
+        world.exec(
+            |mut data: <AssetPrefab<Number, LengthFormat> as PrefabData<'_>>::SystemData| {
+                let mut prefab = AssetPrefab::File("number".to_owned(), LengthFormat, 2);
+                prefab.load_sub_assets(&mut progress, &mut data).unwrap();
+                prefab.add_to_entity(entity, &mut data, &[], &[]).unwrap();
+            },
+        );
+        process(world, &progress);
+        entity
+    }
+
+    #[test]
+    fn extracted_asset_prefab_keeps_format_and_options() {
+        let (mut world, _) = setup();
+        world
+            .write_resource::<AssetStorage<Number>>()
+            .record_formats();
+        let entity = load_number(&mut world);
+
+        let prefab = save_and_load(&world, entity).unwrap();
+        match prefab.entities().next().unwrap().data().unwrap().2 {
+            Some(AssetPrefab::File(ref name, LengthFormat, 2)) => assert_eq!("number", name),
+            _ => panic!("Expected the asset to be extracted as the file it was loaded from"),
+        }
+    }
+
+    #[test]
+    fn asset_prefabs_are_not_extracted_without_recorded_formats() {
+        let (mut world, _) = setup();
+        let entity = load_number(&mut world);
+
+        assert!(save_and_load(&world, entity).is_err());
+    }
+
+    #[test]
+    fn assets_loaded_from_data_are_not_extracted() {
+        let (mut world, _) = setup();
+        let mut progress = ProgressCounter::new();
+        let handle = world.read_resource::<Loader>().load_from_data(
+            3,
+            &mut progress,
+            &world.read_resource::<AssetStorage<Number>>(),
+        );
+        let entity = world.create_entity().with(handle).build();
+        process(&world, &progress);
+
+        assert!(save_and_load(&world, entity).is_err());
+    }
+}
@@ -1,10 +1,12 @@
+use std::any::TypeId;
+
 use amethyst_core::{
     ecs::{Entity, WriteStorage},
     GlobalTransform, Named, Transform,
 };
-use amethyst_error::Error;
+use amethyst_error::{format_err, Error};
 
-use crate::{PrefabData, ProgressCounter};
+use crate::{ExtractPrefabData, PrefabData, ProgressCounter};
 
 impl<'a, T> PrefabData<'a> for Option<T>
 where
@@ -43,6 +45,46 @@ where
             Ok(false)
         }
     }
+
+    fn merge(&mut self, other: Self) {
+        if let Some(other) = other {
+            match *self {
+                Some(ref mut prefab) => prefab.merge(other),
+                None => *self = Some(other),
+            }
+        }
+    }
+
+    fn component_types(&self, types: &mut Vec<TypeId>) {
+        if let Some(ref prefab) = self {
+            prefab.component_types(types);
+        }
+    }
+
+    fn remove_components(
+        entity: Entity,
+        types: &[TypeId],
+        system_data: &mut Self::SystemData,
+    ) -> Result<(), Error> {
+        T::remove_components(entity, types, system_data)
+    }
+}
+
+impl<'a, T> ExtractPrefabData<'a> for Option<T>
+where
+    T: ExtractPrefabData<'a>,
+{
+    fn extract_from_entity(
+        entity: Entity,
+        system_data: &Self::SystemData,
+        entities: &[Entity],
+    ) -> Result<Option<Self>, Error> {
+        Ok(T::extract_from_entity(entity, system_data, entities)?.map(Some))
+    }
+
+    fn extract_missing() -> Option<Self> {
+        Some(None)
+    }
 }
 
 impl<'a> PrefabData<'a> for GlobalTransform {
@@ -59,6 +101,31 @@ impl<'a> PrefabData<'a> for GlobalTransform {
         storage.insert(entity, self.clone()).map(|_| ())?;
         Ok(())
     }
+
+    fn component_types(&self, types: &mut Vec<TypeId>) {
+        types.push(TypeId::of::<Self>());
+    }
+
+    fn remove_components(
+        entity: Entity,
+        types: &[TypeId],
+        storage: &mut Self::SystemData,
+    ) -> Result<(), Error> {
+        if types.contains(&TypeId::of::<Self>()) {
+            storage.remove(entity);
+        }
+        Ok(())
+    }
+}
+
+impl<'a> ExtractPrefabData<'a> for GlobalTransform {
+    fn extract_from_entity(
+        entity: Entity,
+        storage: &Self::SystemData,
+        _: &[Entity],
+    ) -> Result<Option<Self>, Error> {
+        Ok(storage.get(entity).cloned())
+    }
 }
 
 impl<'a> PrefabData<'a> for Transform {
@@ -79,6 +146,35 @@ impl<'a> PrefabData<'a> for Transform {
         storages.0.insert(entity, self.clone()).map(|_| ())?;
         Ok(())
     }
+
+    fn component_types(&self, types: &mut Vec<TypeId>) {
+        types.push(TypeId::of::<Transform>());
+        types.push(TypeId::of::<GlobalTransform>());
+    }
+
+    fn remove_components(
+        entity: Entity,
+        types: &[TypeId],
+        storages: &mut Self::SystemData,
+    ) -> Result<(), Error> {
+        if types.contains(&TypeId::of::<Transform>()) {
+            storages.0.remove(entity);
+        }
+        if types.contains(&TypeId::of::<GlobalTransform>()) {
+            storages.1.remove(entity);
+        }
+        Ok(())
+    }
+}
+
+impl<'a> ExtractPrefabData<'a> for Transform {
+    fn extract_from_entity(
+        entity: Entity,
+        storages: &Self::SystemData,
+        _: &[Entity],
+    ) -> Result<Option<Self>, Error> {
+        Ok(storages.0.get(entity).cloned())
+    }
 }
 
 impl<'a> PrefabData<'a> for Named {
@@ -95,6 +191,31 @@ impl<'a> PrefabData<'a> for Named {
         storages.0.insert(entity, self.clone()).map(|_| ())?;
         Ok(())
     }
+
+    fn component_types(&self, types: &mut Vec<TypeId>) {
+        types.push(TypeId::of::<Named>());
+    }
+
+    fn remove_components(
+        entity: Entity,
+        types: &[TypeId],
+        storages: &mut Self::SystemData,
+    ) -> Result<(), Error> {
+        if types.contains(&TypeId::of::<Named>()) {
+            storages.0.remove(entity);
+        }
+        Ok(())
+    }
+}
+
+impl<'a> ExtractPrefabData<'a> for Named {
+    fn extract_from_entity(
+        entity: Entity,
+        storages: &Self::SystemData,
+        _: &[Entity],
+    ) -> Result<Option<Self>, Error> {
+        Ok(storages.0.get(entity).cloned())
+    }
 }
 
 macro_rules! impl_data {
@@ -137,6 +258,67 @@ macro_rules! impl_data {
                 )*
                 Ok(ret)
             }
+
+            fn merge(&mut self, other: Self) {
+                #![allow(unused_variables)]
+                $(
+                    self.$i.merge(other.$i);
+                )*
+            }
+
+            fn component_types(&self, types: &mut Vec<TypeId>) {
+                #![allow(unused_variables)]
+                $(
+                    self.$i.component_types(types);
+                )*
+            }
+
+            fn remove_components(
+                entity: Entity,
+                types: &[TypeId],
+                system_data: &mut Self::SystemData,
+            ) -> Result<(), Error> {
+                #![allow(unused_variables)]
+                $(
+                    $ty::remove_components(entity, types, &mut system_data.$i)?;
+                )*
+                Ok(())
+            }
+        }
+
+        #[allow(unused)]
+        impl<'a, $($ty),*> ExtractPrefabData<'a> for ( $( $ty , )* )
+            where $( $ty : ExtractPrefabData<'a> ),*
+        {
+            fn extract_from_entity(
+                entity: Entity,
+                system_data: &Self::SystemData,
+                entities: &[Entity],
+            ) -> Result<Option<Self>, Error> {
+                #![allow(unused_variables)]
+                let extracted = (
+                    $(
+                        $ty::extract_from_entity(entity, &system_data.$i, entities)?,
+                    )*
+                );
+                if true $( && extracted.$i.is_none() )* {
+                    return Ok(None);
+                }
+                Ok(Some((
+                    $(
+                        match extracted.$i {
+                            Some(data) => data,
+                            None => $ty::extract_missing().ok_or_else(|| {
+                                format_err!(
+                                    "Can't extract tuple data from entity {:?}, it has only \
+                                     some of the components the tuple adds",
+                                    entity
+                                )
+                            })?,
+                        },
+                    )*
+                )))
+            }
         }
     };
 }
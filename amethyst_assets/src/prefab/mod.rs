@@ -1,19 +1,26 @@
-use std::marker::PhantomData;
+use std::{any::TypeId, marker::PhantomData};
 
 use serde::{Deserialize, Serialize};
 use shred_derive::SystemData;
+use uuid::Uuid;
 
 use amethyst_core::ecs::prelude::{
     Component, DenseVecStorage, Entity, FlaggedStorage, Read, ReadExpect, SystemData, WriteStorage,
 };
-use amethyst_error::Error;
+use amethyst_error::{format_err, Error, ResultExt};
 
 use crate::{Asset, AssetStorage, Format, Handle, Loader, Progress, ProgressCounter};
 
-pub use self::system::PrefabLoaderSystem;
+pub use self::{
+    extract::{ExtractPrefabData, PrefabExtractor},
+    system::PrefabLoaderSystem,
+    variant::PrefabVariantFormat,
+};
 
+mod extract;
 mod impls;
 mod system;
+mod variant;
 
 /// Trait for loading a prefabs data for a single entity
 pub trait PrefabData<'a> {
@@ -69,6 +76,38 @@ pub trait PrefabData<'a> {
     ) -> Result<bool, Error> {
         Ok(false)
     }
+
+    /// Merge the data of a prefab variant into this data, see `PrefabVariantFormat`.
+    ///
+    /// The default implementation replaces `self` with `other`. Data with optional fields should
+    /// only replace the fields that are set in `other`, so a variant only has to specify the
+    /// fields it changes. `Option` and tuples merge their contents, and `#[derive(PrefabData)]`
+    /// merges field by field.
+    fn merge(&mut self, other: Self)
+    where
+        Self: Sized,
+    {
+        *self = other;
+    }
+
+    /// Appends the types of the components `add_to_entity` adds to `types`.
+    ///
+    /// When a prefab is reloaded, the components it added before but doesn't add anymore are
+    /// removed with `remove_components`, see `PrefabLoaderSystem::with_instance_reload`. The
+    /// default implementation reports no components, so they are kept.
+    fn component_types(&self, _types: &mut Vec<TypeId>) {}
+
+    /// Removes the components with the given types from `entity`, if this data can add them.
+    fn remove_components(
+        _entity: Entity,
+        _types: &[TypeId],
+        _system_data: &mut Self::SystemData,
+    ) -> Result<(), Error>
+    where
+        Self: Sized,
+    {
+        Ok(())
+    }
 }
 
 /// Main `Prefab` structure, containing all data loaded in a single prefab.
@@ -80,6 +119,9 @@ pub trait PrefabData<'a> {
 ///
 /// The recommended way of loading resources is to place them on the main `Entity`.
 ///
+/// An entry can also instantiate another prefab, see `PrefabReference`. This allows composing
+/// levels from reusable building, enemy and prop prefabs.
+///
 /// ### Example:
 ///
 /// If we want to give the existing Baker entity a Knife and a Plate with a
@@ -106,9 +148,16 @@ pub trait PrefabData<'a> {
 pub struct Prefab<T> {
     #[serde(skip)]
     tag: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    base: Option<String>,
     entities: Vec<PrefabEntity<T>>,
     #[serde(skip)]
     counter: Option<ProgressCounter>,
+    #[serde(skip)]
+    references: Option<ProgressCounter>,
+    /// Names of the prefabs referencing this prefab, if it was loaded as a reference.
+    #[serde(skip)]
+    ancestors: Vec<String>,
 }
 
 /// Prefab data container for a single entity
@@ -121,6 +170,10 @@ pub struct Prefab<T> {
 pub struct PrefabEntity<T> {
     parent: Option<usize>,
     data: Option<T>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    prefab: Option<PrefabReference<T>>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    overrides: Vec<(usize, T)>,
 }
 
 impl<T> Default for PrefabEntity<T> {
@@ -132,7 +185,38 @@ impl<T> Default for PrefabEntity<T> {
 impl<T> PrefabEntity<T> {
     /// New prefab entity
     pub fn new(parent: Option<usize>, data: Option<T>) -> Self {
-        PrefabEntity { parent, data }
+        PrefabEntity {
+            parent,
+            data,
+            prefab: None,
+            overrides: Vec::new(),
+        }
+    }
+
+    /// Instantiate another prefab on this entity.
+    ///
+    /// The main entity of the referenced prefab is this entity, so the data of this entity is
+    /// applied after the data of the referenced main entity and overrides it.
+    pub fn set_prefab(&mut self, prefab: PrefabReference<T>) {
+        self.prefab = Some(prefab);
+    }
+
+    /// Get the referenced prefab, if any
+    pub fn prefab(&self) -> Option<&PrefabReference<T>> {
+        self.prefab.as_ref()
+    }
+
+    /// Override the data of the entity with the given index in the referenced prefab.
+    ///
+    /// The override is applied after the data of the referenced prefab, so components added by
+    /// it replace the ones from the referenced prefab. Index `0` is this entity.
+    pub fn add_override(&mut self, index: usize, data: T) {
+        self.overrides.push((index, data));
+    }
+
+    /// Get the overrides for the referenced prefab
+    pub fn overrides(&self) -> &[(usize, T)] {
+        &self.overrides
     }
 
     /// Set parent index
@@ -165,6 +249,24 @@ impl<T> PrefabEntity<T> {
         self.data.get_or_insert_with(T::default)
     }
 
+    /// Merge the entity of a prefab variant into this entity.
+    ///
+    /// The data is merged using `PrefabData::merge`, the parent and the referenced prefab are
+    /// replaced if they are set in `other`, and the overrides of `other` are appended.
+    pub fn merge<'a>(&mut self, other: PrefabEntity<T>)
+    where
+        T: PrefabData<'a>,
+    {
+        if other.parent.is_some() {
+            self.parent = other.parent;
+        }
+        self.data.merge(other.data);
+        if other.prefab.is_some() {
+            self.prefab = other.prefab;
+        }
+        self.overrides.extend(other.overrides);
+    }
+
     /// Trigger sub asset loading for the prefab entity
     pub fn load_sub_assets<'a>(
         &mut self,
@@ -174,21 +276,66 @@ impl<T> PrefabEntity<T> {
     where
         T: PrefabData<'a>,
     {
+        let mut ret = false;
         if let Some(ref mut data) = self.data {
-            data.load_sub_assets(progress, system_data)
-        } else {
-            Ok(false)
+            ret |= data.load_sub_assets(progress, system_data)?;
         }
+        for (_, data) in &mut self.overrides {
+            ret |= data.load_sub_assets(progress, system_data)?;
+        }
+        Ok(ret)
     }
 }
 
+/// Reference to another prefab, which is instantiated on a `PrefabEntity`.
+///
+/// References are loaded by the `PrefabLoaderSystem` using the format given to
+/// `PrefabLoaderSystem::with_reference_format`. Prefabs referencing each other in a cycle,
+/// directly or through other prefabs, fail to load.
+///
+/// ### Example:
+///
+/// ```rust,ignore
+/// #![enable(implicit_some)]
+/// Prefab (
+///     entities: [
+///         (
+///             data: (transform: (translation: (0.0, 0.0, 0.0))),
+///         ),
+///         (
+///             parent: 0,
+///             prefab: File("prefab/house.ron"),
+///             // moves the house, replacing the transform of its main entity
+///             data: (transform: (translation: (10.0, 0.0, 0.0))),
+///             // changes the door, which is entity #2 of the house prefab
+///             overrides: [(2, (mesh: File("mesh/red_door.obj", ObjFormat, ())))],
+///         ),
+///     ],
+/// )
+/// ```
+#[derive(Debug, Deserialize, Serialize)]
+pub enum PrefabReference<T> {
+    /// From existing handle
+    #[serde(skip)]
+    Handle(Handle<Prefab<T>>),
+
+    /// From file
+    File(String),
+
+    /// From the id of a prefab in the `AssetManifest`
+    Id(Uuid),
+}
+
 impl<T> Prefab<T> {
     /// Create new empty prefab
     pub fn new() -> Self {
         Prefab {
             tag: None,
+            base: None,
             entities: vec![PrefabEntity::default()],
             counter: None,
+            references: None,
+            ancestors: Vec::new(),
         }
     }
 
@@ -196,8 +343,11 @@ impl<T> Prefab<T> {
     pub fn new_main(data: T) -> Self {
         Prefab {
             tag: None,
+            base: None,
             entities: vec![PrefabEntity::new(None, Some(data))],
             counter: None,
+            references: None,
+            ancestors: Vec::new(),
         }
     }
 
@@ -206,6 +356,34 @@ impl<T> Prefab<T> {
         self.entities[0].data = data;
     }
 
+    /// Path of the base prefab, if this prefab is a variant.
+    ///
+    /// See `PrefabVariantFormat` for more information.
+    pub fn base(&self) -> Option<&str> {
+        self.base.as_ref().map(String::as_str)
+    }
+
+    /// Make this prefab a variant of the prefab at `base`.
+    pub fn set_base(&mut self, base: Option<String>) {
+        self.base = base;
+    }
+
+    /// Merge a variant into this prefab.
+    ///
+    /// Entity `n` of the variant is merged into entity `n` of this prefab using
+    /// `PrefabEntity::merge`, entities beyond the length of this prefab are appended.
+    pub fn merge<'a>(&mut self, variant: Prefab<T>)
+    where
+        T: PrefabData<'a>,
+    {
+        for (index, entity) in variant.entities.into_iter().enumerate() {
+            match self.entities.get_mut(index) {
+                Some(base) => base.merge(entity),
+                None => self.entities.push(entity),
+            }
+        }
+    }
+
     /// Add a new entity to the prefab, with optional data and parent.
     pub fn add(&mut self, parent: Option<usize>, data: Option<T>) -> usize {
         let index = self.entities.len();
@@ -281,6 +459,53 @@ impl<T> Prefab<T> {
         self.counter = Some(progress);
         Ok(ret)
     }
+
+    /// Serialize the prefab to RON, e.g. after extracting it with a `PrefabExtractor`.
+    ///
+    /// Data which was already loaded (like `AssetPrefab::Handle`) can't be serialized.
+    pub fn to_ron(&self) -> Result<String, Error>
+    where
+        T: Serialize,
+    {
+        use ron::ser::to_string_pretty;
+        to_string_pretty(self, Default::default())
+            .with_context(|_| format_err!("Failed serializing prefab"))
+    }
+
+    /// Returns `true` if some referenced prefabs are not loaded yet.
+    fn has_unloaded_references(&self) -> bool {
+        self.entities.iter().any(|entity| match entity.prefab {
+            Some(PrefabReference::File(_)) | Some(PrefabReference::Id(_)) => true,
+            _ => false,
+        })
+    }
+
+    /// Returns the index and the reference of all referenced prefabs which are not loaded yet.
+    fn unloaded_references(&self) -> Vec<(usize, PrefabReference<T>)> {
+        self.entities
+            .iter()
+            .enumerate()
+            .filter_map(|(index, entity)| match entity.prefab {
+                Some(PrefabReference::File(ref name)) => {
+                    Some((index, PrefabReference::File(name.clone())))
+                }
+                Some(PrefabReference::Id(id)) => Some((index, PrefabReference::Id(id))),
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// Replaces references with the handles of the loads started for them.
+    fn set_references(
+        &mut self,
+        handles: Vec<(usize, Handle<Prefab<T>>)>,
+        progress: ProgressCounter,
+    ) {
+        for (index, handle) in handles {
+            self.entities[index].prefab = Some(PrefabReference::Handle(handle));
+        }
+        self.references = Some(progress);
+    }
 }
 
 /// Tag placed on entities created by the prefab system.
@@ -342,6 +567,11 @@ where
 
     /// From file, (name, format, format options)
     File(String, F, F::Options),
+
+    /// From the id of an asset in the `AssetManifest`, (id, format, format options)
+    ///
+    /// Unlike a file name, the id stays valid when the asset is moved.
+    Id(Uuid, F, F::Options),
 }
 
 impl<'a, A, F> PrefabData<'a> for AssetPrefab<A, F>
@@ -367,7 +597,7 @@ where
     ) -> Result<Handle<A>, Error> {
         let handle = match *self {
             AssetPrefab::Handle(ref handle) => handle.clone(),
-            AssetPrefab::File(..) => unreachable!(),
+            AssetPrefab::File(..) | AssetPrefab::Id(..) => unreachable!(),
         };
         Ok(system_data
             .1
@@ -375,23 +605,51 @@ where
             .map(|_| handle)?)
     }
 
+    fn component_types(&self, types: &mut Vec<TypeId>) {
+        types.push(TypeId::of::<Handle<A>>());
+    }
+
+    fn remove_components(
+        entity: Entity,
+        types: &[TypeId],
+        system_data: &mut Self::SystemData,
+    ) -> Result<(), Error> {
+        if types.contains(&TypeId::of::<Handle<A>>()) {
+            system_data.1.remove(entity);
+        }
+        Ok(())
+    }
+
     fn load_sub_assets(
         &mut self,
         progress: &mut ProgressCounter,
         system_data: &mut Self::SystemData,
     ) -> Result<bool, Error> {
-        let handle = if let AssetPrefab::File(ref name, ref format, ref options) = *self {
-            Some(system_data.0.load(
+        let handle = match *self {
+            AssetPrefab::File(ref name, ref format, ref options) => Some(system_data.0.load(
                 name.as_ref(),
                 format.clone(),
                 options.clone(),
                 progress,
                 &system_data.2,
-            ))
-        } else {
-            None
+            )),
+            AssetPrefab::Id(ref id, ref format, ref options) => Some(system_data.0.load_by_id(
+                id,
+                format.clone(),
+                options.clone(),
+                progress,
+                &system_data.2,
+            )),
+            AssetPrefab::Handle(_) => None,
         };
         if let Some(handle) = handle {
+            match *self {
+                AssetPrefab::File(_, ref format, ref options)
+                | AssetPrefab::Id(_, ref format, ref options) => {
+                    system_data.2.record_format(&handle, format, options);
+                }
+                AssetPrefab::Handle(_) => {}
+            }
             *self = AssetPrefab::Handle(handle);
             Ok(true)
         } else {
@@ -400,6 +658,63 @@ where
     }
 }
 
+impl<A, F> AssetPrefab<A, F>
+where
+    A: Asset,
+    F: Format<A> + Clone,
+    F::Options: Clone,
+{
+    /// Create the prefab for an asset `handle` loaded by an `AssetPrefab` or another prefab
+    /// recording its format in the `storage`, referring to the asset by id if it is in the
+    /// manifest.
+    ///
+    /// Fails if the asset was not loaded from a file with format `F`, or if the storage doesn't
+    /// record formats, see `AssetStorage::record_formats`.
+    pub fn from_handle(
+        handle: &Handle<A>,
+        loader: &Loader,
+        storage: &AssetStorage<A>,
+    ) -> Result<Self, Error> {
+        let name = storage.name(handle).ok_or_else(|| {
+            format_err!(
+                "Can't extract {:?} asset, it is not loaded from a file",
+                A::NAME
+            )
+        })?;
+        let (format, options) = storage.format(handle).ok_or_else(|| {
+            format_err!(
+                "Can't extract {:?} asset {:?}, no format {:?} was recorded for it, \
+                 see `AssetStorage::record_formats`",
+                A::NAME,
+                name,
+                F::NAME
+            )
+        })?;
+        Ok(match loader.manifest().id(name) {
+            Some(id) => AssetPrefab::Id(id, format, options),
+            None => AssetPrefab::File(name.to_owned(), format, options),
+        })
+    }
+}
+
+impl<'a, A, F> ExtractPrefabData<'a> for AssetPrefab<A, F>
+where
+    A: Asset,
+    F: Format<A> + Clone,
+    F::Options: Clone,
+{
+    fn extract_from_entity(
+        entity: Entity,
+        system_data: &Self::SystemData,
+        _: &[Entity],
+    ) -> Result<Option<Self>, Error> {
+        match system_data.1.get(entity) {
+            Some(handle) => Self::from_handle(handle, &system_data.0, &system_data.2).map(Some),
+            None => Ok(None),
+        }
+    }
+}
+
 /// Helper structure for loading prefabs.
 ///
 /// The recommended way of using this from `State`s is to use `world.exec`.
@@ -458,7 +773,7 @@ mod tests {
 
     use amethyst_core::{
         ecs::{Builder, RunNow, World},
-        GlobalTransform, Time, Transform,
+        GlobalTransform, Named, Time, Transform,
     };
 
     use crate::Loader;
@@ -495,4 +810,33 @@ mod tests {
             .get(root_entity)
             .is_some());
     }
+
+    #[test]
+    fn test_prefab_variant_merge() {
+        type Data = (Option<Named>, Option<Transform>);
+
+        let mut base = Prefab::<Data>::new_main((Some(Named::new("base")), None));
+        base.add(Some(0), Some((Some(Named::new("child")), None)));
+
+        let mut transform = Transform::default();
+        transform.set_translation_x(1.0);
+        let mut variant = Prefab::<Data>::new_main((None, Some(transform.clone())));
+        variant.new_entity();
+        variant.add(Some(0), Some((Some(Named::new("extra")), None)));
+
+        base.merge(variant);
+        let entities = base.entities().collect::<Vec<_>>();
+        assert_eq!(3, entities.len());
+        let main = entities[0].data().unwrap();
+        assert_eq!("base", main.0.as_ref().unwrap().name);
+        assert_eq!(Some(&transform), main.1.as_ref());
+        assert_eq!(
+            "child",
+            entities[1].data().unwrap().0.as_ref().unwrap().name
+        );
+        assert_eq!(
+            "extra",
+            entities[2].data().unwrap().0.as_ref().unwrap().name
+        );
+    }
 }
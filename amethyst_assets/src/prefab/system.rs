@@ -1,6 +1,7 @@
-use std::{collections::HashMap, marker::PhantomData, ops::Deref};
+use std::{any::TypeId, collections::HashMap, marker::PhantomData, ops::Deref, sync::Arc};
 
-use log::error;
+use fnv::{FnvHashMap, FnvHashSet};
+use log::{debug, error};
 
 use amethyst_core::{
     ecs::{
@@ -11,9 +12,27 @@ use amethyst_core::{
 };
 use amethyst_error::{format_err, Error, ResultExt};
 
-use crate::{AssetStorage, Completion, Handle, HotReloadStrategy, ProcessingState};
+use crate::{
+    AssetStorage, Completion, Format, FormatValue, Handle, HotReloadStrategy, Loader,
+    ProcessingState, ProgressCounter, Reload, Source, WeakHandle,
+};
+
+use super::{Prefab, PrefabData, PrefabReference, PrefabTag};
+
+/// Maximum depth of nested prefab references.
+const MAX_REFERENCE_DEPTH: usize = 32;
 
-use super::{Prefab, PrefabData, PrefabTag};
+type ReferenceLoader<T> = Box<
+    dyn Fn(
+            &Loader,
+            &PrefabReference<T>,
+            Vec<String>,
+            &mut ProgressCounter,
+            &AssetStorage<Prefab<T>>,
+        ) -> Handle<Prefab<T>>
+        + Send
+        + Sync,
+>;
 
 /// System that load `Prefab`s for `PrefabData` `T`.
 ///
@@ -22,26 +41,96 @@ use super::{Prefab, PrefabData, PrefabTag};
 /// - `T`: `PrefabData`
 pub struct PrefabLoaderSystem<T> {
     _m: PhantomData<T>,
-    entities: Vec<Entity>,
     finished: Vec<Entity>,
     to_process: BitSet,
     insert_reader: Option<ReaderId<ComponentEvent>>,
     next_tag: u64,
+    reference_loader: Option<ReferenceLoader<T>>,
+    reference_requests: Vec<(u64, Vec<(usize, PrefabReference<T>, Vec<String>)>)>,
+    requested_references: FnvHashSet<u64>,
+    loaded_references: FnvHashMap<u64, (Vec<(usize, Handle<Prefab<T>>)>, ProgressCounter)>,
+    reload_instances: bool,
+    instances: FnvHashMap<Entity, InstanceRecord<T>>,
+}
+
+/// An instantiated prefab, used for updating the entities when the prefab is reloaded.
+struct InstanceRecord<T> {
+    handle: WeakHandle<Prefab<T>>,
+    tag: u64,
+    /// All entities created for the prefab, excluding the root entity.
+    entities: Vec<Entity>,
+    /// The types of the components the prefab added to each entity, including the root entity.
+    components: FnvHashMap<Entity, Vec<TypeId>>,
 }
 
 impl<T> Default for PrefabLoaderSystem<T> {
     fn default() -> Self {
         PrefabLoaderSystem {
             _m: PhantomData,
-            entities: Vec::default(),
             finished: Vec::default(),
             to_process: BitSet::default(),
             insert_reader: None,
             next_tag: 0,
+            reference_loader: None,
+            reference_requests: Vec::default(),
+            requested_references: FnvHashSet::default(),
+            loaded_references: FnvHashMap::default(),
+            reload_instances: false,
+            instances: FnvHashMap::default(),
         }
     }
 }
 
+impl<T> PrefabLoaderSystem<T>
+where
+    T: Send + Sync + 'static,
+{
+    /// Load prefabs referenced by other prefabs (see `PrefabReference`) using `format`.
+    ///
+    /// Without a format, prefabs containing references fail to load. Prefabs referencing each
+    /// other in a cycle fail to load, too.
+    pub fn with_reference_format<F>(mut self, format: F, options: F::Options) -> Self
+    where
+        F: Format<Prefab<T>> + Clone + Sync,
+        F::Options: Clone + Sync,
+    {
+        self.reference_loader = Some(Box::new(
+            move |loader, reference, ancestors, progress, storage| {
+                let format = ReferencedFormat {
+                    format: format.clone(),
+                    ancestors,
+                };
+                match *reference {
+                    PrefabReference::Handle(ref handle) => handle.clone(),
+                    PrefabReference::File(ref name) => {
+                        loader.load(name.as_str(), format, options.clone(), progress, storage)
+                    }
+                    PrefabReference::Id(ref id) => {
+                        loader.load_by_id(id, format, options.clone(), progress, storage)
+                    }
+                }
+            },
+        ));
+        self
+    }
+
+    /// Update the entities of instantiated prefabs when their prefab is reloaded.
+    ///
+    /// The `PrefabData` of the reloaded prefab is applied again to every entity that was spawned
+    /// from it, child entities are created or deleted to match the new prefab, and their
+    /// `Parent`s are updated. Components which the old prefab added but the new one doesn't are
+    /// removed, while components which were not added by the prefab are preserved. This relies
+    /// on `PrefabData::component_types`; components of data which doesn't report its types are
+    /// kept.
+    ///
+    /// Entities are matched in the order they were created, so inserting an entity in the middle
+    /// of a prefab updates all entities after it to their new data.
+    pub fn with_instance_reload(mut self) -> Self {
+        self.reload_instances = true;
+        self
+    }
+}
+
 impl<'a, T> System<'a> for PrefabLoaderSystem<T>
 where
     T: PrefabData<'a> + Send + Sync + 'static,
@@ -52,6 +141,7 @@ where
         ReadStorage<'a, Handle<Prefab<T>>>,
         Read<'a, Time>,
         ReadExpect<'a, ArcThreadPool>,
+        ReadExpect<'a, Loader>,
         Option<Read<'a, HotReloadStrategy>>,
         WriteStorage<'a, Parent>,
         WriteStorage<'a, PrefabTag<T>>,
@@ -65,36 +155,126 @@ where
             prefab_handles,
             time,
             pool,
+            loader,
             strategy,
             mut parents,
             mut tags,
             mut prefab_system_data,
         ) = data;
         let strategy = strategy.as_ref().map(Deref::deref);
+        let has_reference_loader = self.reference_loader.is_some();
+        let reference_requests = &mut self.reference_requests;
+        let requested_references = &mut self.requested_references;
+        let loaded_references = &mut self.loaded_references;
+        let next_tag = &mut self.next_tag;
+        let mut pending = FnvHashSet::default();
         prefab_storage.process(
             |mut d| {
-                d.tag = Some(self.next_tag);
-                self.next_tag += 1;
-                if !d.loading()
-                    && !d
-                        .load_sub_assets(&mut prefab_system_data)
-                        .with_context(|_| format_err!("Failed starting sub asset loading"))?
-                {
-                    return Ok(ProcessingState::Loaded(d));
-                }
-                match d.progress().complete() {
-                    Completion::Complete => Ok(ProcessingState::Loaded(d)),
-                    Completion::Failed => {
-                        error!("Failed loading sub asset: {:?}", d.progress().errors());
-                        Err(Error::from_string("Failed loading sub asset"))
+                let tag = *d.tag.get_or_insert_with(|| {
+                    *next_tag += 1;
+                    *next_tag - 1
+                });
+                let result = (|| {
+                    if let Some(ref base) = d.base {
+                        return Err(format_err!(
+                            "Prefab is a variant of {:?}, but was not loaded with \
+                             `PrefabVariantFormat`",
+                            base
+                        ));
+                    }
+                    if d.has_unloaded_references() {
+                        // Referenced prefabs are loaded after processing, because loading needs
+                        // access to the storage.
+                        match loaded_references.remove(&tag) {
+                            Some((handles, progress)) => {
+                                requested_references.remove(&tag);
+                                d.set_references(handles, progress);
+                            }
+                            None => {
+                                if !has_reference_loader {
+                                    return Err(format_err!(
+                                        "Prefab references another prefab, but no format was \
+                                         set with `PrefabLoaderSystem::with_reference_format`"
+                                    ));
+                                }
+                                if !requested_references.contains(&tag) {
+                                    let references = d
+                                        .unloaded_references()
+                                        .into_iter()
+                                        .map(|(index, reference)| {
+                                            let ancestors =
+                                                reference_ancestors(&d, &reference, &loader)?;
+                                            Ok((index, reference, ancestors))
+                                        })
+                                        .collect::<Result<Vec<_>, Error>>()?;
+                                    requested_references.insert(tag);
+                                    reference_requests.push((tag, references));
+                                }
+                                return Ok(ProcessingState::Loading(d));
+                            }
+                        }
+                    }
+                    if !d.loading()
+                        && !d
+                            .load_sub_assets(&mut prefab_system_data)
+                            .with_context(|_| format_err!("Failed starting sub asset loading"))?
+                        && d.references.is_none()
+                    {
+                        return Ok(ProcessingState::Loaded(d));
                     }
-                    Completion::Loading => Ok(ProcessingState::Loading(d)),
+                    let references = d
+                        .references
+                        .as_ref()
+                        .map(ProgressCounter::complete)
+                        .unwrap_or(Completion::Complete);
+                    match (d.progress().complete(), references) {
+                        (Completion::Complete, Completion::Complete) => {
+                            Ok(ProcessingState::Loaded(d))
+                        }
+                        (Completion::Failed, _) => {
+                            error!("Failed loading sub asset: {:?}", d.progress().errors());
+                            Err(Error::from_string("Failed loading sub asset"))
+                        }
+                        (_, Completion::Failed) => {
+                            let errors = d.references.as_ref().map(ProgressCounter::errors);
+                            error!("Failed loading referenced prefab: {:?}", errors);
+                            Err(Error::from_string("Failed loading referenced prefab"))
+                        }
+                        _ => Ok(ProcessingState::Loading(d)),
+                    }
+                })();
+                if let Ok(ProcessingState::Loading(_)) = result {
+                    pending.insert(tag);
                 }
+                result
             },
             time.frame_number(),
             &**pool,
             strategy,
         );
+        // Prefabs still loading are processed every frame, so the references of prefabs which
+        // were not processed failed or are not needed anymore.
+        self.requested_references.retain(|tag| pending.contains(tag));
+        self.loaded_references.retain(|tag, _| pending.contains(tag));
+        if let Some(ref reference_loader) = self.reference_loader {
+            for (tag, references) in self.reference_requests.drain(..) {
+                let mut progress = ProgressCounter::new();
+                let handles = references
+                    .into_iter()
+                    .map(|(index, reference, ancestors)| {
+                        let handle = reference_loader(
+                            &loader,
+                            &reference,
+                            ancestors,
+                            &mut progress,
+                            &*prefab_storage,
+                        );
+                        (index, handle)
+                    })
+                    .collect();
+                self.loaded_references.insert(tag, (handles, progress));
+            }
+        }
         prefab_handles
             .channel()
             .read(self.insert_reader.as_mut().expect(
@@ -109,54 +289,31 @@ where
         for (root_entity, handle, _) in (&*entities, &prefab_handles, &self.to_process).join() {
             if let Some(prefab) = prefab_storage.get(handle) {
                 self.finished.push(root_entity);
-                // create entities
-                self.entities.clear();
-                self.entities.push(root_entity);
-
-                let mut children = HashMap::new();
-                for entity_data in prefab.entities.iter().skip(1) {
-                    let new_entity = entities.create();
-                    self.entities.push(new_entity);
-                    if let Some(parent) = entity_data.parent {
-                        parents
-                            .insert(
-                                new_entity,
-                                Parent {
-                                    entity: self.entities[parent],
-                                },
-                            )
-                            .expect("Unable to insert `Parent` for prefab");
-
-                        children
-                            .entry(parent)
-                            .or_insert(vec![])
-                            .push(new_entity.clone());
-                    }
-                    tags.insert(
-                        new_entity,
-                        PrefabTag::new(
-                            prefab.tag.expect(
-                                "Unreachable: Every loaded prefab should have a `PrefabTag`",
-                            ),
-                        ),
-                    )
-                    .expect("Unable to insert `PrefabTag` for prefab entity");
+                let tag = prefab
+                    .tag
+                    .expect("Unreachable: Every loaded prefab should have a `PrefabTag`");
+                let mut instance = Instance {
+                    entities: &entities,
+                    storage: &prefab_storage,
+                    parents: &mut parents,
+                    tags: &mut tags,
+                    system_data: &mut prefab_system_data,
+                    tag,
+                    reuse: Vec::new().into_iter(),
+                    created: Vec::new(),
+                    components: FnvHashMap::default(),
+                };
+                if let Err(err) = instance.instantiate(prefab, root_entity, 0) {
+                    error!("Failed instantiating prefab: {}", err);
                 }
-                // create components
-                for (index, entity_data) in prefab.entities.iter().enumerate() {
-                    if let Some(ref prefab_data) = &entity_data.data {
-                        prefab_data
-                            .add_to_entity(
-                                self.entities[index],
-                                &mut prefab_system_data,
-                                &self.entities,
-                                children
-                                    .get(&index)
-                                    .map(|children| &children[..])
-                                    .unwrap_or(&[]),
-                            )
-                            .expect("Unable to add prefab system data to entity");
-                    }
+                if self.reload_instances {
+                    let record = InstanceRecord {
+                        handle: handle.downgrade(),
+                        tag,
+                        entities: instance.created,
+                        components: instance.components,
+                    };
+                    self.instances.insert(root_entity, record);
                 }
             }
         }
@@ -164,6 +321,80 @@ where
         for entity in &self.finished {
             self.to_process.remove(entity.id());
         }
+
+        if self.reload_instances {
+            self.instances.retain(|root_entity, record| {
+                let handle = match record.handle.upgrade() {
+                    Some(handle) => handle,
+                    None => return false,
+                };
+                if !entities.is_alive(*root_entity) {
+                    return false;
+                }
+                let prefab = match prefab_storage.get(&handle) {
+                    Some(prefab) => prefab,
+                    None => return true,
+                };
+                let tag = prefab
+                    .tag
+                    .expect("Unreachable: Every loaded prefab should have a `PrefabTag`");
+                if tag == record.tag {
+                    return true;
+                }
+
+                debug!(
+                    "Updating instance of reloaded prefab (handle id: {:?}) on entity {:?}",
+                    handle, root_entity,
+                );
+                let reuse = record
+                    .entities
+                    .drain(..)
+                    .filter(|entity| entities.is_alive(*entity))
+                    .collect::<Vec<_>>();
+                let mut instance = Instance {
+                    entities: &entities,
+                    storage: &prefab_storage,
+                    parents: &mut parents,
+                    tags: &mut tags,
+                    system_data: &mut prefab_system_data,
+                    tag,
+                    reuse: reuse.into_iter(),
+                    created: Vec::new(),
+                    components: FnvHashMap::default(),
+                };
+                if let Err(err) = instance.instantiate(prefab, *root_entity, 0) {
+                    error!("Failed updating reloaded prefab: {}", err);
+                }
+                for (entity, old_types) in record.components.drain() {
+                    let stale = old_types
+                        .into_iter()
+                        .filter(|ty| {
+                            instance
+                                .components
+                                .get(&entity)
+                                .map(|types| !types.contains(ty))
+                                .unwrap_or(true)
+                        })
+                        .collect::<Vec<_>>();
+                    if !stale.is_empty() && entities.is_alive(entity) {
+                        if let Err(err) =
+                            T::remove_components(entity, &stale, &mut *instance.system_data)
+                        {
+                            error!("Failed removing components of reloaded prefab: {}", err);
+                        }
+                    }
+                }
+                for entity in instance.reuse {
+                    if let Err(err) = entities.delete(entity) {
+                        error!("Failed deleting entity removed from prefab: {:?}", err);
+                    }
+                }
+                record.tag = tag;
+                record.entities = instance.created;
+                record.components = instance.components;
+                true
+            });
+        }
     }
 
     fn setup(&mut self, res: &mut Resources) {
@@ -172,3 +403,507 @@ where
         self.insert_reader = Some(WriteStorage::<Handle<Prefab<T>>>::fetch(&res).register_reader());
     }
 }
+
+/// Returns the names of the prefabs referencing the prefab loaded for `reference`, starting with
+/// the outermost referenced prefab, or an error if loading it would close a reference cycle.
+fn reference_ancestors<T>(
+    prefab: &Prefab<T>,
+    reference: &PrefabReference<T>,
+    loader: &Loader,
+) -> Result<Vec<String>, Error> {
+    let name = match *reference {
+        PrefabReference::Handle(_) => return Ok(Vec::new()),
+        PrefabReference::File(ref name) => name.clone(),
+        PrefabReference::Id(ref id) => loader
+            .manifest()
+            .path(id)
+            .map(str::to_owned)
+            .unwrap_or_else(|| id.to_string()),
+    };
+    if prefab.ancestors.contains(&name) {
+        return Err(format_err!(
+            "Prefab references form a cycle: {} -> {}",
+            prefab.ancestors.join(" -> "),
+            name
+        ));
+    }
+    if prefab.ancestors.len() >= MAX_REFERENCE_DEPTH {
+        return Err(format_err!(
+            "Prefab references are nested more than {} levels deep",
+            MAX_REFERENCE_DEPTH
+        ));
+    }
+
+    let mut ancestors = prefab.ancestors.clone();
+    ancestors.push(name);
+    Ok(ancestors)
+}
+
+/// Format of referenced prefabs, which records the prefabs referencing them, so reference
+/// cycles are detected while loading.
+#[derive(Clone)]
+struct ReferencedFormat<F> {
+    format: F,
+    ancestors: Vec<String>,
+}
+
+impl<T, F> Format<Prefab<T>> for ReferencedFormat<F>
+where
+    T: Send + Sync + 'static,
+    F: Format<Prefab<T>>,
+{
+    const NAME: &'static str = F::NAME;
+    type Options = F::Options;
+
+    fn import(
+        &self,
+        name: String,
+        source: Arc<dyn Source>,
+        options: Self::Options,
+        create_reload: bool,
+    ) -> Result<FormatValue<Prefab<T>>, Error> {
+        let value = self.format.import(name, source, options, create_reload)?;
+        Ok(with_ancestors(value, self.ancestors.clone()))
+    }
+}
+
+/// Reload instructions of a referenced prefab, keeping the prefabs referencing it.
+struct ReferencedReload<T> {
+    inner: Box<dyn Reload<Prefab<T>>>,
+    ancestors: Vec<String>,
+}
+
+impl<T> Clone for ReferencedReload<T>
+where
+    T: Send + Sync + 'static,
+{
+    fn clone(&self) -> Self {
+        ReferencedReload {
+            inner: self.inner.clone(),
+            ancestors: self.ancestors.clone(),
+        }
+    }
+}
+
+impl<T> Reload<Prefab<T>> for ReferencedReload<T>
+where
+    T: Send + Sync + 'static,
+{
+    fn needs_reload(&self) -> bool {
+        self.inner.needs_reload()
+    }
+
+    fn name(&self) -> String {
+        self.inner.name()
+    }
+
+    fn format(&self) -> &'static str {
+        self.inner.format()
+    }
+
+    fn reload(self: Box<Self>) -> Result<FormatValue<Prefab<T>>, Error> {
+        let ReferencedReload { inner, ancestors } = *self;
+        Ok(with_ancestors(inner.reload()?, ancestors))
+    }
+}
+
+fn with_ancestors<T>(
+    value: FormatValue<Prefab<T>>,
+    ancestors: Vec<String>,
+) -> FormatValue<Prefab<T>>
+where
+    T: Send + Sync + 'static,
+{
+    let FormatValue { mut data, reload } = value;
+    data.ancestors = ancestors.clone();
+    let reload = reload
+        .map(|inner| Box::new(ReferencedReload { inner, ancestors }) as Box<dyn Reload<Prefab<T>>>);
+    FormatValue { data, reload }
+}
+
+/// Creates the entities of a prefab, including the entities of referenced prefabs.
+struct Instance<'s, 'a: 's, T>
+where
+    T: PrefabData<'a> + Send + Sync + 'static,
+{
+    entities: &'s Entities<'a>,
+    storage: &'s AssetStorage<Prefab<T>>,
+    parents: &'s mut WriteStorage<'a, Parent>,
+    tags: &'s mut WriteStorage<'a, PrefabTag<T>>,
+    system_data: &'s mut T::SystemData,
+    tag: u64,
+    /// Entities of a previous instance, which are reused before creating new ones.
+    reuse: std::vec::IntoIter<Entity>,
+    /// All entities created or reused, excluding the root entity.
+    created: Vec<Entity>,
+    /// The types of the components added to each entity.
+    components: FnvHashMap<Entity, Vec<TypeId>>,
+}
+
+impl<'s, 'a: 's, T> Instance<'s, 'a, T>
+where
+    T: PrefabData<'a> + Send + Sync + 'static,
+{
+    /// Instantiates `prefab` on `root_entity`, returning the created entities
+    /// and the children of each entity index.
+    fn instantiate(
+        &mut self,
+        prefab: &Prefab<T>,
+        root_entity: Entity,
+        depth: usize,
+    ) -> Result<(Vec<Entity>, HashMap<usize, Vec<Entity>>), Error> {
+        if depth > MAX_REFERENCE_DEPTH {
+            return Err(format_err!(
+                "Prefab references are nested more than {} levels deep, they probably contain a cycle",
+                MAX_REFERENCE_DEPTH
+            ));
+        }
+
+        // create entities
+        let mut created = vec![root_entity];
+        let mut children = HashMap::new();
+        for entity_data in prefab.entities.iter().skip(1) {
+            let new_entity = match self.reuse.next() {
+                Some(entity) => entity,
+                None => self.entities.create(),
+            };
+            self.created.push(new_entity);
+            created.push(new_entity);
+            if entity_data.parent.is_none() {
+                self.parents.remove(new_entity);
+            }
+            if let Some(parent) = entity_data.parent {
+                self.parents
+                    .insert(
+                        new_entity,
+                        Parent {
+                            entity: created[parent],
+                        },
+                    )
+                    .expect("Unable to insert `Parent` for prefab");
+
+                children
+                    .entry(parent)
+                    .or_insert(vec![])
+                    .push(new_entity.clone());
+            }
+            self.tags
+                .insert(new_entity, PrefabTag::new(self.tag))
+                .expect("Unable to insert `PrefabTag` for prefab entity");
+        }
+        // create components
+        for (index, entity_data) in prefab.entities.iter().enumerate() {
+            match entity_data.prefab {
+                Some(PrefabReference::Handle(ref handle)) => {
+                    let referenced = self
+                        .storage
+                        .get(handle)
+                        .ok_or_else(|| format_err!("Referenced prefab is not loaded"))?;
+                    let (sub_entities, sub_children) =
+                        self.instantiate(referenced, created[index], depth + 1)?;
+                    for (sub_index, data) in &entity_data.overrides {
+                        let entity = *sub_entities.get(*sub_index).ok_or_else(|| {
+                            format_err!(
+                                "Prefab override for entity {} is out of bounds, the referenced \
+                                 prefab has {} entities",
+                                sub_index,
+                                sub_entities.len()
+                            )
+                        })?;
+                        data.component_types(
+                            self.components.entry(entity).or_insert_with(Vec::new),
+                        );
+                        data.add_to_entity(
+                            entity,
+                            self.system_data,
+                            &sub_entities,
+                            sub_children
+                                .get(sub_index)
+                                .map(|children| &children[..])
+                                .unwrap_or(&[]),
+                        )
+                        .with_context(|_| format_err!("Unable to apply prefab override"))?;
+                    }
+                }
+                Some(_) => return Err(format_err!("Referenced prefab is not loaded")),
+                None => {}
+            }
+            if let Some(ref prefab_data) = &entity_data.data {
+                prefab_data.component_types(
+                    self.components
+                        .entry(created[index])
+                        .or_insert_with(Vec::new),
+                );
+                prefab_data
+                    .add_to_entity(
+                        created[index],
+                        self.system_data,
+                        &created,
+                        children
+                            .get(&index)
+                            .map(|children| &children[..])
+                            .unwrap_or(&[]),
+                    )
+                    .with_context(|_| format_err!("Unable to add prefab system data to entity"))?;
+            }
+        }
+
+        Ok((created, children))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{sync::Arc, thread, time::Duration};
+
+    use rayon::ThreadPoolBuilder;
+    use uuid::Uuid;
+
+    use amethyst_core::{
+        ecs::{Builder, Entity, Join, RunNow, World},
+        Named, Time, Transform,
+    };
+    use amethyst_error::Error;
+
+    use crate::{
+        storage::Processed, test_util::Files, AssetManifest, AssetStorage, Completion, FormatValue,
+        Handle, Loader, ProgressCounter, Reload, RonFormat,
+    };
+
+    use super::{Prefab, PrefabLoaderSystem};
+
+    const HOUSE_ID: &str = "1a7b0e48-5cf0-4b6e-9a51-6d2f1f9f8a31";
+
+    /// Loads the prefab `name` from `files` onto a new entity, and returns the completion of
+    /// the load and the names of all entities.
+    ///
+    /// Checks that no references are kept after the load finished or failed.
+    fn load(files: Files, name: &str) -> (Completion, Vec<String>) {
+        let pool = Arc::new(ThreadPoolBuilder::new().num_threads(2).build().unwrap());
+        let mut loader = Loader::with_default_source(files, pool.clone());
+        let mut manifest = AssetManifest::new();
+        manifest.insert(Uuid::parse_str(HOUSE_ID).unwrap(), "house.ron");
+        loader.set_manifest(manifest);
+
+        let mut world = World::new();
+        world.add_resource(pool);
+        world.add_resource(loader);
+        world.add_resource(Time::default());
+        let mut system =
+            PrefabLoaderSystem::<Named>::default().with_reference_format(RonFormat, ());
+        RunNow::setup(&mut system, &mut world.res);
+
+        let mut progress = ProgressCounter::new();
+        let handle = world.read_resource::<Loader>().load(
+            name,
+            RonFormat,
+            (),
+            &mut progress,
+            &world.read_resource::<AssetStorage<Prefab<Named>>>(),
+        );
+        world.create_entity().with(handle).build();
+
+        for _ in 0..1000 {
+            system.run_now(&world.res);
+            if progress.complete() != Completion::Loading {
+                system.run_now(&world.res);
+                assert!(system.requested_references.is_empty());
+                assert!(system.loaded_references.is_empty());
+                let mut names = world
+                    .read_storage::<Named>()
+                    .join()
+                    .map(|named| named.name.to_string())
+                    .collect::<Vec<_>>();
+                names.sort();
+                return (progress.complete(), names);
+            }
+            thread::sleep(Duration::from_millis(1));
+        }
+        panic!("Loading the prefab didn't finish");
+    }
+
+    const HOUSE: &str = r#"Prefab(
+        entities: [
+            (data: Some((name: "house"))),
+            (parent: Some(0), data: Some((name: "door"))),
+        ],
+    )"#;
+
+    #[test]
+    fn instantiates_file_references_with_overrides() {
+        let files = Files::new(vec![
+            (
+                "level.ron",
+                r#"Prefab(
+                    entities: [
+                        (data: Some((name: "level"))),
+                        (
+                            parent: Some(0),
+                            prefab: Some(File("house.ron")),
+                            data: Some((name: "red house")),
+                            overrides: [(1, (name: "red door"))],
+                        ),
+                    ],
+                )"#,
+            ),
+            ("house.ron", HOUSE),
+        ]);
+
+        let (completion, names) = load(files, "level.ron");
+        assert_eq!(Completion::Complete, completion);
+        assert_eq!(vec!["level", "red door", "red house"], names);
+    }
+
+    #[test]
+    fn instantiates_id_references() {
+        // The id of `house.ron` in the manifest, see `HOUSE_ID`.
+        let level = r#"Prefab(
+            entities: [
+                (data: Some((name: "level"))),
+                (parent: Some(0), prefab: Some(Id("1a7b0e48-5cf0-4b6e-9a51-6d2f1f9f8a31"))),
+            ],
+        )"#;
+        let files = Files::new(vec![("level.ron", level), ("house.ron", HOUSE)]);
+
+        let (completion, names) = load(files, "level.ron");
+        assert_eq!(Completion::Complete, completion);
+        assert_eq!(vec!["door", "house", "level"], names);
+    }
+
+    #[test]
+    fn reference_cycles_fail_to_load() {
+        let files = Files::new(vec![
+            (
+                "a.ron",
+                r#"Prefab(entities: [(data: Some((name: "a")), prefab: Some(File("b.ron")))])"#,
+            ),
+            (
+                "b.ron",
+                r#"Prefab(entities: [(data: Some((name: "b")), prefab: Some(File("a.ron")))])"#,
+            ),
+        ]);
+
+        let (completion, names) = load(files, "a.ron");
+        assert_eq!(Completion::Failed, completion);
+        assert!(names.is_empty());
+
+        let files = Files::new(vec![(
+            "self.ron",
+            r#"Prefab(entities: [(prefab: Some(File("self.ron")))])"#,
+        )]);
+        assert_eq!(Completion::Failed, load(files, "self.ron").0);
+    }
+
+    type Data = (Option<Named>, Option<Transform>);
+
+    /// Reload instructions of a prefab loaded from data, which never reload it.
+    #[derive(Clone)]
+    struct NoReload;
+
+    impl Reload<Prefab<Data>> for NoReload {
+        fn needs_reload(&self) -> bool {
+            false
+        }
+
+        fn name(&self) -> String {
+            "test".into()
+        }
+
+        fn format(&self) -> &'static str {
+            "test"
+        }
+
+        fn reload(self: Box<Self>) -> Result<FormatValue<Prefab<Data>>, Error> {
+            Err(Error::from_string("Can't reload test prefab"))
+        }
+    }
+
+    fn setup_instance_reload() -> (World, PrefabLoaderSystem<Data>) {
+        let pool = Arc::new(ThreadPoolBuilder::new().num_threads(1).build().unwrap());
+        let mut world = World::new();
+        world.add_resource(pool.clone());
+        world.add_resource(Loader::new(".", pool));
+        world.add_resource(Time::default());
+        let mut system = PrefabLoaderSystem::<Data>::default().with_instance_reload();
+        RunNow::setup(&mut system, &mut world.res);
+        (world, system)
+    }
+
+    fn reload(world: &World, handle: Handle<Prefab<Data>>, prefab: Prefab<Data>) {
+        world
+            .read_resource::<AssetStorage<Prefab<Data>>>()
+            .processed
+            .push(Processed::HotReload {
+                data: Ok(FormatValue::data(prefab)),
+                handle,
+                name: "test".into(),
+                old_reload: Box::new(NoReload),
+            });
+    }
+
+    fn find(world: &World, name: &str) -> Option<Entity> {
+        (&*world.entities(), &world.read_storage::<Named>())
+            .join()
+            .find(|(_, named)| named.name == name)
+            .map(|(entity, _)| entity)
+    }
+
+    #[test]
+    fn instance_reload_removes_components_the_prefab_no_longer_adds() {
+        let (mut world, mut system) = setup_instance_reload();
+        let mut prefab = Prefab::new_main((Some(Named::new("root")), Some(Transform::default())));
+        prefab.add(Some(0), Some((Some(Named::new("child")), None)));
+        let handle = world.read_resource::<Loader>().load_from_data(
+            prefab,
+            (),
+            &world.read_resource::<AssetStorage<Prefab<Data>>>(),
+        );
+        let root = world.create_entity().with(handle.clone()).build();
+        system.run_now(&world.res);
+
+        let child = find(&world, "child").expect("child entity was not created");
+        // Added by the game, not by the prefab.
+        world
+            .write_storage::<Transform>()
+            .insert(child, Transform::default())
+            .unwrap();
+
+        let mut prefab = Prefab::new_main((None, Some(Transform::default())));
+        prefab.add(Some(0), Some((Some(Named::new("renamed child")), None)));
+        reload(&world, handle, prefab);
+        system.run_now(&world.res);
+
+        let named = world.read_storage::<Named>();
+        let transforms = world.read_storage::<Transform>();
+        assert!(named.get(root).is_none());
+        assert!(transforms.get(root).is_some());
+        assert_eq!("renamed child", named.get(child).unwrap().name);
+        assert!(transforms.get(child).is_some());
+    }
+
+    #[test]
+    fn instance_reload_deletes_removed_entities() {
+        let (mut world, mut system) = setup_instance_reload();
+        let mut prefab = Prefab::new_main((Some(Named::new("root")), None));
+        prefab.add(Some(0), Some((Some(Named::new("child")), None)));
+        let handle = world.read_resource::<Loader>().load_from_data(
+            prefab,
+            (),
+            &world.read_resource::<AssetStorage<Prefab<Data>>>(),
+        );
+        world.create_entity().with(handle.clone()).build();
+        system.run_now(&world.res);
+        let child = find(&world, "child").expect("child entity was not created");
+
+        reload(
+            &world,
+            handle,
+            Prefab::new_main((Some(Named::new("root")), None)),
+        );
+        system.run_now(&world.res);
+        world.maintain();
+
+        assert!(!world.entities().is_alive(child));
+        assert!(find(&world, "root").is_some());
+    }
+}
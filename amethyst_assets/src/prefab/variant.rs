@@ -0,0 +1,381 @@
+use std::sync::Arc;
+
+use serde::{Deserialize, Serialize};
+
+#[cfg(feature = "profiler")]
+use thread_profiler::profile_scope;
+
+use amethyst_error::{format_err, Error, ResultExt};
+
+use crate::{Format, FormatValue, Reload, Source};
+
+use super::{Prefab, PrefabData};
+
+/// Maximum length of a chain of variants, to detect cycles.
+const MAX_VARIANT_DEPTH: usize = 32;
+
+/// Format for loading prefab variants, which declare a base prefab and override parts of it.
+///
+/// A prefab with a `base` is loaded by first loading the base prefab (which may be a variant
+/// itself) using the same format, and then merging the variant into it with `Prefab::merge`.
+/// Entity `n` of the variant overrides entity `n` of the base, so unchanged entities can be
+/// left empty, and fields of the data that are not set in the variant keep the value of the base
+/// (see `PrefabData::merge`). Prefabs without a base are loaded unchanged.
+///
+/// When reload instructions are requested, a change to the variant or any of its bases
+/// reloads the variant.
+///
+/// ### Example:
+///
+/// ```rust,ignore
+/// #![enable(implicit_some)]
+/// Prefab (
+///     base: "prefab/enemy.ron",
+///     entities: [
+///         // only changes the stats of the main entity, keeps everything else
+///         (data: (stats: (health: 200))),
+///         // keeps entity #1 as is
+///         (),
+///         // changes the mesh of entity #2
+///         (data: (mesh: File("mesh/big_sword.obj", ObjFormat, ()))),
+///     ],
+/// )
+/// ```
+///
+/// ```rust,ignore
+/// let handle = loader.load("prefab/boss.ron", PrefabVariantFormat::new(RonFormat), (), (), &storage);
+/// ```
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(transparent)]
+pub struct PrefabVariantFormat<F> {
+    format: F,
+}
+
+impl<F> PrefabVariantFormat<F> {
+    /// Wraps `format`, which is used to load the variant and all of its bases.
+    pub fn new(format: F) -> Self {
+        PrefabVariantFormat { format }
+    }
+
+    /// Loads the prefab `name` and merges it into its base, returning the merged prefab
+    /// and the names of all files it was loaded from.
+    fn import_variant<T>(
+        &self,
+        name: String,
+        source: &Arc<dyn Source>,
+        options: &F::Options,
+        depth: usize,
+        files: &mut Vec<String>,
+    ) -> Result<Prefab<T>, Error>
+    where
+        T: for<'a> PrefabData<'a> + Send + Sync + 'static,
+        F: Format<Prefab<T>>,
+        F::Options: Clone,
+    {
+        if depth > MAX_VARIANT_DEPTH {
+            return Err(format_err!(
+                "Prefab variants are nested more than {} levels deep, they probably contain a cycle",
+                MAX_VARIANT_DEPTH
+            ));
+        }
+
+        files.push(name.clone());
+        let mut variant = self
+            .format
+            .import(name.clone(), source.clone(), options.clone(), false)?
+            .data;
+
+        match variant.base.take() {
+            Some(base) => {
+                let mut prefab = self
+                    .import_variant(base.clone(), source, options, depth + 1, files)
+                    .with_context(|_| {
+                        format_err!(
+                            "Failed loading base {:?} of prefab variant {:?}",
+                            base,
+                            name
+                        )
+                    })?;
+                prefab.merge(variant);
+                Ok(prefab)
+            }
+            None => Ok(variant),
+        }
+    }
+}
+
+impl<T, F> Format<Prefab<T>> for PrefabVariantFormat<F>
+where
+    T: for<'a> PrefabData<'a> + Send + Sync + 'static,
+    F: Format<Prefab<T>> + Clone + Sync,
+    F::Options: Clone + Sync,
+{
+    const NAME: &'static str = F::NAME;
+    type Options = F::Options;
+
+    fn import(
+        &self,
+        name: String,
+        source: Arc<dyn Source>,
+        options: Self::Options,
+        create_reload: bool,
+    ) -> Result<FormatValue<Prefab<T>>, Error> {
+        #[cfg(feature = "profiler")]
+        profile_scope!("import_prefab_variant");
+
+        let mut files = Vec::new();
+        let data = self.import_variant(name.clone(), &source, &options, 0, &mut files)?;
+
+        let reload = if create_reload {
+            let files = files
+                .into_iter()
+                .map(|file| {
+                    let modified = source.modified(&file).unwrap_or(0);
+                    (file, modified)
+                })
+                .collect();
+            let reload = VariantReload {
+                format: self.clone(),
+                name,
+                source,
+                options,
+                files,
+            };
+            Some(Box::new(reload) as Box<dyn Reload<Prefab<T>>>)
+        } else {
+            None
+        };
+
+        Ok(FormatValue { data, reload })
+    }
+}
+
+/// Reload instructions of `PrefabVariantFormat`, which reload the variant if it or any of its
+/// bases changed.
+#[derive(Clone)]
+struct VariantReload<F, O> {
+    format: PrefabVariantFormat<F>,
+    name: String,
+    source: Arc<dyn Source>,
+    options: O,
+    files: Vec<(String, u64)>,
+}
+
+impl<T, F, O> Reload<Prefab<T>> for VariantReload<F, O>
+where
+    T: for<'a> PrefabData<'a> + Send + Sync + 'static,
+    F: Format<Prefab<T>, Options = O> + Clone + Sync,
+    O: Clone + Send + Sync + 'static,
+{
+    fn needs_reload(&self) -> bool {
+        self.files
+            .iter()
+            .any(|(file, modified)| self.source.modified(file).unwrap_or(0) != *modified)
+    }
+
+    fn name(&self) -> String {
+        self.name.clone()
+    }
+
+    fn format(&self) -> &'static str {
+        F::NAME
+    }
+
+    fn reload(self: Box<Self>) -> Result<FormatValue<Prefab<T>>, Error> {
+        #[cfg(feature = "profiler")]
+        profile_scope!("reload_prefab_variant");
+
+        let VariantReload {
+            format,
+            name,
+            source,
+            options,
+            ..
+        } = *self;
+
+        format.import(name, source, options, true)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{sync::Arc, thread, time::Duration};
+
+    use rayon::{ThreadPool, ThreadPoolBuilder};
+
+    use amethyst_core::{
+        ecs::{RunNow, World},
+        Named, Time,
+    };
+
+    use crate::{
+        test_util::Files, AssetStorage, Completion, Handle, HotReloadStrategy, HotReloadSystem,
+        Loader, ProcessingState, ProgressCounter, RonFormat,
+    };
+
+    use super::{Prefab, PrefabVariantFormat, MAX_VARIANT_DEPTH};
+
+    const CREATURE: &str = r#"Prefab(
+        entities: [
+            (data: Some((name: "creature"))),
+            (parent: Some(0), data: Some((name: "claw"))),
+        ],
+    )"#;
+
+    const ENEMY: &str = r#"Prefab(
+        base: Some("creature.ron"),
+        entities: [(data: Some((name: "enemy")))],
+    )"#;
+
+    const BOSS: &str = r#"Prefab(
+        base: Some("enemy.ron"),
+        entities: [(), (data: Some((name: "big claw")))],
+    )"#;
+
+    fn setup(files: Files) -> World {
+        let pool = Arc::new(ThreadPoolBuilder::new().num_threads(2).build().unwrap());
+        let mut world = World::new();
+        world.add_resource(Loader::with_default_source(files, pool.clone()));
+        world.add_resource(pool);
+        world.add_resource(Time::default());
+        world.add_resource(AssetStorage::<Prefab<Named>>::new());
+        world
+    }
+
+    fn process(world: &World, frame_number: u64, strategy: Option<&HotReloadStrategy>) {
+        let pool = world.read_resource::<Arc<ThreadPool>>().clone();
+        world
+            .write_resource::<AssetStorage<Prefab<Named>>>()
+            .process(
+                |prefab| Ok(ProcessingState::Loaded(prefab)),
+                frame_number,
+                &pool,
+                strategy,
+            );
+    }
+
+    /// Returns the names of the entities of the prefab `handle` points to, if it is loaded.
+    fn names(world: &World, handle: &Handle<Prefab<Named>>) -> Option<Vec<Option<String>>> {
+        world
+            .read_resource::<AssetStorage<Prefab<Named>>>()
+            .get(handle)
+            .map(|prefab| {
+                prefab
+                    .entities()
+                    .map(|entity| entity.data().map(|named| named.name.to_string()))
+                    .collect()
+            })
+    }
+
+    /// Loads the variant `name`, and returns the completion of the load and its handle.
+    fn load(world: &World, name: &str) -> (Completion, Handle<Prefab<Named>>) {
+        let mut progress = ProgressCounter::new();
+        let handle = world.read_resource::<Loader>().load(
+            name,
+            PrefabVariantFormat::new(RonFormat),
+            (),
+            &mut progress,
+            &world.read_resource::<AssetStorage<Prefab<Named>>>(),
+        );
+        for frame in 0..1000 {
+            process(world, frame, None);
+            if progress.complete() != Completion::Loading {
+                return (progress.complete(), handle);
+            }
+            thread::sleep(Duration::from_millis(1));
+        }
+        panic!("Loading the prefab variant didn't finish");
+    }
+
+    fn some(names: &[&str]) -> Option<Vec<Option<String>>> {
+        Some(names.iter().map(|name| Some(name.to_string())).collect())
+    }
+
+    #[test]
+    fn merges_variants_into_their_bases() {
+        let world = setup(Files::new(vec![
+            ("creature.ron", CREATURE),
+            ("enemy.ron", ENEMY),
+            ("boss.ron", BOSS),
+        ]));
+
+        let (completion, handle) = load(&world, "boss.ron");
+        assert_eq!(Completion::Complete, completion);
+        assert_eq!(some(&["enemy", "big claw"]), names(&world, &handle));
+    }
+
+    #[test]
+    fn cycles_and_too_deep_chains_fail_to_load() {
+        let world = setup(Files::new(vec![
+            ("a.ron", r#"Prefab(base: Some("b.ron"), entities: [])"#),
+            ("b.ron", r#"Prefab(base: Some("a.ron"), entities: [])"#),
+        ]));
+        assert_eq!(Completion::Failed, load(&world, "a.ron").0);
+
+        // `chain_0.ron` is a variant of `chain_1.ron` and so on, the last file has no base.
+        let chain = |len: usize| {
+            let files = (0..len)
+                .map(|i| {
+                    let contents = if i + 1 < len {
+                        format!(r#"Prefab(base: Some("chain_{}.ron"), entities: [])"#, i + 1)
+                    } else {
+                        r#"Prefab(entities: [(data: Some((name: "last")))])"#.to_owned()
+                    };
+                    (format!("chain_{}.ron", i), contents)
+                })
+                .collect::<Vec<_>>();
+            Files::new(
+                files
+                    .iter()
+                    .map(|(name, contents)| (name.as_str(), contents.as_str()))
+                    .collect(),
+            )
+        };
+        let world = setup(chain(MAX_VARIANT_DEPTH + 1));
+        let (completion, handle) = load(&world, "chain_0.ron");
+        assert_eq!(Completion::Complete, completion);
+        assert_eq!(some(&["last"]), names(&world, &handle));
+
+        let world = setup(chain(MAX_VARIANT_DEPTH + 2));
+        assert_eq!(Completion::Failed, load(&world, "chain_0.ron").0);
+    }
+
+    #[test]
+    fn reloads_when_a_base_changes() {
+        let files = Files::new(vec![
+            ("creature.ron", CREATURE),
+            ("enemy.ron", ENEMY),
+            ("boss.ron", BOSS),
+        ]);
+        let mut world = setup(files.clone());
+        let mut reload_system = HotReloadSystem::new(HotReloadStrategy::when_triggered());
+        RunNow::setup(&mut reload_system, &mut world.res);
+        let (_, handle) = load(&world, "boss.ron");
+
+        files.set(
+            "creature.ron",
+            r#"Prefab(
+                entities: [
+                    (data: Some((name: "creature"))),
+                    (parent: Some(0), data: Some((name: "claw"))),
+                    (parent: Some(0), data: Some((name: "tail"))),
+                ],
+            )"#,
+        );
+        world.write_resource::<HotReloadStrategy>().trigger();
+        reload_system.run_now(&world.res);
+        // The strategy reloads in the frame after the trigger.
+        let strategy = world.read_resource::<HotReloadStrategy>().clone();
+        process(&world, 1, Some(&strategy));
+
+        let expected = some(&["enemy", "big claw", "tail"]);
+        for frame in 2..1000 {
+            if names(&world, &handle) == expected {
+                return;
+            }
+            thread::sleep(Duration::from_millis(1));
+            process(&world, frame, None);
+        }
+        panic!("The variant was not reloaded after its base changed");
+    }
+}
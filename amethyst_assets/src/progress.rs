@@ -3,10 +3,14 @@ use std::sync::{
     Arc,
 };
 
+use serde::{Deserialize, Serialize};
+
 use amethyst_error::Error;
 use log::error;
 use parking_lot::Mutex;
 
+use crate::Source;
+
 /// Completion status, returned by `ProgressCounter::complete`.
 #[derive(Clone, Copy, Debug, Eq, PartialEq)]
 pub enum Completion {
@@ -18,6 +22,102 @@ pub enum Completion {
     Loading,
 }
 
+/// A stage of loading a single asset, reported to `LoadDetails::stage`.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq, Deserialize, Serialize)]
+pub enum LoadStage {
+    /// Bytes are being read from the source.
+    Reading,
+    /// The format is decoding the bytes into asset data.
+    Decoding,
+    /// The asset data waits to be processed by the `AssetStorage`.
+    Processing,
+}
+
+impl LoadStage {
+    fn index(self) -> usize {
+        match self {
+            LoadStage::Reading => 0,
+            LoadStage::Decoding => 1,
+            LoadStage::Processing => 2,
+        }
+    }
+}
+
+/// Receives detailed progress of a single asset load, see `Tracker::details`.
+pub trait LoadDetails: Send + Sync {
+    /// Called with the size of a file before it is read, if the source knows it.
+    fn add_total_bytes(&self, bytes: u64);
+    /// Called after bytes have been read from the source.
+    fn add_read_bytes(&self, bytes: u64);
+    /// Called when the load enters a new stage.
+    fn stage(&self, stage: LoadStage);
+}
+
+/// A `Source` reporting the bytes it loads to the `LoadDetails` of a tracker.
+///
+/// Reload instructions may keep the source alive after the asset finished loading,
+/// so reporting stops once `finish` is called.
+pub(crate) struct ReportingSource {
+    source: Arc<dyn Source>,
+    details: Mutex<Option<Arc<dyn LoadDetails>>>,
+}
+
+impl ReportingSource {
+    pub fn new(source: Arc<dyn Source>, details: Arc<dyn LoadDetails>) -> Self {
+        ReportingSource {
+            source,
+            details: Mutex::new(Some(details)),
+        }
+    }
+
+    /// Stops reporting to the tracker.
+    pub fn finish(&self) {
+        self.details.lock().take();
+    }
+
+    fn report<T, F>(&self, path: &str, load: F, bytes: fn(&T) -> &[u8]) -> Result<T, Error>
+    where
+        F: FnOnce(&dyn Source) -> Result<T, Error>,
+    {
+        let details = self.details.lock().clone();
+        if let Some(ref details) = details {
+            details.stage(LoadStage::Reading);
+            if let Some(size) = self.source.size(path) {
+                details.add_total_bytes(size);
+            }
+        }
+        let value = load(&*self.source)?;
+        if let Some(ref details) = details {
+            details.add_read_bytes(bytes(&value).len() as u64);
+            details.stage(LoadStage::Decoding);
+        }
+
+        Ok(value)
+    }
+}
+
+impl Source for ReportingSource {
+    fn modified(&self, path: &str) -> Result<u64, Error> {
+        self.source.modified(path)
+    }
+
+    fn load(&self, path: &str) -> Result<Vec<u8>, Error> {
+        self.report(path, |source| source.load(path), |bytes| bytes)
+    }
+
+    fn load_with_metadata(&self, path: &str) -> Result<(Vec<u8>, u64), Error> {
+        self.report(
+            path,
+            |source| source.load_with_metadata(path),
+            |(bytes, _)| bytes,
+        )
+    }
+
+    fn size(&self, path: &str) -> Option<u64> {
+        self.source.size(path)
+    }
+}
+
 /// The `Progress` trait, allowing to track which assets are
 /// imported already.
 pub trait Progress {
@@ -43,12 +143,22 @@ impl Progress for () {
 
 /// A progress tracker which is passed to the `Loader`
 /// in order to check how many assets are loaded.
+///
+/// Besides counting whole assets, the counter records how many bytes have been read
+/// and which stage the loading assets are in, so `fraction` can move smoothly while
+/// a single large asset is loading.
+///
+/// Counters can be nested into named groups (e.g. "level", "audio", "ui"), see `group`.
+/// All methods of a counter include the assets and errors of its groups.
 #[derive(Default)]
 pub struct ProgressCounter {
     errors: Arc<Mutex<Vec<AssetErrorMeta>>>,
     num_assets: usize,
+    num_cancelled: Arc<AtomicUsize>,
     num_failed: Arc<AtomicUsize>,
     num_loading: Arc<AtomicUsize>,
+    bytes: Arc<ByteCounter>,
+    groups: Vec<(String, ProgressCounter)>,
 }
 
 impl ProgressCounter {
@@ -57,38 +167,136 @@ impl ProgressCounter {
         Default::default()
     }
 
-    /// Removes all errors and returns them.
+    /// Returns the group with the given name, creating it if it doesn't exist.
+    ///
+    /// The group is a `ProgressCounter` of its own which can be passed to the `Loader`,
+    /// while this counter includes the group's assets and errors in its own.
+    pub fn group(&mut self, name: &str) -> &mut ProgressCounter {
+        let index = match self.groups.iter().position(|(n, _)| n == name) {
+            Some(index) => index,
+            None => {
+                self.groups.push((name.to_owned(), ProgressCounter::new()));
+                self.groups.len() - 1
+            }
+        };
+
+        &mut self.groups[index].1
+    }
+
+    /// Returns the group with the given name, if it exists.
+    pub fn get_group(&self, name: &str) -> Option<&ProgressCounter> {
+        self.groups
+            .iter()
+            .find(|(n, _)| n == name)
+            .map(|(_, group)| group)
+    }
+
+    /// Iterates over the names and counters of the direct groups of this counter.
+    pub fn groups(&self) -> impl Iterator<Item = (&str, &ProgressCounter)> {
+        self.groups
+            .iter()
+            .map(|(name, group)| (name.as_str(), group))
+    }
+
+    /// Removes all errors, including those of all groups, and returns them.
+    ///
+    /// Errors of a group have the path of the group (like `"level/audio"`)
+    /// in `AssetErrorMeta::group`.
     pub fn errors(&self) -> Vec<AssetErrorMeta> {
-        let mut lock = self.errors.lock();
-        lock.drain(..).collect()
+        let mut errors = {
+            let mut lock = self.errors.lock();
+            lock.drain(..).collect::<Vec<_>>()
+        };
+        for (name, group) in &self.groups {
+            errors.extend(group.errors().into_iter().map(|mut error| {
+                error.group = if error.group.is_empty() {
+                    name.clone()
+                } else {
+                    format!("{}/{}", name, error.group)
+                };
+                error
+            }));
+        }
+
+        errors
     }
 
     /// Returns the number of assets this struct is tracking.
     pub fn num_assets(&self) -> usize {
-        self.num_assets
+        self.num_assets + self.sum(ProgressCounter::num_assets)
     }
 
     /// Returns the number of assets that have failed.
     pub fn num_failed(&self) -> usize {
-        self.num_failed.load(Ordering::Relaxed)
+        self.num_failed.load(Ordering::Relaxed) + self.sum(ProgressCounter::num_failed)
     }
 
     /// Returns the number of assets that are still loading.
     pub fn num_loading(&self) -> usize {
-        self.num_loading.load(Ordering::Relaxed)
+        self.num_loading.load(Ordering::Relaxed) + self.sum(ProgressCounter::num_loading)
+    }
+
+    /// Returns the number of assets whose load was cancelled because the asset wasn't used
+    /// anymore, see `Tracker::cancel`.
+    ///
+    /// Cancelled assets don't keep the counter from completing.
+    pub fn num_cancelled(&self) -> usize {
+        self.num_cancelled.load(Ordering::Relaxed) + self.sum(ProgressCounter::num_cancelled)
     }
 
     /// Returns the number of assets that have successfully loaded.
     pub fn num_finished(&self) -> usize {
-        self.num_assets - self.num_loading() - self.num_failed()
+        self.num_assets() - self.num_loading() - self.num_failed() - self.num_cancelled()
+    }
+
+    /// Returns the number of loading assets which are currently in the given stage.
+    ///
+    /// Assets are only counted once a worker thread started loading them,
+    /// and only if the `Loader` reports stages for them.
+    pub fn num_in_stage(&self, stage: LoadStage) -> usize {
+        self.bytes.stages[stage.index()].load(Ordering::Relaxed)
+            + self.sum(|group| group.num_in_stage(stage))
+    }
+
+    /// Returns the number of bytes read so far.
+    pub fn bytes_read(&self) -> u64 {
+        self.bytes.read.load(Ordering::Relaxed) as u64 + self.sum(ProgressCounter::bytes_read)
+    }
+
+    /// Returns the total number of bytes of all files which have started loading.
+    ///
+    /// The size of a file is only known once a worker thread started reading it,
+    /// and only if its `Source` reports sizes.
+    pub fn bytes_total(&self) -> u64 {
+        self.bytes.total.load(Ordering::Relaxed) as u64 + self.sum(ProgressCounter::bytes_total)
+    }
+
+    /// Returns an estimate of the overall progress between `0.0` and `1.0`.
+    ///
+    /// Finished, failed and cancelled assets count fully. Loading assets whose size is known
+    /// count with the fraction of their bytes read so far, while assets without a known size,
+    /// like queued ones, don't count yet. An empty counter is complete (`1.0`).
+    pub fn fraction(&self) -> f32 {
+        let num_assets = self.num_assets();
+        if num_assets == 0 {
+            return 1.0;
+        }
+
+        let num_loading = self.num_loading();
+        let done = (num_assets - num_loading) as f32;
+        let (read, total, sized) = self.loading_bytes();
+        let partial = if total == 0 {
+            0.0
+        } else {
+            sized as f32 * (read as f32 / total as f32).min(1.0)
+        };
+
+        ((done + partial) / num_assets as f32).min(1.0)
     }
 
     /// Returns `Completion::Complete` if all tracked assets are finished.
     pub fn complete(&self) -> Completion {
-        match (
-            self.num_failed.load(Ordering::Relaxed),
-            self.num_loading.load(Ordering::Relaxed),
-        ) {
+        match (self.num_failed(), self.num_loading()) {
             (0, 0) => Completion::Complete,
             (0, _) => Completion::Loading,
             (_, _) => Completion::Failed,
@@ -99,6 +307,30 @@ impl ProgressCounter {
     pub fn is_complete(&self) -> bool {
         self.complete() == Completion::Complete
     }
+
+    /// Returns the bytes read and the total bytes of the assets which are still loading, and the
+    /// number of those assets which reported their size.
+    fn loading_bytes(&self) -> (usize, usize, usize) {
+        self.groups.iter().fold(
+            (
+                self.bytes.loading_read.load(Ordering::Relaxed),
+                self.bytes.loading_total.load(Ordering::Relaxed),
+                self.bytes.loading_sized.load(Ordering::Relaxed),
+            ),
+            |(read, total, sized), (_, group)| {
+                let (group_read, group_total, group_sized) = group.loading_bytes();
+                (read + group_read, total + group_total, sized + group_sized)
+            },
+        )
+    }
+
+    fn sum<T, F>(&self, f: F) -> T
+    where
+        T: std::iter::Sum<T>,
+        F: Fn(&ProgressCounter) -> T,
+    {
+        self.groups.iter().map(|(_, group)| f(group)).sum()
+    }
 }
 
 impl<'a> Progress for &'a mut ProgressCounter {
@@ -110,28 +342,107 @@ impl<'a> Progress for &'a mut ProgressCounter {
 
     fn create_tracker(self) -> Self::Tracker {
         let errors = self.errors.clone();
+        let num_cancelled = self.num_cancelled.clone();
         let num_failed = self.num_failed.clone();
         let num_loading = self.num_loading.clone();
         num_loading.fetch_add(1, Ordering::Relaxed);
 
         ProgressCounterTracker {
             errors,
+            num_cancelled,
             num_failed,
             num_loading,
+            details: Arc::new(TrackerDetails {
+                bytes: self.bytes.clone(),
+                read: AtomicUsize::new(0),
+                total: AtomicUsize::new(0),
+                stage: Mutex::new(None),
+            }),
         }
     }
 }
 
-/// Progress tracker for `ProgressCounter`.
+/// Byte and stage counts of a `ProgressCounter`.
 #[derive(Default)]
+struct ByteCounter {
+    read: AtomicUsize,
+    total: AtomicUsize,
+    /// The part of `read` and `total` of the assets which are still loading.
+    loading_read: AtomicUsize,
+    loading_total: AtomicUsize,
+    /// The number of assets which are still loading and reported their size.
+    loading_sized: AtomicUsize,
+    stages: [AtomicUsize; 3],
+}
+
+/// `LoadDetails` of a single asset tracked by a `ProgressCounter`.
+struct TrackerDetails {
+    bytes: Arc<ByteCounter>,
+    read: AtomicUsize,
+    total: AtomicUsize,
+    stage: Mutex<Option<LoadStage>>,
+}
+
+impl TrackerDetails {
+    /// Removes the asset from the loading assets of the counter.
+    fn finish(&self) {
+        self.set_stage(None);
+        self.bytes
+            .loading_read
+            .fetch_sub(self.read.load(Ordering::Relaxed), Ordering::Relaxed);
+        let total = self.total.load(Ordering::Relaxed);
+        self.bytes.loading_total.fetch_sub(total, Ordering::Relaxed);
+        if total > 0 {
+            self.bytes.loading_sized.fetch_sub(1, Ordering::Relaxed);
+        }
+    }
+
+    fn set_stage(&self, stage: Option<LoadStage>) {
+        let mut current = self.stage.lock();
+        if let Some(old) = current.take() {
+            self.bytes.stages[old.index()].fetch_sub(1, Ordering::Relaxed);
+        }
+        if let Some(new) = stage {
+            self.bytes.stages[new.index()].fetch_add(1, Ordering::Relaxed);
+        }
+        *current = stage;
+    }
+}
+
+impl LoadDetails for TrackerDetails {
+    fn add_total_bytes(&self, bytes: u64) {
+        let bytes = bytes as usize;
+        if self.total.fetch_add(bytes, Ordering::Relaxed) == 0 && bytes > 0 {
+            self.bytes.loading_sized.fetch_add(1, Ordering::Relaxed);
+        }
+        self.bytes.total.fetch_add(bytes, Ordering::Relaxed);
+        self.bytes.loading_total.fetch_add(bytes, Ordering::Relaxed);
+    }
+
+    fn add_read_bytes(&self, bytes: u64) {
+        let bytes = bytes as usize;
+        self.read.fetch_add(bytes, Ordering::Relaxed);
+        self.bytes.read.fetch_add(bytes, Ordering::Relaxed);
+        self.bytes.loading_read.fetch_add(bytes, Ordering::Relaxed);
+    }
+
+    fn stage(&self, stage: LoadStage) {
+        self.set_stage(Some(stage));
+    }
+}
+
+/// Progress tracker for `ProgressCounter`.
 pub struct ProgressCounterTracker {
     errors: Arc<Mutex<Vec<AssetErrorMeta>>>,
+    num_cancelled: Arc<AtomicUsize>,
     num_failed: Arc<AtomicUsize>,
     num_loading: Arc<AtomicUsize>,
+    details: Arc<TrackerDetails>,
 }
 
 impl Tracker for ProgressCounterTracker {
     fn success(self: Box<Self>) {
+        self.details.finish();
         self.num_loading.fetch_sub(1, Ordering::Relaxed);
     }
 
@@ -143,18 +454,29 @@ impl Tracker for ProgressCounterTracker {
         error: Error,
     ) {
         show_error(handle_id, asset_type_name, &asset_name, &error);
-        self.errors.lock().push(AssetErrorMeta {
+        self.details.finish();
+        self.errors.lock().push(AssetErrorMeta::new(
             error,
             handle_id,
             asset_type_name,
             asset_name,
-        });
+        ));
         self.num_failed.fetch_add(1, Ordering::Relaxed);
 
         // Failed assets are not requeued for loading, so we subtract it from the number that tracks
         // the assets that are still loading.
         self.num_loading.fetch_sub(1, Ordering::Relaxed);
     }
+
+    fn cancel(self: Box<Self>, _: u32, _: &'static str, _: String) {
+        self.details.finish();
+        self.num_cancelled.fetch_add(1, Ordering::Relaxed);
+        self.num_loading.fetch_sub(1, Ordering::Relaxed);
+    }
+
+    fn details(&self) -> Option<Arc<dyn LoadDetails>> {
+        Some(self.details.clone())
+    }
 }
 
 #[derive(Debug)]
@@ -163,6 +485,32 @@ pub struct AssetErrorMeta {
     pub handle_id: u32,
     pub asset_type_name: &'static str,
     pub asset_name: String,
+    /// The path of the `ProgressCounter` group the asset was loaded in, see `group`.
+    pub group: String,
+}
+
+impl AssetErrorMeta {
+    /// Creates the error of an asset loaded outside of any `ProgressCounter` group.
+    pub fn new(
+        error: Error,
+        handle_id: u32,
+        asset_type_name: &'static str,
+        asset_name: String,
+    ) -> Self {
+        AssetErrorMeta {
+            error,
+            handle_id,
+            asset_type_name,
+            asset_name,
+            group: String::new(),
+        }
+    }
+
+    /// Returns the path of the `ProgressCounter` group the asset was loaded in, like
+    /// `"level/audio"`, or an empty string for the root counter.
+    pub fn group(&self) -> &str {
+        &self.group
+    }
 }
 
 /// The `Tracker` trait which will be used by the loader to report
@@ -179,10 +527,28 @@ pub trait Tracker: Send + 'static {
         asset_name: String,
         error: Error,
     );
+    /// Called if the load was cancelled because all handles to the asset were dropped
+    /// before a worker thread started loading it.
+    ///
+    /// By default, this calls `fail` with an error saying that the load was cancelled.
+    /// `ProgressCounter` counts cancelled assets as done instead, see `num_cancelled`.
+    fn cancel(self: Box<Self>, handle_id: u32, asset_type_name: &'static str, asset_name: String) {
+        self.fail(
+            handle_id,
+            asset_type_name,
+            asset_name,
+            Error::from_string("Loading was cancelled because the asset isn't used anymore"),
+        );
+    }
+    /// Returns a receiver for the bytes read and the stages of this load, if supported.
+    fn details(&self) -> Option<Arc<dyn LoadDetails>> {
+        None
+    }
 }
 
 impl Tracker for () {
     fn success(self: Box<Self>) {}
+    fn cancel(self: Box<Self>, _: u32, _: &'static str, _: String) {}
     fn fail(
         self: Box<Self>,
         handle_id: u32,
@@ -211,7 +577,7 @@ fn show_error(handle_id: u32, asset_type_name: &'static str, asset_name: &String
 mod tests {
     use amethyst_error::Error;
 
-    use super::{Completion, Progress, ProgressCounter, Tracker};
+    use super::{Completion, LoadStage, Progress, ProgressCounter, Tracker};
 
     #[test]
     fn progress_counter_complete_returns_correct_completion_status_when_loading_or_complete() {
@@ -289,4 +655,108 @@ mod tests {
         tracker_2.success();
         assert_eq!(2, progress.num_finished());
     }
+
+    #[test]
+    fn progress_counter_counts_cancelled_assets_as_done() {
+        let mut progress_counter = ProgressCounter::new();
+        let mut progress = &mut progress_counter;
+        progress.add_assets(2);
+        let tracker_0 = Box::new(progress.create_tracker());
+        let tracker_1 = Box::new(progress.create_tracker());
+
+        tracker_0.cancel(1, "AssetType", String::from("test.asset"));
+        assert_eq!(1, progress.num_cancelled());
+        assert_eq!(0, progress.num_finished());
+        assert_eq!(Completion::Loading, progress.complete());
+
+        tracker_1.success();
+        assert_eq!(1, progress.num_finished());
+        assert_eq!(Completion::Complete, progress.complete());
+    }
+
+    #[test]
+    fn progress_counter_fraction_ignores_bytes_of_finished_assets() {
+        let mut progress_counter = ProgressCounter::new();
+        let mut progress = &mut progress_counter;
+        progress.add_assets(2);
+        let tracker_0 = Box::new(progress.create_tracker());
+        let tracker_1 = Box::new(progress.create_tracker());
+
+        let details = tracker_0.details().unwrap();
+        details.add_total_bytes(100);
+        details.add_read_bytes(100);
+        tracker_0.success();
+        tracker_1.details().unwrap().add_total_bytes(100);
+
+        assert_eq!(0.5, progress.fraction());
+        assert_eq!(100, progress.bytes_read());
+        assert_eq!(200, progress.bytes_total());
+    }
+
+    #[test]
+    fn progress_counter_fraction_ignores_assets_without_size() {
+        let mut progress_counter = ProgressCounter::new();
+        let mut progress = &mut progress_counter;
+        progress.add_assets(101);
+        let trackers = (0..101)
+            .map(|_| Box::new(progress.create_tracker()))
+            .collect::<Vec<_>>();
+
+        let details = trackers[0].details().unwrap();
+        details.add_total_bytes(100);
+        details.add_read_bytes(100);
+        assert_eq!(1.0 / 101.0, progress.fraction());
+
+        let details = trackers[1].details().unwrap();
+        details.add_total_bytes(300);
+        details.add_read_bytes(100);
+        // Byte ratio of the sized loads: 200 / 400
+        assert_eq!(2.0 * 0.5 / 101.0, progress.fraction());
+
+        for tracker in trackers.into_iter().skip(1) {
+            tracker.success();
+        }
+        assert_eq!(1.0, progress.fraction());
+    }
+
+    #[test]
+    fn progress_counter_groups_roll_up_to_parent() {
+        let mut progress_counter = ProgressCounter::new();
+        let audio_tracker = {
+            let mut audio = progress_counter.group("level").group("audio");
+            audio.add_assets(2);
+            (audio.create_tracker(), audio.create_tracker())
+        };
+        let details = audio_tracker.0.details().unwrap();
+        details.stage(LoadStage::Reading);
+        details.add_total_bytes(100);
+        details.add_read_bytes(50);
+
+        assert_eq!(2, progress_counter.num_assets());
+        assert_eq!(2, progress_counter.num_loading());
+        assert_eq!(1, progress_counter.num_in_stage(LoadStage::Reading));
+        assert_eq!(100, progress_counter.bytes_total());
+        // Only the asset which reported its size counts as half done.
+        assert_eq!(0.25, progress_counter.fraction());
+
+        Box::new(audio_tracker.0).success();
+        Box::new(audio_tracker.1).fail(
+            1,
+            "AssetType",
+            String::from("test.asset"),
+            Error::from_string(""),
+        );
+        assert_eq!(0, progress_counter.num_in_stage(LoadStage::Reading));
+        assert_eq!(Completion::Failed, progress_counter.complete());
+        assert_eq!(1.0, progress_counter.fraction());
+
+        let errors = progress_counter.errors();
+        assert_eq!(1, errors.len());
+        assert_eq!("level/audio", errors[0].group());
+        assert!(progress_counter
+            .get_group("level")
+            .unwrap()
+            .errors()
+            .is_empty());
+    }
 }
@@ -0,0 +1,264 @@
+//! Prioritized queue of pending load requests.
+
+use std::{
+    any::TypeId,
+    cmp::Ordering as CmpOrdering,
+    collections::BinaryHeap,
+    mem,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
+};
+
+use parking_lot::Mutex;
+use rayon::ThreadPool;
+use serde::{Deserialize, Serialize};
+
+/// Priority of a load request. Pending requests with a higher priority are started first,
+/// requests with the same priority are started in the order they were made.
+#[derive(Clone, Copy, Debug, Eq, Hash, Ord, PartialEq, PartialOrd, Deserialize, Serialize)]
+pub enum Priority {
+    /// Assets which may be needed in the future, e.g. the next area of a level.
+    Prefetch,
+    /// Assets which are not needed right away.
+    Low,
+    /// The default priority.
+    Normal,
+    /// Assets which are needed soon.
+    High,
+    /// Assets which are needed right now, e.g. because they are visible.
+    Immediate,
+}
+
+impl Default for Priority {
+    fn default() -> Self {
+        Priority::Normal
+    }
+}
+
+/// Identifies the job loading a given asset; the type is needed because
+/// handle ids are only unique per `AssetStorage`.
+pub(crate) type JobKey = (TypeId, u32);
+
+struct Job {
+    priority: Priority,
+    seq: usize,
+    key: JobKey,
+    run: Box<dyn FnOnce() + Send>,
+}
+
+impl PartialEq for Job {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == CmpOrdering::Equal
+    }
+}
+
+impl Eq for Job {}
+
+impl PartialOrd for Job {
+    fn partial_cmp(&self, other: &Self) -> Option<CmpOrdering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Job {
+    fn cmp(&self, other: &Self) -> CmpOrdering {
+        // `BinaryHeap` is a max-heap, so older jobs need to compare greater.
+        self.priority
+            .cmp(&other.priority)
+            .then_with(|| other.seq.cmp(&self.seq))
+    }
+}
+
+/// Queue of load jobs waiting for a worker thread.
+///
+/// Pushing a job spawns a task on the thread pool unless the maximum number of tasks is
+/// already running. A task runs the pending jobs in order of their priority until the queue
+/// is empty, so a task doesn't necessarily run the job it was spawned for.
+#[derive(Default)]
+pub(crate) struct LoadQueue {
+    jobs: Mutex<BinaryHeap<Job>>,
+    seq: AtomicUsize,
+    workers: Mutex<Workers>,
+}
+
+/// The number of tasks running jobs, and the maximum number of them.
+#[derive(Default)]
+struct Workers {
+    running: usize,
+    limit: Option<usize>,
+}
+
+impl Workers {
+    fn can_spawn(&self) -> bool {
+        self.limit.map(|limit| self.running < limit).unwrap_or(true)
+    }
+}
+
+impl LoadQueue {
+    pub fn push<F>(queue: &Arc<Self>, pool: &ThreadPool, priority: Priority, key: JobKey, run: F)
+    where
+        F: FnOnce() + Send + 'static,
+    {
+        let seq = queue.seq.fetch_add(1, Ordering::Relaxed);
+        // Lock the workers first, so a task can't stop between pushing the job and
+        // checking whether another task is needed.
+        let mut workers = queue.workers.lock();
+        queue.jobs.lock().push(Job {
+            priority,
+            seq,
+            key,
+            run: Box::new(run),
+        });
+
+        if workers.can_spawn() {
+            Self::spawn(queue, pool, &mut workers);
+        }
+    }
+
+    /// Limits the number of jobs running at the same time, `None` removes the limit.
+    ///
+    /// If the limit is raised, tasks are spawned for the pending jobs.
+    pub fn set_limit(queue: &Arc<Self>, pool: &ThreadPool, limit: Option<usize>) {
+        let mut workers = queue.workers.lock();
+        workers.limit = limit.map(|limit| limit.max(1));
+
+        let pending = queue.jobs.lock().len();
+        let mut spawned = 0;
+        while spawned < pending && workers.can_spawn() {
+            Self::spawn(queue, pool, &mut workers);
+            spawned += 1;
+        }
+    }
+
+    fn spawn(queue: &Arc<Self>, pool: &ThreadPool, workers: &mut Workers) {
+        workers.running += 1;
+        let queue = queue.clone();
+        pool.spawn(move || queue.run_jobs());
+    }
+
+    fn run_jobs(&self) {
+        loop {
+            // Don't hold the locks while running the job.
+            let job = {
+                let mut workers = self.workers.lock();
+                let job = self.jobs.lock().pop();
+                if job.is_none() {
+                    workers.running -= 1;
+                }
+                job
+            };
+
+            match job {
+                Some(job) => (job.run)(),
+                None => return,
+            }
+        }
+    }
+
+    /// Changes the priority of a pending job.
+    /// Returns `false` if there is no such job, e.g. because it has already started.
+    pub fn reprioritize(&self, key: JobKey, priority: Priority) -> bool {
+        let mut jobs = self.jobs.lock();
+        let mut pending = mem::replace(&mut *jobs, BinaryHeap::new()).into_vec();
+        let found = match pending.iter_mut().find(|job| job.key == key) {
+            Some(job) => {
+                job.priority = priority;
+                true
+            }
+            None => false,
+        };
+        *jobs = pending.into();
+
+        found
+    }
+
+    /// Returns the number of jobs which haven't started yet.
+    pub fn len(&self) -> usize {
+        self.jobs.lock().len()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{any::TypeId, sync::Arc};
+
+    use parking_lot::Mutex;
+    use rayon::ThreadPoolBuilder;
+
+    use super::{LoadQueue, Priority};
+
+    #[test]
+    fn jobs_run_by_priority_then_age() {
+        let queue = Arc::new(LoadQueue::default());
+        let order = Arc::new(Mutex::new(Vec::new()));
+        let pool = ThreadPoolBuilder::new().num_threads(1).build().unwrap();
+
+        // Block the only worker, so all jobs are queued before the first one runs.
+        let (tx, rx) = std::sync::mpsc::channel::<()>();
+        pool.spawn(move || {
+            let _ = rx.recv();
+        });
+
+        let jobs = vec![
+            ("prefetch", Priority::Prefetch),
+            ("normal_a", Priority::Normal),
+            ("immediate", Priority::Immediate),
+            ("normal_b", Priority::Normal),
+        ];
+        for (id, (name, priority)) in jobs.into_iter().enumerate() {
+            let order = order.clone();
+            let key = (TypeId::of::<()>(), id as u32);
+            LoadQueue::push(&queue, &pool, priority, key, move || {
+                order.lock().push(name)
+            });
+        }
+        assert!(queue.reprioritize((TypeId::of::<()>(), 3), Priority::High));
+        assert!(!queue.reprioritize((TypeId::of::<()>(), 4), Priority::High));
+        tx.send(()).unwrap();
+
+        while order.lock().len() < 4 {
+            std::thread::yield_now();
+        }
+
+        assert_eq!(
+            vec!["immediate", "normal_b", "normal_a", "prefetch"],
+            *order.lock()
+        );
+    }
+
+    #[test]
+    fn limits_jobs_running_at_the_same_time() {
+        use std::sync::atomic::{AtomicUsize, Ordering};
+
+        let queue = Arc::new(LoadQueue::default());
+        let pool = ThreadPoolBuilder::new().num_threads(4).build().unwrap();
+        LoadQueue::set_limit(&queue, &pool, Some(2));
+
+        let running = Arc::new(AtomicUsize::new(0));
+        let max_running = Arc::new(Mutex::new(0));
+        let finished = Arc::new(AtomicUsize::new(0));
+        for id in 0..8 {
+            let running = running.clone();
+            let max_running = max_running.clone();
+            let finished = finished.clone();
+            let key = (TypeId::of::<()>(), id);
+            LoadQueue::push(&queue, &pool, Priority::Normal, key, move || {
+                let now = running.fetch_add(1, Ordering::SeqCst) + 1;
+                {
+                    let mut max_running = max_running.lock();
+                    *max_running = now.max(*max_running);
+                }
+                std::thread::sleep(std::time::Duration::from_millis(5));
+                running.fetch_sub(1, Ordering::SeqCst);
+                finished.fetch_add(1, Ordering::SeqCst);
+            });
+        }
+
+        while finished.load(Ordering::SeqCst) < 8 {
+            std::thread::yield_now();
+        }
+        assert!(*max_running.lock() <= 2);
+    }
+}
@@ -183,6 +183,23 @@ impl<'a> System<'a> for HotReloadSystem {
 pub trait Reload<A: Asset>: ReloadClone<A> + Send + Sync + 'static {
     /// Checks if a reload is necessary.
     fn needs_reload(&self) -> bool;
+    /// Checks if a reload is necessary independent of the `HotReloadStrategy`.
+    ///
+    /// Unlike `needs_reload`, this is checked by the `AssetStorage` every frame, even without
+    /// hot reloading, so it has to be cheap. `LocalizedFormat` uses it to load another variant
+    /// after the languages changed.
+    ///
+    /// Only called if `reloads_immediately` returns `true`.
+    fn needs_immediate_reload(&self) -> bool {
+        false
+    }
+    /// Returns `true` if `needs_immediate_reload` can return `true`.
+    ///
+    /// The `AssetStorage` only checks for immediate reloads while it holds reload objects
+    /// returning `true` here, so storages without them don't pay for the check every frame.
+    fn reloads_immediately(&self) -> bool {
+        false
+    }
     /// Returns the asset name.
     fn name(&self) -> String;
     /// Returns the format name.
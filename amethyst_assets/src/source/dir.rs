@@ -75,6 +75,10 @@ impl Source for Directory {
 
         Ok(v)
     }
+
+    fn size(&self, path: &str) -> Option<u64> {
+        std::fs::metadata(self.path(path)).ok().map(|m| m.len())
+    }
 }
 
 #[cfg(test)]
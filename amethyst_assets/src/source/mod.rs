@@ -20,6 +20,12 @@ pub trait Source: Send + Sync + 'static {
     /// The id should always use `/` as separator in paths.
     fn load(&self, path: &str) -> Result<Vec<u8>, Error>;
 
+    /// Returns the size of the file at `path` in bytes, if it can be determined
+    /// without loading it. This is used for reporting loading progress.
+    fn size(&self, _path: &str) -> Option<u64> {
+        None
+    }
+
     /// Returns both the result of `load` and `modified` as a tuple.
     /// There's a default implementation which just calls both methods,
     /// but you may be able to provide a more optimized version yourself.
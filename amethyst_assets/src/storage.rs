@@ -1,13 +1,16 @@
 use std::{
+    any::Any,
     marker::PhantomData,
     sync::{
         atomic::{AtomicUsize, Ordering},
         Arc, Mutex, Weak,
     },
+    time::Instant,
 };
 
 use crossbeam::queue::MsQueue;
 use derivative::Derivative;
+use fnv::{FnvHashMap, FnvHashSet};
 use hibitset::BitSet;
 use log::{debug, error, trace, warn};
 use rayon::ThreadPool;
@@ -22,8 +25,10 @@ use amethyst_core::{
 use amethyst_error::{Error, ResultExt};
 
 use crate::{
-    asset::{Asset, FormatValue},
+    asset::{Asset, Format, FormatValue},
+    diagnostics::PendingTiming,
     error,
+    memory::{AssetSize, MemoryTracker},
     progress::Tracker,
     reload::{HotReloadStrategy, Reload},
 };
@@ -43,6 +48,18 @@ impl Allocator {
 
 /// An asset storage, storing the actual assets and allocating
 /// handles to them.
+///
+/// ## Memory budget
+///
+/// By default, an asset is freed as soon as the last `Handle` to it is dropped.
+/// If the asset type implements `AssetSize`, a memory budget can be set with
+/// `set_memory_budget`. Assets which are then only referenced by a `WeakHandle`
+/// (e.g. from a `Cache`) are kept alive until the storage exceeds its budget, at which
+/// point the least recently used ones are evicted.
+///
+/// If the `Loader` created reload information for an evicted asset
+/// (see `Loader::set_hot_reload`), upgrading one of its weak handles again
+/// will transparently reload the asset; until that finished, `get` returns `None`.
 pub struct AssetStorage<A: Asset> {
     assets: VecStorage<A>,
     bitset: BitSet,
@@ -52,8 +69,21 @@ pub struct AssetStorage<A: Asset> {
     reloads: Vec<(WeakHandle<A>, Box<dyn Reload<A>>)>,
     unused_handles: MsQueue<Handle<A>>,
     requeue: Mutex<Vec<Processed<A>>>,
+    memory: MemoryTracker<A>,
+    last_used: FnvHashMap<u32, u64>,
+    evicted: Vec<(Handle<A>, Box<dyn Reload<A>>)>,
+    names: FnvHashMap<u32, String>,
+    /// Ids of the assets borrowed with `get_mut`, which are measured again in `process`.
+    mutated: FnvHashSet<u32>,
+    /// Whether any reload object in `reloads` returns `true` from `reloads_immediately`.
+    immediate_reloads: bool,
+    /// The formats and options recorded with `record_format`, if enabled with `record_formats`.
+    formats: Option<Mutex<FnvHashMap<u32, (WeakHandle<A>, Box<dyn Any + Send>)>>>,
 }
 
+/// Name of the assets loaded with `Loader::load_from_data`, used in log messages.
+pub(crate) const DATA_NAME: &str = "<Data>";
+
 /// Returned by processor systems, describes the loading state of the asset.
 pub enum ProcessingState<A>
 where
@@ -101,6 +131,10 @@ impl<A: Asset> AssetStorage<A> {
             let id = h.id();
             self.bitset.add(id);
             self.handles.push(h.clone());
+            self.memory.insert(id, &asset);
+            if let Some(name) = self.names.get(&handle.id()).cloned() {
+                self.names.insert(id, name);
+            }
 
             unsafe {
                 self.assets.insert(id, asset);
@@ -122,14 +156,127 @@ impl<A: Asset> AssetStorage<A> {
     }
 
     /// Get an asset mutably from a given asset handle.
+    ///
+    /// If memory tracking is enabled, the size of the asset is measured again the next time the
+    /// storage is processed, before assets are evicted.
     pub fn get_mut(&mut self, handle: &Handle<A>) -> Option<&mut A> {
         if self.bitset.contains(handle.id()) {
+            if self.memory.enabled() {
+                self.mutated.insert(handle.id());
+            }
             Some(unsafe { self.assets.get_mut(handle.id()) })
         } else {
             None
         }
     }
 
+    /// Starts measuring the memory used by the assets of this storage.
+    ///
+    /// Only assets inserted after this call are accounted for, so this
+    /// should be called before the first asset is loaded.
+    pub fn track_memory(&mut self)
+    where
+        A: AssetSize,
+    {
+        self.memory.enable(A::asset_size);
+    }
+
+    /// Sets the memory budget of this storage in bytes, enabling memory tracking
+    /// if it wasn't already. `None` removes the budget.
+    ///
+    /// See the type-level documentation for how the budget is enforced.
+    pub fn set_memory_budget(&mut self, budget: Option<usize>)
+    where
+        A: AssetSize,
+    {
+        if !self.memory.enabled() {
+            self.track_memory();
+        }
+        self.memory.set_budget(budget);
+    }
+
+    /// Returns the memory budget of this storage in bytes, if any.
+    pub fn memory_budget(&self) -> Option<usize> {
+        self.memory.budget()
+    }
+
+    /// Returns the number of bytes occupied by all assets in this storage,
+    /// or `None` if memory tracking is not enabled.
+    pub fn memory_usage(&self) -> Option<usize> {
+        self.memory.usage()
+    }
+
+    /// Returns the number of bytes occupied by the asset `handle` points to,
+    /// or `None` if it isn't loaded or memory tracking is not enabled.
+    pub fn asset_size(&self, handle: &Handle<A>) -> Option<usize> {
+        self.memory.size(handle.id())
+    }
+
+    /// Returns the name the asset `handle` points to was loaded with,
+    /// e.g. its path in the source, or `None` if it isn't loaded.
+    ///
+    /// Assets loaded with `Loader::load_from_data` have no name.
+    pub fn name(&self, handle: &Handle<A>) -> Option<&str> {
+        self.names
+            .get(&handle.id())
+            .map(String::as_str)
+            .filter(|name| *name != DATA_NAME)
+    }
+
+    /// Starts recording the `Format` and options assets of this storage are loaded with by
+    /// prefabs, e.g. `AssetPrefab`.
+    ///
+    /// Extracting prefab data which refers to assets needs these records to write the asset
+    /// back out, so this should be called before such prefabs are loaded.
+    pub fn record_formats(&mut self) {
+        if self.formats.is_none() {
+            self.formats = Some(Mutex::new(FnvHashMap::default()));
+        }
+    }
+
+    /// Records that the asset `handle` points to is loaded with `format` and `options`.
+    ///
+    /// Does nothing unless recording was enabled with `record_formats`.
+    pub fn record_format<F>(&self, handle: &Handle<A>, format: &F, options: &F::Options)
+    where
+        F: Format<A> + Clone,
+        F::Options: Clone,
+    {
+        if let Some(ref formats) = self.formats {
+            formats.lock().unwrap().insert(
+                handle.id(),
+                (
+                    handle.downgrade(),
+                    Box::new((format.clone(), options.clone())),
+                ),
+            );
+        }
+    }
+
+    /// Returns the format and options recorded for `handle` with `record_format`, or `None` if
+    /// recording is disabled or none were recorded with format `F`.
+    pub fn format<F>(&self, handle: &Handle<A>) -> Option<(F, F::Options)>
+    where
+        F: Format<A> + Clone,
+        F::Options: Clone,
+    {
+        let formats = self.formats.as_ref()?.lock().unwrap();
+        match formats.get(&handle.id()) {
+            Some((weak, record)) if !weak.is_dead() => {
+                record.downcast_ref::<(F, F::Options)>().cloned()
+            }
+            _ => None,
+        }
+    }
+
+    /// Forgets everything recorded about the asset `id`, once the id is recycled.
+    fn forget(&mut self, id: u32) {
+        self.names.remove(&id);
+        if let Some(ref mut formats) = self.formats {
+            formats.get_mut().unwrap().remove(&id);
+        }
+    }
+
     /// Process finished asset data and maintain the storage.
     pub fn process<F>(
         &mut self,
@@ -166,6 +313,9 @@ impl<A: Asset> AssetStorage<A> {
                 let bitset = &mut self.bitset;
                 let handles = &mut self.handles;
                 let reloads = &mut self.reloads;
+                let memory = &mut self.memory;
+                let names = &mut self.names;
+                let immediate_reloads = &mut self.immediate_reloads;
 
                 let f = &mut f;
                 let (reload_obj, handle) = match processed {
@@ -174,12 +324,18 @@ impl<A: Asset> AssetStorage<A> {
                         handle,
                         name,
                         tracker,
+                        mut timing,
                     } => {
-                        let (asset, reload_obj) = match data
+                        let asset_name = name.clone();
+                        let processing_start = Instant::now();
+                        let result = data
                             .map(|FormatValue { data, reload }| (data, reload))
                             .and_then(|(d, rel)| f(d).map(|a| (a, rel)))
-                            .with_context(|_| error::Error::Asset(name.clone()))
-                        {
+                            .with_context(|_| error::Error::Asset(name.clone()));
+                        if let Some(ref mut timing) = timing {
+                            timing.add_processing(processing_start.elapsed());
+                        }
+                        let (asset, reload_obj) = match result {
                             Ok((ProcessingState::Loaded(x), r)) => {
                                 debug!(
                                         "{:?}: Asset {:?} (handle id: {:?}) has been loaded successfully",
@@ -204,6 +360,9 @@ impl<A: Asset> AssetStorage<A> {
                                 } else {
                                     tracker.success();
                                 }
+                                if let Some(timing) = timing {
+                                    timing.finish(None);
+                                }
 
                                 (x, r)
                             }
@@ -219,6 +378,7 @@ impl<A: Asset> AssetStorage<A> {
                                     handle,
                                     name,
                                     tracker,
+                                    timing,
                                 });
                                 continue;
                             }
@@ -230,6 +390,9 @@ impl<A: Asset> AssetStorage<A> {
                                     handle,
                                     e,
                                 );
+                                if let Some(timing) = timing {
+                                    timing.finish(Some(e.to_string()));
+                                }
                                 tracker.fail(handle.id(), A::NAME, name, e);
 
                                 continue;
@@ -239,6 +402,8 @@ impl<A: Asset> AssetStorage<A> {
                         let id = handle.id();
                         bitset.add(id);
                         handles.push(handle.clone());
+                        memory.insert(id, &asset);
+                        names.insert(id, asset_name);
 
                         // NOTE: the loader has to ensure that a handle will be used
                         // together with a `Data` only once.
@@ -285,6 +450,7 @@ impl<A: Asset> AssetStorage<A> {
                                     e,
                                 );
 
+                                *immediate_reloads |= old_reload.reloads_immediately();
                                 reloads.push((handle.downgrade(), old_reload));
 
                                 continue;
@@ -297,6 +463,7 @@ impl<A: Asset> AssetStorage<A> {
                             "Expected handle {:?} to be valid, but the asset storage says otherwise",
                             handle,
                         );
+                        memory.insert(id, &asset);
                         unsafe {
                             let old = assets.get_mut(id);
                             *old = asset;
@@ -304,10 +471,21 @@ impl<A: Asset> AssetStorage<A> {
 
                         (reload_obj, handle)
                     }
+                    Processed::Cancelled { id } => {
+                        if let Some(ref mut formats) = self.formats {
+                            formats.get_mut().unwrap().remove(&id);
+                        }
+                        self.unused_handles.push(Handle {
+                            id: Arc::new(id),
+                            marker: PhantomData,
+                        });
+                        continue;
+                    }
                 };
 
                 // Add the reload obj if it is `Some`.
                 if let Some(reload_obj) = reload_obj {
+                    *immediate_reloads |= reload_obj.reloads_immediately();
                     reloads.push((handle.downgrade(), reload_obj));
                 }
             }
@@ -317,46 +495,227 @@ impl<A: Asset> AssetStorage<A> {
             }
         }
 
+        let cached = self.weakly_referenced();
         let mut count = 0;
         let mut skip = 0;
-        while let Some(i) = self.handles.iter().skip(skip).position(Handle::is_unique) {
+        while let Some(i) = self
+            .handles
+            .iter()
+            .skip(skip)
+            .position(|h| h.is_unique() && !cached.contains(&h.id()))
+        {
             count += 1;
             // Re-normalize index
             let i = skip + i;
             skip = i;
             let handle = self.handles.swap_remove(i);
-            let id = handle.id();
-            unsafe {
-                drop_fn(self.assets.remove(id));
-            }
-            self.bitset.remove(id);
-
-            // Can't reuse old handle here, because otherwise weak handles would still be valid.
-            // TODO: maybe just store u32?
-            self.unused_handles.push(Handle {
-                id: Arc::new(id),
-                marker: PhantomData,
-            });
+            self.unload(handle, &mut drop_fn, false);
         }
         if count != 0 {
             debug!("{:?}: Freed {} handle ids", A::NAME, count,);
         }
 
+        for id in self.mutated.drain() {
+            if self.bitset.contains(id) {
+                self.memory.insert(id, unsafe { self.assets.get(id) });
+            }
+        }
+        if let Some(budget) = self.memory.budget() {
+            self.evict(budget, frame_number, &mut drop_fn);
+        }
+        self.reload_evicted(pool);
+
         if strategy
             .map(|s| s.needs_reload(frame_number))
             .unwrap_or(false)
         {
             trace!("{:?}: Testing for asset reloads..", A::NAME);
-            self.hot_reload(pool);
+            self.hot_reload(pool, |rel| rel.needs_reload());
+        } else if self.immediate_reloads {
+            self.hot_reload(pool, |rel| {
+                rel.reloads_immediately() && rel.needs_immediate_reload()
+            });
+        }
+        if self.immediate_reloads {
+            // Reload objects being reloaded are added again when the reload finished.
+            self.immediate_reloads = self
+                .reloads
+                .iter()
+                .any(|(_, rel)| rel.reloads_immediately());
+        }
+    }
+
+    /// Returns the ids of unused assets which are still referenced by a `WeakHandle`
+    /// outside of this storage.
+    ///
+    /// This is only computed if a memory budget is set; without one, weakly
+    /// referenced assets are freed like any other.
+    fn weakly_referenced(&self) -> FnvHashSet<u32> {
+        if self.memory.budget().is_none() {
+            return FnvHashSet::default();
+        }
+        // The storage holds weak handles of its own for the reload objects.
+        let mut own = FnvHashMap::default();
+        for handle in self.reloads.iter().filter_map(|(h, _)| h.upgrade()) {
+            *own.entry(handle.id()).or_insert(0) += 1;
+        }
+        self.handles
+            .iter()
+            .filter(|h| h.is_unique())
+            .filter(|h| Arc::weak_count(&h.id) > own.get(&h.id()).cloned().unwrap_or(0))
+            .map(Handle::id)
+            .collect()
+    }
+
+    /// Removes the asset of `handle` from the storage.
+    ///
+    /// If `keep_for_reload` is `true` and the asset has a reload object, the handle is kept
+    /// alive so weak handles to it can still be upgraded, causing a reload.
+    /// Otherwise its id is recycled.
+    fn unload<D>(&mut self, handle: Handle<A>, drop_fn: &mut D, keep_for_reload: bool)
+    where
+        D: FnMut(A),
+    {
+        let id = handle.id();
+        unsafe {
+            drop_fn(self.assets.remove(id));
+        }
+        self.bitset.remove(id);
+        self.memory.remove(id);
+        self.last_used.remove(&id);
+
+        let reload = if keep_for_reload {
+            self.reloads
+                .iter()
+                .position(|(h, _)| h.upgrade().map(|h| h.id()) == Some(id))
+                .map(|p| self.reloads.swap_remove(p).1)
+        } else {
+            None
+        };
+
+        match reload {
+            Some(reload) => self.evicted.push((handle, reload)),
+            None => {
+                self.forget(id);
+                // Can't reuse old handle here, because otherwise weak handles would still be valid.
+                // TODO: maybe just store u32?
+                self.unused_handles.push(Handle {
+                    id: Arc::new(id),
+                    marker: PhantomData,
+                });
+            }
+        }
+    }
+
+    /// Evicts the least recently used assets that are only weakly referenced
+    /// until the memory usage is within `budget`.
+    ///
+    /// An asset counts as used in every frame in which a `Handle` outside of
+    /// the storage points to it.
+    fn evict<D>(&mut self, budget: usize, frame_number: u64, drop_fn: &mut D)
+    where
+        D: FnMut(A),
+    {
+        for handle in self.handles.iter().filter(|h| !h.is_unique()) {
+            self.last_used.insert(handle.id(), frame_number);
+        }
+
+        let mut usage = self.memory.usage().unwrap_or(0);
+        if usage <= budget {
+            return;
+        }
+
+        let mut candidates = self
+            .handles
+            .iter()
+            .enumerate()
+            .filter(|(_, h)| h.is_unique())
+            .map(|(i, h)| (self.last_used.get(&h.id()).cloned().unwrap_or(0), i))
+            .collect::<Vec<_>>();
+        candidates.sort();
+
+        let mut to_evict = Vec::new();
+        for (_, i) in candidates {
+            if usage <= budget {
+                break;
+            }
+            usage -= self.memory.size(self.handles[i].id()).unwrap_or(0);
+            to_evict.push(i);
+        }
+
+        if to_evict.is_empty() {
+            return;
+        }
+
+        // Remove from the back, so `swap_remove` doesn't move any of the remaining indices.
+        to_evict.sort_unstable_by(|a, b| b.cmp(a));
+        debug!(
+            "{:?}: Evicting {} assets to stay within the memory budget of {} bytes",
+            A::NAME,
+            to_evict.len(),
+            budget,
+        );
+        for i in to_evict {
+            let handle = self.handles.swap_remove(i);
+            self.unload(handle, drop_fn, true);
+        }
+    }
+
+    /// Reloads evicted assets whose weak handles have been upgraded again,
+    /// and forgets those which can't be reached anymore.
+    fn reload_evicted(&mut self, pool: &ThreadPool) {
+        let mut i = 0;
+        while i < self.evicted.len() {
+            let (strong, weak) = {
+                let handle = &self.evicted[i].0;
+                (Arc::strong_count(&handle.id), Arc::weak_count(&handle.id))
+            };
+
+            if strong > 1 {
+                let (handle, rel) = self.evicted.swap_remove(i);
+                let name = rel.name();
+                let format = rel.format();
+                debug!(
+                    "{:?}: Evicted asset {:?} (handle id: {:?}) is used again, reloading",
+                    A::NAME,
+                    name,
+                    handle,
+                );
+
+                let processed = self.processed.clone();
+                pool.spawn(move || {
+                    let data = rel.reload().with_context(|_| error::Error::Format(format));
+                    processed.push(Processed::NewAsset {
+                        data,
+                        handle,
+                        name,
+                        tracker: Box::new(()),
+                        timing: None,
+                    });
+                });
+            } else if weak == 0 {
+                let (handle, _) = self.evicted.swap_remove(i);
+                self.forget(handle.id());
+                self.unused_handles.push(Handle {
+                    id: Arc::new(handle.id()),
+                    marker: PhantomData,
+                });
+            } else {
+                i += 1;
+            }
         }
     }
 
-    fn hot_reload(&mut self, pool: &ThreadPool) {
+    /// Reloads the assets for which `needs_reload` returns `true`.
+    fn hot_reload<F>(&mut self, pool: &ThreadPool, needs_reload: F)
+    where
+        F: Fn(&dyn Reload<A>) -> bool,
+    {
         self.reloads.retain(|&(ref handle, _)| !handle.is_dead());
         while let Some(p) = self
             .reloads
             .iter()
-            .position(|&(_, ref rel)| rel.needs_reload())
+            .position(|&(_, ref rel)| needs_reload(&**rel))
         {
             let (handle, rel): (WeakHandle<_>, Box<dyn Reload<_>>) = self.reloads.swap_remove(p);
 
@@ -402,6 +761,13 @@ impl<A: Asset> Default for AssetStorage<A> {
             reloads: Default::default(),
             unused_handles: MsQueue::new(),
             requeue: Mutex::new(Vec::default()),
+            memory: Default::default(),
+            last_used: Default::default(),
+            evicted: Default::default(),
+            names: Default::default(),
+            mutated: Default::default(),
+            immediate_reloads: false,
+            formats: None,
         }
     }
 }
@@ -509,6 +875,7 @@ pub(crate) enum Processed<A: Asset> {
         handle: Handle<A>,
         name: String,
         tracker: Box<dyn Tracker>,
+        timing: Option<PendingTiming>,
     },
     HotReload {
         data: Result<FormatValue<A>, Error>,
@@ -516,6 +883,8 @@ pub(crate) enum Processed<A: Asset> {
         name: String,
         old_reload: Box<dyn Reload<A>>,
     },
+    /// The load was cancelled because all handles were dropped, so the id can be reused.
+    Cancelled { id: u32 },
 }
 
 /// A weak handle, which is useful if you don't directly need the asset
@@ -543,3 +912,270 @@ impl<A> WeakHandle<A> {
         self.upgrade().is_none()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        sync::{
+            atomic::{AtomicUsize, Ordering},
+            Arc,
+        },
+        thread,
+        time::Duration,
+    };
+
+    use amethyst_core::ecs::prelude::VecStorage;
+    use amethyst_error::Error;
+    use rayon::{ThreadPool, ThreadPoolBuilder};
+
+    use crate::{Asset, AssetSize, FormatValue, Reload};
+
+    use super::{AssetStorage, Handle, Processed, ProcessingState};
+
+    struct Blob(usize);
+
+    impl Asset for Blob {
+        const NAME: &'static str = "test::Blob";
+        type Data = Blob;
+        type HandleStorage = VecStorage<Handle<Self>>;
+    }
+
+    impl AssetSize for Blob {
+        fn asset_size(&self) -> usize {
+            self.0
+        }
+    }
+
+    /// Reloads a blob of the given size.
+    #[derive(Clone)]
+    struct Reloader(usize);
+
+    impl Reload<Blob> for Reloader {
+        fn needs_reload(&self) -> bool {
+            false
+        }
+
+        fn name(&self) -> String {
+            "blob".to_owned()
+        }
+
+        fn format(&self) -> &'static str {
+            "Test"
+        }
+
+        fn reload(self: Box<Self>) -> Result<FormatValue<Blob>, Error> {
+            Ok(FormatValue::data(Blob(self.0)))
+        }
+    }
+
+    /// Counts how often it was checked for an immediate reload.
+    #[derive(Clone)]
+    struct Immediate(Arc<AtomicUsize>);
+
+    impl Reload<Blob> for Immediate {
+        fn needs_reload(&self) -> bool {
+            false
+        }
+
+        fn needs_immediate_reload(&self) -> bool {
+            self.0.fetch_add(1, Ordering::Relaxed);
+            false
+        }
+
+        fn reloads_immediately(&self) -> bool {
+            true
+        }
+
+        fn name(&self) -> String {
+            "immediate".to_owned()
+        }
+
+        fn format(&self) -> &'static str {
+            "Test"
+        }
+
+        fn reload(self: Box<Self>) -> Result<FormatValue<Blob>, Error> {
+            Ok(FormatValue::data(Blob(0)))
+        }
+    }
+
+    fn pool() -> ThreadPool {
+        ThreadPoolBuilder::new().num_threads(1).build().unwrap()
+    }
+
+    fn insert(storage: &AssetStorage<Blob>, size: usize, reload: bool) -> Handle<Blob> {
+        let handle = storage.allocate();
+        let reload = if reload {
+            Some(Box::new(Reloader(size)) as Box<dyn Reload<Blob>>)
+        } else {
+            None
+        };
+        storage.processed.push(Processed::NewAsset {
+            data: Ok(FormatValue {
+                data: Blob(size),
+                reload,
+            }),
+            handle: handle.clone(),
+            name: format!("blob_{}", handle.id()),
+            tracker: Box::new(()),
+            timing: None,
+        });
+        handle
+    }
+
+    fn process(storage: &mut AssetStorage<Blob>, frame_number: u64, pool: &ThreadPool) {
+        storage.process(
+            |blob| Ok(ProcessingState::Loaded(blob)),
+            frame_number,
+            pool,
+            None,
+        );
+    }
+
+    #[test]
+    fn weakly_referenced_assets_stay_alive_within_budget() {
+        let pool = pool();
+        let mut storage = AssetStorage::<Blob>::new();
+        storage.set_memory_budget(Some(100));
+
+        let kept = insert(&storage, 10, false);
+        let freed = insert(&storage, 20, false);
+        process(&mut storage, 1, &pool);
+        assert_eq!(Some(30), storage.memory_usage());
+
+        let kept = kept.downgrade();
+        drop(freed);
+        process(&mut storage, 2, &pool);
+
+        let kept = kept.upgrade().expect("weakly referenced asset was freed");
+        assert_eq!(Some(10), storage.get(&kept).map(|blob| blob.0));
+        assert_eq!(Some(10), storage.memory_usage());
+    }
+
+    #[test]
+    fn measures_mutated_assets_again() {
+        let pool = pool();
+        let mut storage = AssetStorage::<Blob>::new();
+        storage.set_memory_budget(Some(100));
+
+        let handle = insert(&storage, 10, false);
+        process(&mut storage, 1, &pool);
+        assert_eq!(Some(10), storage.memory_usage());
+
+        storage.get_mut(&handle).unwrap().0 = 50;
+        process(&mut storage, 2, &pool);
+        assert_eq!(Some(50), storage.asset_size(&handle));
+        assert_eq!(Some(50), storage.memory_usage());
+    }
+
+    #[test]
+    fn evicts_least_recently_used_assets_first() {
+        let pool = pool();
+        let mut storage = AssetStorage::<Blob>::new();
+        storage.set_memory_budget(Some(100));
+
+        let a = insert(&storage, 10, false);
+        let b = insert(&storage, 10, false);
+        let c = insert(&storage, 10, false);
+        process(&mut storage, 1, &pool);
+
+        // `a` was last used in frame 1, `b` in frame 2.
+        let weak_a = a.downgrade();
+        drop(a);
+        process(&mut storage, 2, &pool);
+        let weak_b = b.downgrade();
+        drop(b);
+        process(&mut storage, 3, &pool);
+
+        storage.set_memory_budget(Some(20));
+        process(&mut storage, 4, &pool);
+
+        assert!(weak_a.upgrade().is_none());
+        let b = weak_b.upgrade().expect("`b` was evicted before `a`");
+        assert!(storage.get(&b).is_some());
+        assert!(storage.get(&c).is_some());
+        assert_eq!(Some(20), storage.memory_usage());
+    }
+
+    #[test]
+    fn upgrading_evicted_asset_reloads_it() {
+        let pool = pool();
+        let mut storage = AssetStorage::<Blob>::new();
+        storage.set_memory_budget(Some(10));
+
+        let evicted = insert(&storage, 10, true);
+        process(&mut storage, 1, &pool);
+        let weak = evicted.downgrade();
+        drop(evicted);
+
+        let _used = insert(&storage, 10, false);
+        process(&mut storage, 2, &pool);
+
+        let evicted = weak
+            .upgrade()
+            .expect("evicted asset with reload was forgotten");
+        assert!(storage.get(&evicted).is_none());
+
+        let mut frame_number = 3;
+        while storage.get(&evicted).is_none() {
+            assert!(frame_number < 1000, "evicted asset was not reloaded");
+            thread::sleep(Duration::from_millis(1));
+            process(&mut storage, frame_number, &pool);
+            frame_number += 1;
+        }
+        assert_eq!(Some(10), storage.get(&evicted).map(|blob| blob.0));
+    }
+
+    #[test]
+    fn forgets_evicted_assets_without_weak_handles() {
+        let pool = pool();
+        let mut storage = AssetStorage::<Blob>::new();
+        storage.set_memory_budget(Some(10));
+
+        let evicted = insert(&storage, 10, true);
+        let id = evicted.id();
+        process(&mut storage, 1, &pool);
+        let weak = evicted.downgrade();
+        drop(evicted);
+
+        let _used = insert(&storage, 10, false);
+        process(&mut storage, 2, &pool);
+        assert!(storage.names.contains_key(&id));
+
+        drop(weak);
+        process(&mut storage, 3, &pool);
+        assert!(storage.evicted.is_empty());
+        assert!(!storage.names.contains_key(&id));
+    }
+
+    #[test]
+    fn checks_immediate_reloads_only_while_registered() {
+        let pool = pool();
+        let mut storage = AssetStorage::<Blob>::new();
+        let _plain = insert(&storage, 10, true);
+        process(&mut storage, 1, &pool);
+        assert!(!storage.immediate_reloads);
+
+        let checks = Arc::new(AtomicUsize::new(0));
+        let handle = storage.allocate();
+        storage.processed.push(Processed::NewAsset {
+            data: Ok(FormatValue {
+                data: Blob(10),
+                reload: Some(Box::new(Immediate(checks.clone()))),
+            }),
+            handle: handle.clone(),
+            name: "immediate".to_owned(),
+            tracker: Box::new(()),
+            timing: None,
+        });
+        process(&mut storage, 2, &pool);
+        process(&mut storage, 3, &pool);
+        assert_eq!(2, checks.load(Ordering::Relaxed));
+
+        drop(handle);
+        process(&mut storage, 4, &pool);
+        assert!(!storage.immediate_reloads);
+        process(&mut storage, 5, &pool);
+        assert_eq!(2, checks.load(Ordering::Relaxed));
+    }
+}
@@ -0,0 +1,92 @@
+//! Assets, formats and sources shared by the tests of this crate.
+
+use std::sync::{Arc, Mutex};
+
+use amethyst_core::ecs::prelude::VecStorage;
+use amethyst_error::Error;
+
+use crate::{Asset, Handle, SimpleFormat, Source};
+
+/// An asset holding a string.
+pub struct Text;
+
+impl Asset for Text {
+    const NAME: &'static str = "test::Text";
+    type Data = String;
+    type HandleStorage = VecStorage<Handle<Self>>;
+}
+
+/// Imports the file contents as they are.
+#[derive(Clone, Debug)]
+pub struct Utf8;
+
+impl SimpleFormat<Text> for Utf8 {
+    const NAME: &'static str = "Utf8";
+    type Options = ();
+
+    fn import(&self, bytes: Vec<u8>, _: ()) -> Result<String, Error> {
+        Ok(String::from_utf8(bytes)?)
+    }
+}
+
+/// A `Source` keeping its files in memory.
+///
+/// Every file has a modification time, which starts at 1 and is increased by `set`.
+/// Clones share their files, so a test can change the files of a source given to a `Loader`.
+#[derive(Clone)]
+pub struct Files {
+    files: Arc<Mutex<Vec<(String, String, u64)>>>,
+}
+
+impl Files {
+    /// Creates a source with the given `(path, contents)` pairs.
+    pub fn new(files: Vec<(&str, &str)>) -> Self {
+        Files {
+            files: Arc::new(Mutex::new(
+                files
+                    .into_iter()
+                    .map(|(path, contents)| (path.to_owned(), contents.to_owned(), 1))
+                    .collect(),
+            )),
+        }
+    }
+
+    /// Creates a source with files whose contents are their path.
+    pub fn named(paths: Vec<&str>) -> Self {
+        Files::new(paths.into_iter().map(|path| (path, path)).collect())
+    }
+
+    /// Sets the contents of `path`, adding the file if it doesn't exist yet.
+    pub fn set(&self, path: &str, contents: &str) {
+        let mut files = self.files.lock().unwrap();
+        match files.iter_mut().find(|(p, _, _)| p == path) {
+            Some(file) => {
+                file.1 = contents.to_owned();
+                file.2 += 1;
+            }
+            None => files.push((path.to_owned(), contents.to_owned(), 1)),
+        }
+    }
+}
+
+impl Source for Files {
+    fn modified(&self, path: &str) -> Result<u64, Error> {
+        self.files
+            .lock()
+            .unwrap()
+            .iter()
+            .find(|(p, _, _)| p == path)
+            .map(|(_, _, modified)| *modified)
+            .ok_or_else(|| Error::from_string("Not found"))
+    }
+
+    fn load(&self, path: &str) -> Result<Vec<u8>, Error> {
+        self.files
+            .lock()
+            .unwrap()
+            .iter()
+            .find(|(p, _, _)| p == path)
+            .map(|(_, contents, _)| contents.as_bytes().to_vec())
+            .ok_or_else(|| Error::from_string("Not found"))
+    }
+}
@@ -2,7 +2,7 @@
 
 pub use self::{audio_emitter::AudioEmitter, audio_listener::AudioListener};
 
-use amethyst_assets::PrefabData;
+use amethyst_assets::{ExtractPrefabData, PrefabData};
 use amethyst_core::{
     ecs::prelude::{Entity, Read, WriteStorage},
     math::Point3,
@@ -56,3 +56,22 @@ impl<'a> PrefabData<'a> for AudioPrefab {
         Ok(())
     }
 }
+
+impl<'a> ExtractPrefabData<'a> for AudioPrefab {
+    fn extract_from_entity(
+        entity: Entity,
+        system_data: &Self::SystemData,
+        _: &[Entity],
+    ) -> Result<Option<Self>, Error> {
+        let emitter = system_data.0.contains(entity);
+        let listener = system_data
+            .1
+            .get(entity)
+            .map(|listener| (listener.left_ear, listener.right_ear));
+        if !emitter && listener.is_none() {
+            return Ok(None);
+        }
+
+        Ok(Some(AudioPrefab { emitter, listener }))
+    }
+}
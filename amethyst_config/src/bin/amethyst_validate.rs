@@ -0,0 +1,94 @@
+//! Validates RON files against a schema written with `Schema::to_ron`, reporting all problems
+//! found with their line and column.
+//!
+//! ```text
+//! amethyst_validate <schema> <file>...
+//! amethyst_validate --json-schema <schema>
+//! ```
+//!
+//! With `--json-schema`, the schema is printed as JSON Schema instead, which requires the `json`
+//! feature.
+//!
+//! The process exits with status 1 if any file is invalid or could not be read.
+
+#![warn(missing_docs, rust_2018_idioms, rust_2018_compatibility)]
+
+use std::{env, fs, process};
+
+use amethyst_config::Schema;
+
+const USAGE: &str = "Usage: amethyst_validate <schema> <file>...
+       amethyst_validate --json-schema <schema>";
+
+fn main() {
+    let mut json_schema = false;
+    let mut paths = Vec::new();
+    for arg in env::args().skip(1) {
+        match arg.as_str() {
+            "--json-schema" => json_schema = true,
+            "-h" | "--help" => {
+                println!("{}", USAGE);
+                return;
+            }
+            _ if arg.starts_with("--") => {
+                eprintln!("Unknown option `{}`\n{}", arg, USAGE);
+                process::exit(2);
+            }
+            _ => paths.push(arg),
+        }
+    }
+
+    if paths.is_empty() || (json_schema && paths.len() != 1) {
+        eprintln!("{}", USAGE);
+        process::exit(2);
+    }
+
+    let schema = fs::read_to_string(&paths[0])
+        .map_err(|e| e.to_string())
+        .and_then(|source| ron::de::from_str::<Schema>(&source).map_err(|e| e.to_string()))
+        .unwrap_or_else(|e| {
+            eprintln!("{}: Failed to load schema: {}", paths[0], e);
+            process::exit(2);
+        });
+
+    if json_schema {
+        print_json_schema(&schema);
+        return;
+    }
+
+    let mut failed = false;
+    for path in &paths[1..] {
+        match schema.validate_file(path) {
+            Ok(ref errors) if errors.is_empty() => {}
+            Ok(errors) => {
+                for error in errors {
+                    eprintln!("{}:{}", path, error);
+                }
+                failed = true;
+            }
+            Err(e) => {
+                eprintln!("{}: {}", path, e);
+                failed = true;
+            }
+        }
+    }
+
+    if failed {
+        process::exit(1);
+    }
+}
+
+#[cfg(feature = "json")]
+fn print_json_schema(schema: &Schema) {
+    println!(
+        "{}",
+        serde_json::to_string_pretty(&schema.to_json_schema())
+            .expect("Failed to serialize JSON Schema")
+    );
+}
+
+#[cfg(not(feature = "json"))]
+fn print_json_schema(_: &Schema) {
+    eprintln!("`--json-schema` requires the `json` feature");
+    process::exit(2);
+}
@@ -0,0 +1,250 @@
+use std::{ffi::OsStr, path::Path};
+
+use serde::{de::DeserializeOwned, Serialize};
+
+use crate::{ConfigError, Value};
+
+/// File format of a config.
+///
+/// RON is always supported, the other formats are enabled with the cargo features `toml`, `json`
+/// and `yaml`. Loading or writing a format whose feature is disabled fails with
+/// `ConfigError::Unsupported`.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum ConfigFormat {
+    /// Rusty Object Notation, with the extension `ron`.
+    Ron,
+    /// TOML, with the extension `toml`.
+    Toml,
+    /// JSON, with the extension `json`.
+    Json,
+    /// YAML, with the extension `yaml` or `yml`.
+    Yaml,
+}
+
+impl ConfigFormat {
+    /// Returns the format of a file from its extension, even if it is not supported.
+    pub fn from_path<P: AsRef<Path>>(path: P) -> Option<Self> {
+        match path.as_ref().extension().and_then(OsStr::to_str)? {
+            "ron" => Some(ConfigFormat::Ron),
+            "toml" => Some(ConfigFormat::Toml),
+            "json" => Some(ConfigFormat::Json),
+            "yaml" | "yml" => Some(ConfigFormat::Yaml),
+            _ => None,
+        }
+    }
+
+    /// Returns all supported formats.
+    pub fn all() -> Vec<Self> {
+        [
+            ConfigFormat::Ron,
+            ConfigFormat::Toml,
+            ConfigFormat::Json,
+            ConfigFormat::Yaml,
+        ]
+        .iter()
+        .cloned()
+        .filter(|format| format.is_supported())
+        .collect()
+    }
+
+    /// Returns `true` if the cargo feature of this format is enabled.
+    pub fn is_supported(self) -> bool {
+        match self {
+            ConfigFormat::Ron => true,
+            ConfigFormat::Toml => cfg!(feature = "toml"),
+            ConfigFormat::Json => cfg!(feature = "json"),
+            ConfigFormat::Yaml => cfg!(feature = "yaml"),
+        }
+    }
+
+    /// Returns the file extension of this format.
+    pub fn extension(self) -> &'static str {
+        match self {
+            ConfigFormat::Ron => "ron",
+            ConfigFormat::Toml => "toml",
+            ConfigFormat::Json => "json",
+            ConfigFormat::Yaml => "yaml",
+        }
+    }
+
+    /// Returns the cargo feature enabling this format, `None` for RON.
+    pub fn feature(self) -> Option<&'static str> {
+        match self {
+            ConfigFormat::Ron => None,
+            ConfigFormat::Toml => Some("toml"),
+            ConfigFormat::Json => Some("json"),
+            ConfigFormat::Yaml => Some("yaml"),
+        }
+    }
+
+    /// Deserializes a config in this format.
+    pub(crate) fn deserialize<T: DeserializeOwned>(self, bytes: &[u8]) -> Result<T, ConfigError> {
+        match self {
+            ConfigFormat::Ron => {
+                let mut de = ron::de::Deserializer::from_bytes(bytes)?;
+                let val = T::deserialize(&mut de)?;
+                de.end()?;
+
+                Ok(val)
+            }
+            #[cfg(feature = "toml")]
+            ConfigFormat::Toml => {
+                let text = std::str::from_utf8(bytes).map_err(|e| ConfigError::Toml {
+                    message: e.to_string(),
+                    position: None,
+                })?;
+                toml::from_str(text).map_err(|e| ConfigError::Toml {
+                    position: e.line_col().map(|(line, col)| (line + 1, col + 1)),
+                    message: e.to_string(),
+                })
+            }
+            #[cfg(feature = "json")]
+            ConfigFormat::Json => serde_json::from_slice(bytes).map_err(json_error),
+            #[cfg(feature = "yaml")]
+            ConfigFormat::Yaml => serde_yaml::from_slice(bytes).map_err(yaml_error),
+            #[allow(unreachable_patterns)]
+            _ => Err(ConfigError::Unsupported(self)),
+        }
+    }
+
+    /// Deserializes a config in this format into a `Value`.
+    pub(crate) fn deserialize_value(self, bytes: &[u8]) -> Result<Value, ConfigError> {
+        match self {
+            ConfigFormat::Ron => {
+                let text =
+                    std::str::from_utf8(bytes).map_err(|e| ConfigError::Value(e.to_string()))?;
+                Value::from_ron(text)
+            }
+            #[allow(unreachable_patterns)]
+            _ => self.deserialize(bytes),
+        }
+    }
+
+    /// Serializes a config in this format.
+    pub(crate) fn serialize<T: Serialize>(self, value: &T) -> Result<String, ConfigError> {
+        match self {
+            ConfigFormat::Ron => Ok(ron::ser::to_string_pretty(value, Default::default())?),
+            #[cfg(feature = "toml")]
+            ConfigFormat::Toml => toml::to_string_pretty(value).map_err(|e| ConfigError::Toml {
+                message: e.to_string(),
+                position: None,
+            }),
+            #[cfg(feature = "json")]
+            ConfigFormat::Json => serde_json::to_string_pretty(value).map_err(json_error),
+            #[cfg(feature = "yaml")]
+            ConfigFormat::Yaml => serde_yaml::to_string(value).map_err(yaml_error),
+            #[allow(unreachable_patterns)]
+            _ => Err(ConfigError::Unsupported(self)),
+        }
+    }
+}
+
+#[cfg(feature = "json")]
+fn json_error(e: serde_json::Error) -> ConfigError {
+    // Serialization errors have no position and report line 0.
+    let position = if e.line() > 0 {
+        Some((e.line(), e.column()))
+    } else {
+        None
+    };
+    ConfigError::Json {
+        message: e.to_string(),
+        position,
+    }
+}
+
+#[cfg(feature = "yaml")]
+fn yaml_error(e: serde_yaml::Error) -> ConfigError {
+    ConfigError::Yaml {
+        position: e
+            .location()
+            .map(|location| (location.line(), location.column())),
+        message: e.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{env, fs, process};
+
+    use serde::{Deserialize, Serialize};
+
+    use super::ConfigFormat;
+    use crate::{Config, ConfigError};
+
+    #[derive(Debug, Default, Deserialize, PartialEq, Serialize)]
+    struct Display {
+        title: String,
+        size: (u32, u32),
+        fullscreen: bool,
+        scale: Option<f32>,
+    }
+
+    #[test]
+    fn format_from_extension() {
+        assert_eq!(
+            Some(ConfigFormat::Ron),
+            ConfigFormat::from_path("display.ron")
+        );
+        assert_eq!(None, ConfigFormat::from_path("display.txt"));
+        assert_eq!(None, ConfigFormat::from_path("display"));
+        for format in ConfigFormat::all() {
+            let path = format!("display.{}", format.extension());
+            assert_eq!(Some(format), ConfigFormat::from_path(path));
+        }
+    }
+
+    #[cfg(feature = "json")]
+    #[test]
+    fn json_error_position() {
+        let error = ConfigFormat::Json
+            .deserialize::<Vec<u32>>(b"[1,\n 2,\n x]")
+            .unwrap_err();
+        match error {
+            crate::ConfigError::Json { position, .. } => assert_eq!(Some((3, 2)), position),
+            other => panic!("Unexpected error {:?}", other),
+        }
+    }
+
+    #[test]
+    fn write_then_load_every_format() {
+        let config = Display {
+            title: "Game".to_owned(),
+            size: (800, 600),
+            fullscreen: true,
+            scale: Some(1.5),
+        };
+        let dir = env::temp_dir().join(format!("amethyst_config_formats_{}", process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        for format in ConfigFormat::all() {
+            let path = dir.join(format!("display.{}", format.extension()));
+            config.write(&path).unwrap();
+            assert_eq!(
+                config,
+                Display::load_no_fallback(&path).unwrap(),
+                "{:?}",
+                format
+            );
+        }
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn disabled_formats_fail() {
+        let disabled = [ConfigFormat::Toml, ConfigFormat::Json, ConfigFormat::Yaml]
+            .iter()
+            .cloned()
+            .filter(|format| !format.is_supported())
+            .collect::<Vec<_>>();
+        for format in disabled {
+            match Display::load_bytes_format(b"", format) {
+                Err(ConfigError::Unsupported(f)) => assert_eq!(format, f),
+                other => panic!("Unexpected result {:?}", other),
+            }
+            assert_eq!(
+                Some(format),
+                ConfigFormat::from_path(format!("display.{}", format.extension()))
+            );
+        }
+    }
+}
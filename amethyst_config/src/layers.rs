@@ -0,0 +1,360 @@
+//! Configs merged from several layers, like defaults, user files, environment variables and
+//! command line arguments.
+
+use std::{collections::BTreeMap, env, fmt, fs, path::Path};
+
+use log::warn;
+use serde::{de::DeserializeOwned, Serialize};
+
+use crate::{ConfigError, ConfigFormat, Value};
+
+/// Builder merging a config from several layers.
+///
+/// Every layer only needs to contain the fields it changes. The layers are merged field by field
+/// on top of the `Default` of the config, in the order they were added, so later layers override
+/// earlier ones. `build` returns the config together with the `ConfigSources`, telling which
+/// layer set each value.
+///
+/// ### Example
+///
+/// ```rust,ignore
+/// let (config, sources) = ConfigLayers::new()
+///     .file("resources/display_config.ron")?
+///     .user_file("my_game", "display_config.ron")?
+///     .env("MY_GAME")?
+///     .args(std::env::args())?
+///     .build::<DisplayConfig>()?;
+///
+/// info!("Loaded display config:\n{}", sources);
+/// ```
+#[derive(Clone, Debug, Default)]
+pub struct ConfigLayers {
+    layers: Vec<(String, Value)>,
+}
+
+impl ConfigLayers {
+    /// Creates a builder without any layers.
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// Adds a layer read from a config file, which must exist.
+    ///
+    /// The format is chosen by the extension of the file, see `ConfigFormat`.
+    pub fn file<P: AsRef<Path>>(self, path: P) -> Result<Self, ConfigError> {
+        let path = path.as_ref();
+        let format = ConfigFormat::from_path(path)
+            .ok_or_else(|| ConfigError::Extension(path.to_path_buf()))?;
+
+        let name = path.display().to_string();
+        let content = fs::read(path)?;
+        let value = format
+            .deserialize_value(&content)
+            .map_err(|e| ConfigError::Layer(name.clone(), e.to_string()))?;
+        Ok(self.layer(name, value))
+    }
+
+    /// Adds a layer read from a config file, if it exists.
+    pub fn optional_file<P: AsRef<Path>>(self, path: P) -> Result<Self, ConfigError> {
+        if path.as_ref().exists() {
+            self.file(path)
+        } else {
+            Ok(self)
+        }
+    }
+
+    /// Adds a layer read from the file `file` in the directory `app` of the platform's user
+    /// config directory, if it exists.
+    ///
+    /// On Linux, this is `$XDG_CONFIG_HOME/app/file` or `$HOME/.config/app/file`, on Windows
+    /// `{FOLDERID_RoamingAppData}\app\file` and on macOS
+    /// `$HOME/Library/Preferences/app/file`.
+    pub fn user_file(self, app: &str, file: &str) -> Result<Self, ConfigError> {
+        match dirs::config_dir() {
+            Some(dir) => self.optional_file(dir.join(app).join(file)),
+            None => Ok(self),
+        }
+    }
+
+    /// Adds a layer from the environment variables starting with `prefix` followed by `_`.
+    ///
+    /// The rest of the variable name is the path of the field, with nested fields separated by
+    /// `__`, e.g. `MY_GAME_DISPLAY__MAX_FPS=60` sets `display.max_fps`. See `args` for how the
+    /// values are parsed.
+    pub fn env(self, prefix: &str) -> Result<Self, ConfigError> {
+        let vars = env::vars_os()
+            .filter_map(|(key, value)| Some((key.into_string().ok()?, value.into_string().ok()?)));
+        self.env_vars(prefix, vars)
+    }
+
+    /// Adds a layer from the given environment variables, see `env`.
+    pub fn env_vars<I>(self, prefix: &str, vars: I) -> Result<Self, ConfigError>
+    where
+        I: IntoIterator<Item = (String, String)>,
+    {
+        let prefix = format!("{}_", prefix);
+        let mut layer = Value::Struct(Vec::new());
+        for (key, value) in vars {
+            if !key.starts_with(&prefix) || key.len() == prefix.len() {
+                continue;
+            }
+            let path = key[prefix.len()..]
+                .split("__")
+                .map(str::to_lowercase)
+                .collect::<Vec<_>>()
+                .join(".");
+            set_override(&mut layer, "env", &path, &value)?;
+        }
+        Ok(self.layer("env", layer))
+    }
+
+    /// Adds a layer from the command line arguments `--set key.path=value` or
+    /// `--set=key.path=value`. All other arguments are ignored.
+    ///
+    /// `true` and `false` are parsed as booleans, numbers as integers or floats, and values
+    /// starting with `(`, `[`, `{` or `"` or ending with `)` as RON. Anything else is a string,
+    /// which can also be used as a unit enum variant.
+    pub fn args<I, S>(self, args: I) -> Result<Self, ConfigError>
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<str>,
+    {
+        let mut layer = Value::Struct(Vec::new());
+        let mut args = args.into_iter();
+        while let Some(arg) = args.next() {
+            let arg = arg.as_ref();
+            let assignment = if arg == "--set" {
+                match args.next() {
+                    Some(next) => next.as_ref().to_owned(),
+                    None => {
+                        return Err(layer_error("args", "Expected `key.path=value` after --set"))
+                    }
+                }
+            } else if arg.starts_with("--set=") {
+                arg["--set=".len()..].to_owned()
+            } else {
+                continue;
+            };
+
+            let mut parts = assignment.splitn(2, '=');
+            match (parts.next(), parts.next()) {
+                (Some(path), Some(value)) if !path.is_empty() => {
+                    set_override(&mut layer, "args", path, value)?
+                }
+                _ => {
+                    return Err(layer_error(
+                        "args",
+                        &format!("Expected `key.path=value`, got `{}`", assignment),
+                    ));
+                }
+            }
+        }
+        Ok(self.layer("args", layer))
+    }
+
+    /// Adds a layer with the given name.
+    pub fn layer<S: Into<String>>(mut self, name: S, value: Value) -> Self {
+        self.layers.push((name.into(), value));
+        self
+    }
+
+    /// Merges all layers on top of the `Default` of `T`.
+    pub fn build<T>(&self) -> Result<(T, ConfigSources), ConfigError>
+    where
+        T: Serialize + DeserializeOwned + Default,
+    {
+        let default = Value::from_config(&T::default())?;
+        let mut sources = ConfigSources::default();
+        sources.record(&default, "default");
+
+        let mut value = default.clone();
+        for (name, layer) in &self.layers {
+            layer.visit_leaves("", &mut |path| {
+                let parent = path
+                    .rfind('.')
+                    .map_or(Some(&default), |i| default.get_path(&path[..i]));
+                if let Some(fields) = parent.and_then(Value::fields) {
+                    let field = path.rsplit('.').next().unwrap_or(&path);
+                    if fields.iter().all(|(name, _)| name != field) {
+                        warn!("Config layer `{}` sets unknown field `{}`", name, path);
+                    }
+                }
+            });
+            sources.record(layer, name);
+            value.merge(layer.clone());
+        }
+
+        Ok((value.into_config()?, sources))
+    }
+}
+
+fn layer_error(layer: &str, message: &str) -> ConfigError {
+    ConfigError::Layer(layer.to_owned(), message.to_owned())
+}
+
+fn set_override(layer: &mut Value, name: &str, path: &str, value: &str) -> Result<(), ConfigError> {
+    let value = parse_override(value).map_err(|e| layer_error(name, &e.to_string()))?;
+    if layer.set_path(path, value) {
+        Ok(())
+    } else {
+        Err(layer_error(
+            name,
+            &format!("`{}` is set both as a value and a struct", path),
+        ))
+    }
+}
+
+fn parse_override(text: &str) -> Result<Value, ConfigError> {
+    let text = text.trim();
+    match text {
+        "true" => return Ok(Value::Bool(true)),
+        "false" => return Ok(Value::Bool(false)),
+        _ => {}
+    }
+    if text.starts_with(|c: char| "([{\"'".contains(c)) || text.ends_with(')') {
+        return Value::from_ron(text);
+    }
+    if let Ok(u) = text.parse() {
+        Ok(Value::U64(u))
+    } else if let Ok(i) = text.parse() {
+        Ok(Value::I64(i))
+    } else if text.starts_with(|c: char| c.is_ascii_digit() || "+-.".contains(c)) {
+        text.parse()
+            .map(Value::F64)
+            .map_err(|_| ConfigError::Value(format!("Invalid number `{}`", text)))
+    } else {
+        Ok(Value::String(text.to_owned()))
+    }
+}
+
+/// The layer which set each value of a config merged by `ConfigLayers`.
+///
+/// Values are identified by the path of their field, with nested fields separated by `.`.
+/// Values which were never overridden come from the `default` layer.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ConfigSources {
+    sources: BTreeMap<String, String>,
+}
+
+impl ConfigSources {
+    /// Returns the name of the layer which set the value at `path`.
+    pub fn source(&self, path: &str) -> Option<&str> {
+        self.sources.get(path).map(String::as_str)
+    }
+
+    /// Iterates over the paths of all values and the layers which set them, sorted by path.
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &str)> {
+        self.sources
+            .iter()
+            .map(|(path, layer)| (path.as_str(), layer.as_str()))
+    }
+
+    fn record(&mut self, value: &Value, layer: &str) {
+        value.visit_leaves("", &mut |path| {
+            // A value replaces everything below it, and a nested value replaces its parents.
+            let prefix = format!("{}.", path);
+            let replaced = self
+                .sources
+                .keys()
+                .filter(|p| p.starts_with(&prefix) || path.starts_with(&format!("{}.", p)))
+                .cloned()
+                .collect::<Vec<_>>();
+            for p in replaced {
+                self.sources.remove(&p);
+            }
+            self.sources.insert(path, layer.to_owned());
+        });
+    }
+}
+
+impl fmt::Display for ConfigSources {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (path, layer) in self.iter() {
+            writeln!(f, "{} = {}", path, layer)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde::{Deserialize, Serialize};
+
+    use super::ConfigLayers;
+    use crate::Value;
+
+    #[derive(Debug, Default, Deserialize, PartialEq, Serialize)]
+    struct Display {
+        fullscreen: bool,
+        size: (u32, u32),
+        mode: Mode,
+    }
+
+    #[derive(Debug, Deserialize, PartialEq, Serialize)]
+    enum Mode {
+        Windowed,
+        Borderless { monitor: u8 },
+    }
+
+    impl Default for Mode {
+        fn default() -> Self {
+            Mode::Windowed
+        }
+    }
+
+    #[derive(Debug, Default, Deserialize, PartialEq, Serialize)]
+    struct GameConfig {
+        name: String,
+        volume: f32,
+        display: Display,
+    }
+
+    #[test]
+    fn merges_layers_in_order() {
+        let file = Value::from_ron("(name: \"game\", display: (size: (800, 600)))").unwrap();
+        let env = vec![
+            ("GAME_DISPLAY__FULLSCREEN".to_owned(), "true".to_owned()),
+            ("GAME_VOLUME".to_owned(), "0.5".to_owned()),
+            ("OTHER_VOLUME".to_owned(), "1".to_owned()),
+        ];
+        let args = [
+            "game",
+            "--set",
+            "volume=1",
+            "--set=display.mode=Borderless(monitor: 1)",
+        ];
+
+        let (config, sources) = ConfigLayers::new()
+            .layer("game.ron", file)
+            .env_vars("GAME", env)
+            .unwrap()
+            .args(&args)
+            .unwrap()
+            .build::<GameConfig>()
+            .unwrap();
+
+        assert_eq!(
+            config,
+            GameConfig {
+                name: "game".to_owned(),
+                volume: 1.0,
+                display: Display {
+                    fullscreen: true,
+                    size: (800, 600),
+                    mode: Mode::Borderless { monitor: 1 },
+                },
+            }
+        );
+        assert_eq!(Some("game.ron"), sources.source("name"));
+        assert_eq!(Some("args"), sources.source("volume"));
+        assert_eq!(Some("env"), sources.source("display.fullscreen"));
+        assert_eq!(Some("game.ron"), sources.source("display.size"));
+        assert_eq!(Some("args"), sources.source("display.mode.monitor"));
+    }
+
+    #[test]
+    fn rejects_malformed_args() {
+        assert!(ConfigLayers::new().args(&["--set"]).is_err());
+        assert!(ConfigLayers::new().args(&["--set=volume"]).is_err());
+    }
+}
@@ -1,4 +1,5 @@
-//! Loads RON files into a structure for easy / statically typed usage.
+//! Loads RON files, or TOML, JSON and YAML files with the matching cargo features, into a
+//! structure for easy / statically typed usage.
 
 #![crate_name = "amethyst_config"]
 #![warn(missing_docs, rust_2018_idioms, rust_2018_compatibility)]
@@ -10,9 +11,24 @@ use std::{
 };
 
 use log::error;
-use ron::{self, de::Error as DeError, ser::Error as SerError};
+use ron::{de::Error as DeError, ser::Error as SerError};
 use serde::{Deserialize, Serialize};
 
+pub use crate::{
+    format::ConfigFormat,
+    layers::{ConfigLayers, ConfigSources},
+    migration::Migrations,
+    schema::{Schema, ValidationError, Variant},
+    value::Value,
+};
+
+pub mod schema;
+
+mod format;
+mod layers;
+mod migration;
+mod value;
+
 /// Error related to anything that manages/creates configurations as well as
 /// "workspace"-related things.
 #[derive(Debug)]
@@ -25,6 +41,42 @@ pub enum ConfigError {
     Serializer(SerError),
     /// Related to the path of the file.
     Extension(PathBuf),
+    /// Occurs if a schema can't be generated for a type.
+    Schema(String),
+    /// Errors parsing or writing a TOML file, with the line and column if known.
+    Toml {
+        /// Description of the error.
+        message: String,
+        /// Line and column of the error, starting at 1.
+        position: Option<(usize, usize)>,
+    },
+    /// Errors parsing or writing a JSON file, with the line and column if known.
+    Json {
+        /// Description of the error.
+        message: String,
+        /// Line and column of the error, starting at 1.
+        position: Option<(usize, usize)>,
+    },
+    /// Errors parsing or writing a YAML file, with the line and column if known.
+    Yaml {
+        /// Description of the error.
+        message: String,
+        /// Line and column of the error, starting at 1.
+        position: Option<(usize, usize)>,
+    },
+    /// Occurs if a config is loaded or written in a format whose cargo feature is disabled.
+    Unsupported(ConfigFormat),
+    /// Occurs if a `Value` can't be converted from or into a config.
+    Value(String),
+    /// Occurs if a layer of `ConfigLayers` is invalid, with the name of the layer.
+    Layer(String, String),
+    /// Occurs if a config file can't be upgraded by `Migrations`.
+    Migration {
+        /// The version the failed migration started from.
+        from: u32,
+        /// Description of the error.
+        message: String,
+    },
 }
 
 impl fmt::Display for ConfigError {
@@ -33,16 +85,42 @@ impl fmt::Display for ConfigError {
             ConfigError::File(ref err) => write!(f, "{}", err),
             ConfigError::Parser(ref msg) => write!(f, "{}", msg),
             ConfigError::Serializer(ref msg) => write!(f, "{}", msg),
+            ConfigError::Schema(ref msg) => write!(f, "Failed generating schema: {}", msg),
+            ConfigError::Toml { ref message, .. } => write!(f, "TOML error: {}", message),
+            ConfigError::Json { ref message, .. } => write!(f, "JSON error: {}", message),
+            ConfigError::Yaml { ref message, .. } => write!(f, "YAML error: {}", message),
+            ConfigError::Unsupported(format) => write!(
+                f,
+                "{:?} configs require the `{}` feature of amethyst_config",
+                format,
+                format.feature().unwrap_or_default(),
+            ),
+            ConfigError::Value(ref msg) => write!(f, "Invalid config value: {}", msg),
+            ConfigError::Layer(ref layer, ref msg) => {
+                write!(f, "Invalid config layer `{}`: {}", layer, msg)
+            }
+            ConfigError::Migration { from, ref message } => write!(
+                f,
+                "Failed migrating config from version {}: {}",
+                from, message
+            ),
             ConfigError::Extension(ref path) => {
                 let found = match path.extension() {
                     Some(extension) => format!("{:?}", extension),
                     None => "a directory.".to_string(),
                 };
 
+                let expected = ConfigFormat::all()
+                    .into_iter()
+                    .map(|format| format!("{:?}", format.extension()))
+                    .collect::<Vec<_>>()
+                    .join(", ");
+
                 write!(
                     f,
-                    "{}: Invalid path extension, expected \"ron\", got {}.",
+                    "{}: Invalid path extension, expected one of {}, got {}.",
                     path.display().to_string(),
+                    expected,
                     found,
                 )
             }
@@ -75,6 +153,14 @@ impl Error for ConfigError {
             ConfigError::Parser(_) => "Project parser error",
             ConfigError::Serializer(_) => "Project serializer error",
             ConfigError::Extension(_) => "Invalid extension or directory for a file",
+            ConfigError::Schema(_) => "Schema generation error",
+            ConfigError::Toml { .. } => "TOML error",
+            ConfigError::Json { .. } => "JSON error",
+            ConfigError::Yaml { .. } => "YAML error",
+            ConfigError::Unsupported(_) => "Unsupported config format",
+            ConfigError::Value(_) => "Config value error",
+            ConfigError::Layer(..) => "Config layer error",
+            ConfigError::Migration { .. } => "Config migration error",
         }
     }
 
@@ -96,13 +182,30 @@ where
     fn load<P: AsRef<Path>>(path: P) -> Self;
 
     /// Loads a configuration structure from a file.
+    ///
+    /// The format is chosen by the extension of the file, see `ConfigFormat`.
     fn load_no_fallback<P: AsRef<Path>>(path: P) -> Result<Self, ConfigError>;
 
+    /// Loads a configuration structure from a file in the given format.
+    fn load_format<P: AsRef<Path>>(path: P, format: ConfigFormat) -> Result<Self, ConfigError>;
+
     /// Loads configuration structure from raw bytes.
     fn load_bytes(bytes: &[u8]) -> Result<Self, ConfigError>;
 
+    /// Loads configuration structure from raw bytes in the given format.
+    fn load_bytes_format(bytes: &[u8], format: ConfigFormat) -> Result<Self, ConfigError>;
+
     /// Writes a configuration structure to a file.
+    ///
+    /// The format is chosen by the extension of the file, and is RON for unknown extensions.
     fn write<P: AsRef<Path>>(&self, path: P) -> Result<(), ConfigError>;
+
+    /// Writes a configuration structure to a file in the given format.
+    fn write_format<P: AsRef<Path>>(
+        &self,
+        path: P,
+        format: ConfigFormat,
+    ) -> Result<(), ConfigError>;
 }
 
 impl<T> Config for T
@@ -122,9 +225,15 @@ where
     }
 
     fn load_no_fallback<P: AsRef<Path>>(path: P) -> Result<Self, ConfigError> {
-        use std::{fs::File, io::Read};
-
         let path = path.as_ref();
+        match ConfigFormat::from_path(path) {
+            Some(format) => Self::load_format(path, format),
+            None => Err(ConfigError::Extension(path.to_path_buf())),
+        }
+    }
+
+    fn load_format<P: AsRef<Path>>(path: P, format: ConfigFormat) -> Result<Self, ConfigError> {
+        use std::{fs::File, io::Read};
 
         let content = {
             let mut file = File::open(path)?;
@@ -134,26 +243,30 @@ where
             buffer
         };
 
-        if path.extension().and_then(std::ffi::OsStr::to_str) == Some("ron") {
-            Self::load_bytes(&content)
-        } else {
-            Err(ConfigError::Extension(path.to_path_buf()))
-        }
+        Self::load_bytes_format(&content, format)
     }
 
     fn load_bytes(bytes: &[u8]) -> Result<Self, ConfigError> {
-        let mut de = ron::de::Deserializer::from_bytes(bytes)?;
-        let val = T::deserialize(&mut de)?;
-        de.end()?;
+        Self::load_bytes_format(bytes, ConfigFormat::Ron)
+    }
 
-        Ok(val)
+    fn load_bytes_format(bytes: &[u8], format: ConfigFormat) -> Result<Self, ConfigError> {
+        format.deserialize(bytes)
     }
 
     fn write<P: AsRef<Path>>(&self, path: P) -> Result<(), ConfigError> {
-        use ron::ser::to_string_pretty;
+        let format = ConfigFormat::from_path(path.as_ref()).unwrap_or(ConfigFormat::Ron);
+        self.write_format(path, format)
+    }
+
+    fn write_format<P: AsRef<Path>>(
+        &self,
+        path: P,
+        format: ConfigFormat,
+    ) -> Result<(), ConfigError> {
         use std::{fs::File, io::Write};
 
-        let s = to_string_pretty(self, Default::default())?;
+        let s = format.serialize(self)?;
         File::create(path)?.write_all(s.as_bytes())?;
 
         Ok(())
@@ -0,0 +1,234 @@
+//! Upgrading config files written for older versions of a config struct.
+
+use std::{collections::BTreeMap, fmt, fs, path::Path};
+
+use log::info;
+use serde::{de::DeserializeOwned, Serialize};
+
+use crate::{ConfigError, ConfigFormat, Value};
+
+type Migration = Box<dyn Fn(&mut Value) -> Result<(), ConfigError> + Send + Sync>;
+
+/// Chain of migrations upgrading config files to the current version of a config struct.
+///
+/// The version of a file is stored in a field of the config, `version` by default. Files without
+/// that field have version 0. When loading a file, the migrations are applied one version at a
+/// time to the untyped `Value` of the file, until it has the current version, and only then it is
+/// converted into the config struct.
+///
+/// The config struct should contain the version field with the current version as default, so
+/// it is written together with the config.
+///
+/// ### Example
+///
+/// ```rust,ignore
+/// // Version 1 moved `fullscreen` into `display`, version 2 renamed `fps` to `max_fps`.
+/// let migrations = Migrations::new(2)
+///     .step(0, |value| {
+///         value.rename_path("fullscreen", "display.fullscreen");
+///         Ok(())
+///     })
+///     .step(1, |value| {
+///         value.rename_path("display.fps", "display.max_fps");
+///         Ok(())
+///     });
+///
+/// let config: GameConfig = migrations.load_and_upgrade("resources/game.ron")?;
+/// ```
+pub struct Migrations {
+    version: u32,
+    field: String,
+    steps: BTreeMap<u32, Migration>,
+}
+
+impl Migrations {
+    /// Creates a chain without migrations, for configs with the current version `version`.
+    pub fn new(version: u32) -> Self {
+        Migrations {
+            version,
+            field: "version".to_owned(),
+            steps: BTreeMap::new(),
+        }
+    }
+
+    /// Sets the path of the field storing the version, `version` by default.
+    pub fn with_version_field<S: Into<String>>(mut self, field: S) -> Self {
+        self.field = field.into();
+        self
+    }
+
+    /// Adds the migration upgrading a file from version `from` to `from + 1`.
+    ///
+    /// Versions without a migration are upgraded without changes.
+    ///
+    /// ### Panics
+    ///
+    /// Panics if `from` isn't older than the current version.
+    pub fn step<F>(mut self, from: u32, migration: F) -> Self
+    where
+        F: Fn(&mut Value) -> Result<(), ConfigError> + Send + Sync + 'static,
+    {
+        assert!(
+            from < self.version,
+            "Migration from version {} added, but the current version is {}",
+            from,
+            self.version,
+        );
+        self.steps.insert(from, Box::new(migration));
+        self
+    }
+
+    /// Returns the current version.
+    pub fn version(&self) -> u32 {
+        self.version
+    }
+
+    /// Returns the version of a config value.
+    pub fn version_of(&self, value: &Value) -> Result<u32, ConfigError> {
+        match value.get_path(&self.field) {
+            None => Ok(0),
+            Some(&Value::U64(version)) if version <= u64::from(std::u32::MAX) => Ok(version as u32),
+            Some(other) => Err(ConfigError::Value(format!(
+                "Invalid version `{}` in field `{}`",
+                other, self.field
+            ))),
+        }
+    }
+
+    /// Upgrades a config value to the current version, returning the version it had before.
+    pub fn migrate(&self, value: &mut Value) -> Result<u32, ConfigError> {
+        let old = self.version_of(value)?;
+        if old > self.version {
+            return Err(ConfigError::Migration {
+                from: old,
+                message: format!("The current version is {}", self.version),
+            });
+        }
+
+        for version in old..self.version {
+            if let Some(migration) = self.steps.get(&version) {
+                migration(value).map_err(|e| ConfigError::Migration {
+                    from: version,
+                    message: e.to_string(),
+                })?;
+            }
+        }
+        value.set_path(&self.field, Value::U64(u64::from(self.version)));
+        Ok(old)
+    }
+
+    /// Loads a config file, upgrading it to the current version.
+    ///
+    /// The format is chosen by the extension of the file, see `ConfigFormat`.
+    pub fn load<T, P>(&self, path: P) -> Result<T, ConfigError>
+    where
+        T: DeserializeOwned,
+        P: AsRef<Path>,
+    {
+        self.load_value(path.as_ref())
+            .and_then(|(value, _, _)| value.into_config())
+    }
+
+    /// Loads a config file like `load`, and writes the upgraded config back to the file if it
+    /// had an older version.
+    ///
+    /// The file is written from the config struct, so comments and formatting are lost.
+    pub fn load_and_upgrade<T, P>(&self, path: P) -> Result<T, ConfigError>
+    where
+        T: Serialize + DeserializeOwned,
+        P: AsRef<Path>,
+    {
+        let path = path.as_ref();
+        let (value, format, old) = self.load_value(path)?;
+        let config = value.into_config()?;
+        if old < self.version {
+            fs::write(path, format.serialize(&config)?)?;
+            info!(
+                "Upgraded config {:?} from version {} to {}",
+                path, old, self.version
+            );
+        }
+        Ok(config)
+    }
+
+    fn load_value(&self, path: &Path) -> Result<(Value, ConfigFormat, u32), ConfigError> {
+        let format = ConfigFormat::from_path(path)
+            .ok_or_else(|| ConfigError::Extension(path.to_path_buf()))?;
+        let mut value = format.deserialize_value(&fs::read(path)?)?;
+        let old = self.migrate(&mut value)?;
+        Ok((value, format, old))
+    }
+}
+
+impl fmt::Debug for Migrations {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Migrations")
+            .field("version", &self.version)
+            .field("field", &self.field)
+            .field("steps", &self.steps.keys().collect::<Vec<_>>())
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde::{Deserialize, Serialize};
+
+    use super::Migrations;
+    use crate::{ConfigError, Value};
+
+    #[derive(Debug, Default, Deserialize, PartialEq, Serialize)]
+    struct Display {
+        fullscreen: bool,
+        max_fps: u32,
+    }
+
+    #[derive(Debug, Default, Deserialize, PartialEq, Serialize)]
+    struct GameConfig {
+        version: u32,
+        display: Display,
+    }
+
+    fn migrations() -> Migrations {
+        Migrations::new(2)
+            .step(0, |value| {
+                value.rename_path("fullscreen", "display.fullscreen");
+                Ok(())
+            })
+            .step(1, |value| {
+                if value.rename_path("display.fps", "display.max_fps") {
+                    Ok(())
+                } else {
+                    Err(ConfigError::Value("Missing `display.fps`".to_owned()))
+                }
+            })
+    }
+
+    #[test]
+    fn upgrades_step_by_step() {
+        let mut value = Value::from_ron("(fullscreen: true, display: (fps: 60))").unwrap();
+        assert_eq!(0, migrations().migrate(&mut value).unwrap());
+        assert_eq!(
+            GameConfig {
+                version: 2,
+                display: Display {
+                    fullscreen: true,
+                    max_fps: 60,
+                },
+            },
+            value.into_config().unwrap()
+        );
+    }
+
+    #[test]
+    fn reports_failed_and_newer_versions() {
+        let mut value = Value::from_ron("(version: 1, display: ())").unwrap();
+        match migrations().migrate(&mut value) {
+            Err(ConfigError::Migration { from: 1, .. }) => {}
+            other => panic!("Unexpected result {:?}", other),
+        }
+
+        let mut value = Value::from_ron("(version: 3)").unwrap();
+        assert!(migrations().migrate(&mut value).is_err());
+    }
+}
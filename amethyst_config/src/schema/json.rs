@@ -0,0 +1,107 @@
+//! Conversion of schemas to JSON Schema, describing the representation used by `serde_json`.
+
+use serde_json::{json, Map, Value};
+
+use super::{Schema, Variant};
+
+impl Schema {
+    /// Converts the schema to a JSON Schema (draft 7) of the type's representation in JSON.
+    ///
+    /// This can be used by editors to check and complete `.json` config files. Like `validate`,
+    /// the JSON Schema doesn't require any fields of structs, since they may have default
+    /// values, but rejects unknown fields.
+    pub fn to_json_schema(&self) -> Value {
+        let mut schema = json_schema(self);
+        if let Value::Object(ref mut object) = schema {
+            object.insert(
+                "$schema".to_owned(),
+                json!("http://json-schema.org/draft-07/schema#"),
+            );
+        }
+        schema
+    }
+}
+
+fn json_schema(schema: &Schema) -> Value {
+    match *schema {
+        Schema::Any => json!({}),
+        Schema::Bool => json!({"type": "boolean"}),
+        Schema::Integer => json!({"type": "integer"}),
+        Schema::Float => json!({"type": "number"}),
+        Schema::Char => json!({"type": "string", "minLength": 1, "maxLength": 1}),
+        Schema::String => json!({"type": "string"}),
+        Schema::Bytes => json!({
+            "anyOf": [
+                {"type": "string"},
+                {"type": "array", "items": {"type": "integer", "minimum": 0, "maximum": 255}},
+            ]
+        }),
+        Schema::Unit | Schema::UnitStruct(_) => json!({"type": "null"}),
+        Schema::Option(ref inner) => json!({"anyOf": [{"type": "null"}, json_schema(inner)]}),
+        Schema::Seq(ref inner) => json!({"type": "array", "items": json_schema(inner)}),
+        Schema::Tuple(ref items) => tuple(items),
+        // JSON only has string keys
+        Schema::Map(_, ref inner) => {
+            json!({"type": "object", "additionalProperties": json_schema(inner)})
+        }
+        // `serde_json` writes newtype structs as their contents
+        Schema::NewtypeStruct(_, ref inner) => json_schema(inner),
+        Schema::TupleStruct(ref name, ref items) => titled(name, tuple(items)),
+        Schema::Struct(ref name, ref fields) => titled(name, object(fields)),
+        Schema::Enum(ref name, ref variants) => {
+            let mut alternatives = Vec::new();
+            for (variant, contents) in variants {
+                let contents = match *contents {
+                    Some(Variant::Unit) => None,
+                    Some(Variant::Newtype(ref inner)) => Some(json_schema(inner)),
+                    Some(Variant::Tuple(ref items)) => Some(tuple(items)),
+                    Some(Variant::Struct(ref fields)) => Some(object(fields)),
+                    // Unknown contents, allow both representations
+                    None => {
+                        alternatives.push(json!({ "const": variant }));
+                        Some(json!({}))
+                    }
+                };
+                alternatives.push(match contents {
+                    None => json!({ "const": variant }),
+                    // `serde_json` writes variants with contents as `{"Variant": contents}`
+                    Some(contents) => json!({
+                        "type": "object",
+                        "properties": { variant.as_str(): contents },
+                        "required": [variant],
+                        "additionalProperties": false,
+                    }),
+                });
+            }
+            titled(name, json!({ "oneOf": alternatives }))
+        }
+    }
+}
+
+fn tuple(items: &[Schema]) -> Value {
+    json!({
+        "type": "array",
+        "items": items.iter().map(json_schema).collect::<Vec<_>>(),
+        "minItems": items.len(),
+        "maxItems": items.len(),
+    })
+}
+
+fn object(fields: &[(String, Schema)]) -> Value {
+    let properties = fields
+        .iter()
+        .map(|(name, schema)| (name.clone(), json_schema(schema)))
+        .collect::<Map<_, _>>();
+    json!({
+        "type": "object",
+        "properties": properties,
+        "additionalProperties": false,
+    })
+}
+
+fn titled(name: &str, mut schema: Value) -> Value {
+    if let Value::Object(ref mut object) = schema {
+        object.insert("title".to_owned(), json!(name));
+    }
+    schema
+}
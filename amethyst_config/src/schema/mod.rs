@@ -0,0 +1,608 @@
+//! Schemas describing the RON representation of a type, used to validate RON files.
+//!
+//! A `Schema` is generated from any type implementing `Deserialize`, like config structures or
+//! `Prefab`s, by tracing how the type deserializes itself, and used to validate files before they
+//! are loaded. Unlike loading a file, validation reports all problems found in the file, each
+//! with the line and column of the invalid value and the path of the field containing it.
+//!
+//! Validation only checks what the schema knows about: fields missing from a struct are not
+//! reported, since they may have default values, and values of types deserialized with
+//! `deserialize_any` (e.g. untagged enums) are not checked.
+//!
+//! Schemas are written out with `Schema::to_ron` for the `amethyst_validate` tool, which checks
+//! files without having to build the game, and with `Schema::to_json_schema` (`json` feature)
+//! for editors supporting JSON Schema.
+//!
+//! ### Example
+//!
+//! Writing the schema of the prefabs of a game, e.g. from a build script or a test:
+//!
+//! ```rust,ignore
+//! let schema = Schema::of::<Prefab<MyPrefabData>>()?;
+//! std::fs::write("prefab.schema.ron", schema.to_ron()?)?;
+//! ```
+//!
+//! The prefab files can then be checked with:
+//!
+//! ```text
+//! amethyst_validate prefab.schema.ron assets/prefab/*.ron
+//! ```
+
+use std::{fmt, fs, path::Path};
+
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+
+use crate::ConfigError;
+
+use self::{
+    parse::{Extensions, Value, ValueKind},
+    trace::{TraceState, Tracer},
+};
+
+#[cfg(feature = "json")]
+mod json;
+pub(crate) mod parse;
+mod trace;
+
+/// Maximum number of passes over a type to find the variants of its enums.
+const MAX_PASSES: usize = 256;
+
+/// Description of the RON representation of a type.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub enum Schema {
+    /// Any value, used where the type could not be described.
+    Any,
+    /// `true` or `false`.
+    Bool,
+    /// An integer number.
+    Integer,
+    /// A floating point number, integers are accepted as well.
+    Float,
+    /// A character, e.g. `'a'`.
+    Char,
+    /// A string, e.g. `"text"`.
+    String,
+    /// A byte buffer, written as a base64 string.
+    Bytes,
+    /// The unit value `()`.
+    Unit,
+    /// `Some(value)` or `None`.
+    Option(Box<Schema>),
+    /// A list of values, e.g. `[1, 2, 3]`.
+    Seq(Box<Schema>),
+    /// A tuple, e.g. `(1, "one")`.
+    Tuple(Vec<Schema>),
+    /// A map with key and value schemas, e.g. `{"one": 1}`.
+    Map(Box<Schema>, Box<Schema>),
+    /// A struct without fields, e.g. `Name`.
+    UnitStruct(String),
+    /// A struct wrapping a single value, e.g. `Name(value)`.
+    NewtypeStruct(String, Box<Schema>),
+    /// A tuple struct, e.g. `Name(1, 2)`.
+    TupleStruct(String, Vec<Schema>),
+    /// A struct with named fields, e.g. `Name(field: value)`.
+    Struct(String, Vec<(String, Schema)>),
+    /// An enum with the names of its variants.
+    ///
+    /// The contents of a variant are `None` if they could not be traced.
+    Enum(String, Vec<(String, Option<Variant>)>),
+}
+
+/// Contents of an enum variant.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub enum Variant {
+    /// A variant without contents, e.g. `Name`.
+    Unit,
+    /// A variant wrapping a single value, e.g. `Name(value)`.
+    Newtype(Schema),
+    /// A tuple variant, e.g. `Name(1, 2)`.
+    Tuple(Vec<Schema>),
+    /// A struct variant, e.g. `Name(field: value)`.
+    Struct(Vec<(String, Schema)>),
+}
+
+/// Problem found while validating a RON file against a `Schema`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ValidationError {
+    /// Path of the field containing the invalid value, e.g. `entities[2].data.transform`.
+    ///
+    /// Empty for the top level value and for syntax errors.
+    pub path: String,
+    /// Line of the invalid value, starting at 1.
+    pub line: usize,
+    /// Column of the invalid value, starting at 1.
+    pub column: usize,
+    /// Description of the problem.
+    pub message: String,
+}
+
+impl fmt::Display for ValidationError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.path.is_empty() {
+            write!(f, "{}:{}: {}", self.line, self.column, self.message)
+        } else {
+            write!(
+                f,
+                "{}:{}: {}: {}",
+                self.line, self.column, self.path, self.message
+            )
+        }
+    }
+}
+
+impl Schema {
+    /// Generates the schema of `T`.
+    ///
+    /// Enums are traced one variant per pass over `T`, so types with many variants take several
+    /// passes. Fails if the `Deserialize` implementation of `T` rejects the placeholder values
+    /// used for tracing.
+    pub fn of<T>() -> Result<Schema, ConfigError>
+    where
+        T: DeserializeOwned,
+    {
+        let mut schema = Schema::Any;
+        let mut state = TraceState::default();
+        while state.pass == 0 || (state.pass < state.max_variants && state.pass < MAX_PASSES) {
+            let mut traced = Schema::Any;
+            T::deserialize(Tracer {
+                state: &mut state,
+                slot: &mut traced,
+            })
+            .map_err(|e| ConfigError::Schema(e.0))?;
+            schema.merge(traced);
+            state.pass += 1;
+        }
+        Ok(schema)
+    }
+
+    /// Serializes the schema to RON.
+    pub fn to_ron(&self) -> Result<String, ConfigError> {
+        Ok(ron::ser::to_string_pretty(self, Default::default())?)
+    }
+
+    /// Validates a RON document, returning all problems found.
+    pub fn validate(&self, source: &str) -> Vec<ValidationError> {
+        match parse::parse(source) {
+            Ok((extensions, value)) => {
+                let mut validator = Validator {
+                    extensions,
+                    errors: Vec::new(),
+                };
+                validator.check(self, &value, "");
+                validator.errors
+            }
+            Err(error) => vec![error],
+        }
+    }
+
+    /// Validates a RON file, returning all problems found.
+    pub fn validate_file<P: AsRef<Path>>(
+        &self,
+        path: P,
+    ) -> Result<Vec<ValidationError>, ConfigError> {
+        let source = fs::read_to_string(path)?;
+        Ok(self.validate(&source))
+    }
+
+    /// Fills in the parts of the schema that are unknown with the parts known by `other`.
+    fn merge(&mut self, other: Schema) {
+        if let Schema::Any = *self {
+            *self = other;
+            return;
+        }
+        match (self, other) {
+            (Schema::Option(a), Schema::Option(b))
+            | (Schema::Seq(a), Schema::Seq(b))
+            | (Schema::NewtypeStruct(_, a), Schema::NewtypeStruct(_, b)) => a.merge(*b),
+            (Schema::Tuple(a), Schema::Tuple(b))
+            | (Schema::TupleStruct(_, a), Schema::TupleStruct(_, b)) => merge_all(a, b),
+            (Schema::Map(k, v), Schema::Map(k2, v2)) => {
+                k.merge(*k2);
+                v.merge(*v2);
+            }
+            (Schema::Struct(_, a), Schema::Struct(_, b)) => merge_fields(a, b),
+            (Schema::Enum(_, a), Schema::Enum(_, b)) => {
+                for ((_, a), (_, b)) in a.iter_mut().zip(b) {
+                    match (a, b) {
+                        (Some(a), Some(b)) => a.merge(b),
+                        (a, b @ Some(_)) => *a = b,
+                        _ => {}
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn describe(&self) -> String {
+        match *self {
+            Schema::Any => "any value".to_owned(),
+            Schema::Bool => "a boolean".to_owned(),
+            Schema::Integer => "an integer".to_owned(),
+            Schema::Float => "a number".to_owned(),
+            Schema::Char => "a character".to_owned(),
+            Schema::String | Schema::Bytes => "a string".to_owned(),
+            Schema::Unit => "`()`".to_owned(),
+            Schema::Option(_) => "`Some(..)` or `None`".to_owned(),
+            Schema::Seq(_) => "a list".to_owned(),
+            Schema::Tuple(ref items) => format!("a tuple of {} elements", items.len()),
+            Schema::Map(_, _) => "a map".to_owned(),
+            Schema::UnitStruct(ref name)
+            | Schema::NewtypeStruct(ref name, _)
+            | Schema::TupleStruct(ref name, _)
+            | Schema::Struct(ref name, _) => format!("struct `{}`", name),
+            Schema::Enum(ref name, _) => format!("enum `{}`", name),
+        }
+    }
+}
+
+impl Variant {
+    fn merge(&mut self, other: Variant) {
+        match (self, other) {
+            (Variant::Newtype(a), Variant::Newtype(b)) => a.merge(b),
+            (Variant::Tuple(a), Variant::Tuple(b)) => merge_all(a, b),
+            (Variant::Struct(a), Variant::Struct(b)) => merge_fields(a, b),
+            _ => {}
+        }
+    }
+}
+
+fn merge_all(a: &mut [Schema], b: Vec<Schema>) {
+    for (a, b) in a.iter_mut().zip(b) {
+        a.merge(b);
+    }
+}
+
+fn merge_fields(a: &mut [(String, Schema)], b: Vec<(String, Schema)>) {
+    for ((_, a), (_, b)) in a.iter_mut().zip(b) {
+        a.merge(b);
+    }
+}
+
+fn describe_value(value: &Value) -> String {
+    match value.kind {
+        ValueKind::Bool(_) => "a boolean".to_owned(),
+        ValueKind::Integer(_) => "an integer".to_owned(),
+        ValueKind::Float(_) => "a floating point number".to_owned(),
+        ValueKind::Char(_) => "a character".to_owned(),
+        ValueKind::String(_) => "a string".to_owned(),
+        ValueKind::Option(Some(_)) => "`Some(..)`".to_owned(),
+        ValueKind::Option(None) => "`None`".to_owned(),
+        ValueKind::List(_) => "a list".to_owned(),
+        ValueKind::Map(_) => "a map".to_owned(),
+        ValueKind::Ident(ref name) => format!("`{}`", name),
+        ValueKind::Tuple(Some(ref name), _) | ValueKind::Struct(Some(ref name), _) => {
+            format!("`{}(..)`", name)
+        }
+        ValueKind::Tuple(None, ref items) if items.is_empty() => "`()`".to_owned(),
+        ValueKind::Tuple(None, _) => "a tuple".to_owned(),
+        ValueKind::Struct(None, _) => "a struct".to_owned(),
+    }
+}
+
+fn field_path(path: &str, field: &str) -> String {
+    if path.is_empty() {
+        field.to_owned()
+    } else {
+        format!("{}.{}", path, field)
+    }
+}
+
+/// Checks a parsed RON document against a schema, collecting all errors.
+struct Validator {
+    extensions: Extensions,
+    errors: Vec<ValidationError>,
+}
+
+impl Validator {
+    fn error(&mut self, line: usize, column: usize, path: &str, message: String) {
+        self.errors.push(ValidationError {
+            path: path.to_owned(),
+            line,
+            column,
+            message,
+        });
+    }
+
+    fn mismatch(&mut self, schema: &Schema, value: &Value, path: &str) {
+        let message = format!(
+            "Expected {}, found {}",
+            schema.describe(),
+            describe_value(value)
+        );
+        self.error(value.line, value.column, path, message);
+    }
+
+    /// Checks the name given to a struct, if any.
+    fn check_name(&mut self, expected: &str, name: &Option<String>, value: &Value, path: &str) {
+        if let Some(ref name) = *name {
+            if name != expected {
+                let message = format!("Expected struct `{}`, found `{}`", expected, name);
+                self.error(value.line, value.column, path, message);
+            }
+        }
+    }
+
+    fn check(&mut self, schema: &Schema, value: &Value, path: &str) {
+        if let ValueKind::Option(ref inner) = value.kind {
+            match (schema, inner) {
+                (Schema::Any, _) => {}
+                (Schema::Option(_), None) => {}
+                (Schema::Option(schema), Some(inner)) => self.check(schema, inner, path),
+                _ => self.mismatch(schema, value, path),
+            }
+            return;
+        }
+
+        match (schema, &value.kind) {
+            (Schema::Any, _)
+            | (Schema::Bool, ValueKind::Bool(_))
+            | (Schema::Integer, ValueKind::Integer(_))
+            | (Schema::Float, ValueKind::Integer(_))
+            | (Schema::Float, ValueKind::Float(_))
+            | (Schema::Char, ValueKind::Char(_))
+            | (Schema::String, ValueKind::String(_))
+            | (Schema::Bytes, ValueKind::String(_)) => {}
+            (Schema::Unit, ValueKind::Tuple(None, items)) if items.is_empty() => {}
+            (Schema::Option(inner), _) if self.extensions.implicit_some => {
+                self.check(inner, value, path)
+            }
+            (Schema::Seq(inner), ValueKind::List(items)) => {
+                for (index, item) in items.iter().enumerate() {
+                    self.check(inner, item, &format!("{}[{}]", path, index));
+                }
+            }
+            (Schema::Map(key, inner), ValueKind::Map(entries)) => {
+                for (index, (k, v)) in entries.iter().enumerate() {
+                    let path = format!("{}[{}]", path, index);
+                    self.check(key, k, &path);
+                    self.check(inner, v, &path);
+                }
+            }
+            (Schema::Tuple(schemas), ValueKind::Tuple(None, items)) => {
+                self.check_items(schemas, items, value, path)
+            }
+            (Schema::UnitStruct(name), ValueKind::Ident(ident)) if name == ident => {}
+            (Schema::UnitStruct(name), ValueKind::Tuple(ident, items)) if items.is_empty() => {
+                self.check_name(name, ident, value, path)
+            }
+            (Schema::NewtypeStruct(name, inner), ValueKind::Tuple(ident, items))
+                if items.len() == 1 =>
+            {
+                self.check_name(name, ident, value, path);
+                self.check(inner, &items[0], path);
+            }
+            (Schema::NewtypeStruct(_, inner), _) if self.extensions.unwrap_newtypes => {
+                self.check(inner, value, path)
+            }
+            (Schema::TupleStruct(name, schemas), ValueKind::Tuple(ident, items)) => {
+                self.check_name(name, ident, value, path);
+                self.check_items(schemas, items, value, path);
+            }
+            (Schema::Struct(name, schemas), ValueKind::Struct(ident, fields)) => {
+                self.check_name(name, ident, value, path);
+                self.check_fields(schemas, fields, path);
+            }
+            (Schema::Struct(name, _), ValueKind::Tuple(ident, items)) if items.is_empty() => {
+                self.check_name(name, ident, value, path)
+            }
+            (Schema::Enum(name, variants), _) => self.check_enum(name, variants, value, path),
+            _ => self.mismatch(schema, value, path),
+        }
+    }
+
+    fn check_items(&mut self, schemas: &[Schema], items: &[Value], value: &Value, path: &str) {
+        if schemas.len() != items.len() {
+            let message = format!("Expected {} elements, found {}", schemas.len(), items.len());
+            self.error(value.line, value.column, path, message);
+        }
+        for (index, (schema, item)) in schemas.iter().zip(items).enumerate() {
+            self.check(schema, item, &field_path(path, &index.to_string()));
+        }
+    }
+
+    fn check_fields(
+        &mut self,
+        schemas: &[(String, Schema)],
+        fields: &[(parse::Field, Value)],
+        path: &str,
+    ) {
+        for (index, (field, value)) in fields.iter().enumerate() {
+            let path = field_path(path, &field.name);
+            if fields[..index].iter().any(|(f, _)| f.name == field.name) {
+                let message = format!("Duplicate field `{}`", field.name);
+                self.error(field.line, field.column, &path, message);
+                continue;
+            }
+            match schemas.iter().find(|(name, _)| *name == field.name) {
+                Some((_, schema)) => self.check(schema, value, &path),
+                None => {
+                    let expected = schemas
+                        .iter()
+                        .map(|(name, _)| format!("`{}`", name))
+                        .collect::<Vec<_>>()
+                        .join(", ");
+                    let message = format!(
+                        "Unknown field `{}`, expected one of {}",
+                        field.name, expected
+                    );
+                    self.error(field.line, field.column, &path, message);
+                }
+            }
+        }
+    }
+
+    fn check_enum(
+        &mut self,
+        name: &str,
+        variants: &[(String, Option<Variant>)],
+        value: &Value,
+        path: &str,
+    ) {
+        let variant_name = match value.kind {
+            ValueKind::Ident(ref variant)
+            | ValueKind::Tuple(Some(ref variant), _)
+            | ValueKind::Struct(Some(ref variant), _) => variant,
+            _ => {
+                let message = format!(
+                    "Expected a variant of enum `{}`, found {}",
+                    name,
+                    describe_value(value)
+                );
+                return self.error(value.line, value.column, path, message);
+            }
+        };
+
+        let variant = match variants.iter().find(|(v, _)| v == variant_name) {
+            Some((_, variant)) => variant,
+            None => {
+                let expected = variants
+                    .iter()
+                    .map(|(name, _)| format!("`{}`", name))
+                    .collect::<Vec<_>>()
+                    .join(", ");
+                let message = format!(
+                    "Unknown variant `{}` of enum `{}`, expected one of {}",
+                    variant_name, name, expected
+                );
+                return self.error(value.line, value.column, path, message);
+            }
+        };
+
+        match (variant, &value.kind) {
+            (None, _) | (Some(Variant::Unit), ValueKind::Ident(_)) => {}
+            (Some(Variant::Newtype(schema)), ValueKind::Tuple(_, items)) if items.len() == 1 => {
+                self.check(schema, &items[0], path)
+            }
+            (Some(Variant::Tuple(schemas)), ValueKind::Tuple(_, items)) => {
+                self.check_items(schemas, items, value, path)
+            }
+            (Some(Variant::Struct(schemas)), ValueKind::Struct(_, fields)) => {
+                self.check_fields(schemas, fields, path)
+            }
+            (Some(Variant::Struct(_)), ValueKind::Tuple(_, items)) if items.is_empty() => {}
+            (Some(variant), _) => {
+                let expected = match *variant {
+                    Variant::Unit => format!("`{}`", variant_name),
+                    Variant::Newtype(_) => format!("`{}(value)`", variant_name),
+                    Variant::Tuple(ref items) => {
+                        format!("`{}(..)` with {} elements", variant_name, items.len())
+                    }
+                    Variant::Struct(_) => format!("`{}(field: value, ..)`", variant_name),
+                };
+                let message = format!("Expected {}, found {}", expected, describe_value(value));
+                self.error(value.line, value.column, path, message);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use serde::Deserialize;
+
+    use super::*;
+
+    #[derive(Deserialize)]
+    #[allow(dead_code)]
+    enum Shape {
+        Empty,
+        Circle(f32),
+        Rect { width: f32, height: f32 },
+    }
+
+    #[derive(Deserialize)]
+    #[allow(dead_code)]
+    struct Data {
+        name: String,
+        #[serde(default)]
+        count: u32,
+        shapes: Vec<Shape>,
+        parent: Option<Box<Data>>,
+        tags: HashMap<String, (u8, bool)>,
+    }
+
+    #[test]
+    fn schema_traces_all_variants() {
+        let schema = Schema::of::<Data>().unwrap();
+        match schema {
+            Schema::Struct(ref name, ref fields) => {
+                assert_eq!(name, "Data");
+                let names = fields.iter().map(|(n, _)| n.as_str()).collect::<Vec<_>>();
+                assert_eq!(names, ["name", "count", "shapes", "parent", "tags"]);
+                match fields[2].1 {
+                    Schema::Seq(ref shape) => match **shape {
+                        Schema::Enum(_, ref variants) => {
+                            assert!(variants.iter().all(|(_, v)| v.is_some()))
+                        }
+                        _ => panic!("Expected enum, got {:?}", shape),
+                    },
+                    _ => panic!("Expected seq, got {:?}", fields[2].1),
+                }
+            }
+            _ => panic!("Expected struct, got {:?}", schema),
+        }
+    }
+
+    #[test]
+    fn validate_reports_all_errors() {
+        let schema = Schema::of::<Data>().unwrap();
+        let errors = schema.validate(
+            r#"#![enable(implicit_some)]
+Data(
+    name: "root",
+    shapes: [Empty, Circle(1), Rect(width: 1.0, hieght: 2.0), Square],
+    parent: (name: 3, shapes: [], tags: {}),
+    tags: {"a": (1, true)},
+)"#,
+        );
+        let found = errors
+            .iter()
+            .map(|e| (e.path.as_str(), e.line, e.column))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            found,
+            [
+                ("shapes[2].hieght", 4, 49),
+                ("shapes[3]", 4, 63),
+                ("parent.name", 5, 20),
+            ]
+        );
+        assert!(schema
+            .validate("(name: \"\", shapes: [], tags: {})")
+            .is_empty());
+    }
+
+    #[test]
+    fn schema_survives_ron() {
+        let schema = Schema::of::<Data>().unwrap();
+        assert_eq!(
+            schema,
+            ron::de::from_str(&schema.to_ron().unwrap()).unwrap()
+        );
+    }
+
+    #[cfg(feature = "json")]
+    #[test]
+    fn json_schema_follows_serde_json() {
+        use serde_json::json;
+
+        let schema = Schema::of::<Data>().unwrap().to_json_schema();
+        let properties = &schema["properties"];
+        assert_eq!(json!("Data"), schema["title"]);
+        assert_eq!(json!(false), schema["additionalProperties"]);
+        assert_eq!(json!({"type": "string"}), properties["name"]);
+        assert_eq!(json!({"type": "null"}), properties["parent"]["anyOf"][0]);
+        assert_eq!(json!("Data"), properties["parent"]["anyOf"][1]["title"]);
+        let shapes = properties["shapes"]["items"]["oneOf"].as_array().unwrap();
+        assert_eq!(json!({"const": "Empty"}), shapes[0]);
+        assert_eq!(json!(["Circle"]), shapes[1]["required"]);
+        assert_eq!(json!({"type": "number"}), shapes[1]["properties"]["Circle"]);
+        assert_eq!(
+            json!(2),
+            properties["tags"]["additionalProperties"]["maxItems"]
+        );
+    }
+}
@@ -0,0 +1,456 @@
+//! Parser for RON files which keeps the position of every value, so validation errors can point
+//! to the line and column of the invalid value.
+//!
+//! The accepted syntax follows the `ron` crate, which is used to load the files; the tests of
+//! `Value::from_ron` check both agree.
+
+use super::ValidationError;
+
+/// A parsed RON value with the position it starts at.
+#[derive(Debug)]
+pub struct Value {
+    pub kind: ValueKind,
+    pub line: usize,
+    pub column: usize,
+}
+
+#[derive(Debug)]
+pub enum ValueKind {
+    Bool(bool),
+    /// The text of an integer, which may contain `_` separators.
+    Integer(String),
+    /// The text of a floating point number.
+    Float(String),
+    Char(char),
+    String(String),
+    /// `Some(value)` or `None`.
+    Option(Option<Box<Value>>),
+    /// `[a, b]`
+    List(Vec<Value>),
+    /// `{key: value}`
+    Map(Vec<(Value, Value)>),
+    /// A bare identifier, e.g. a unit variant.
+    Ident(String),
+    /// `Name(a, b)` or `(a, b)`, including `()`.
+    Tuple(Option<String>, Vec<Value>),
+    /// `Name(field: value)` or `(field: value)`.
+    Struct(Option<String>, Vec<(Field, Value)>),
+}
+
+/// Name of a struct field with its position.
+#[derive(Debug)]
+pub struct Field {
+    pub name: String,
+    pub line: usize,
+    pub column: usize,
+}
+
+/// RON extensions enabled with `#![enable(...)]`.
+#[derive(Debug, Default)]
+pub struct Extensions {
+    pub implicit_some: bool,
+    pub unwrap_newtypes: bool,
+}
+
+/// Parses a RON document.
+pub fn parse(source: &str) -> Result<(Extensions, Value), ValidationError> {
+    let mut parser = Parser {
+        chars: source.chars().collect(),
+        pos: 0,
+        line: 1,
+        column: 1,
+    };
+    let extensions = parser.extensions()?;
+    let value = parser.value()?;
+    parser.skip_whitespace()?;
+    if parser.peek().is_some() {
+        return Err(parser.error("Unexpected trailing characters"));
+    }
+    Ok((extensions, value))
+}
+
+struct Parser {
+    chars: Vec<char>,
+    pos: usize,
+    line: usize,
+    column: usize,
+}
+
+impl Parser {
+    fn error(&self, message: &str) -> ValidationError {
+        ValidationError {
+            path: String::new(),
+            line: self.line,
+            column: self.column,
+            message: message.to_owned(),
+        }
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.chars.get(self.pos).cloned()
+    }
+
+    fn peek_at(&self, offset: usize) -> Option<char> {
+        self.chars.get(self.pos + offset).cloned()
+    }
+
+    fn bump(&mut self) -> Option<char> {
+        let c = self.peek()?;
+        self.pos += 1;
+        if c == '\n' {
+            self.line += 1;
+            self.column = 1;
+        } else {
+            self.column += 1;
+        }
+        Some(c)
+    }
+
+    fn expect(&mut self, expected: char) -> Result<(), ValidationError> {
+        self.skip_whitespace()?;
+        if self.peek() == Some(expected) {
+            self.bump();
+            Ok(())
+        } else {
+            Err(self.error(&format!("Expected `{}`", expected)))
+        }
+    }
+
+    /// Consumes `c` if it is the next character after whitespace.
+    fn eat(&mut self, c: char) -> Result<bool, ValidationError> {
+        self.skip_whitespace()?;
+        if self.peek() == Some(c) {
+            self.bump();
+            Ok(true)
+        } else {
+            Ok(false)
+        }
+    }
+
+    fn skip_whitespace(&mut self) -> Result<(), ValidationError> {
+        loop {
+            match (self.peek(), self.peek_at(1)) {
+                (Some(' '), _) | (Some('\t'), _) | (Some('\n'), _) | (Some('\r'), _) => {
+                    self.bump();
+                }
+                (Some('/'), Some('/')) => {
+                    while self.peek().map_or(false, |c| c != '\n') {
+                        self.bump();
+                    }
+                }
+                (Some('/'), Some('*')) => {
+                    self.bump();
+                    self.bump();
+                    // Block comments nest
+                    let mut depth = 1;
+                    while depth > 0 {
+                        match (self.bump(), self.peek()) {
+                            (Some('/'), Some('*')) => {
+                                self.bump();
+                                depth += 1;
+                            }
+                            (Some('*'), Some('/')) => {
+                                self.bump();
+                                depth -= 1;
+                            }
+                            (Some(_), _) => {}
+                            (None, _) => return Err(self.error("Unterminated block comment")),
+                        }
+                    }
+                }
+                _ => return Ok(()),
+            }
+        }
+    }
+
+    fn extensions(&mut self) -> Result<Extensions, ValidationError> {
+        let mut extensions = Extensions::default();
+        loop {
+            self.skip_whitespace()?;
+            if self.peek() != Some('#') {
+                return Ok(extensions);
+            }
+            self.bump();
+            self.expect('!')?;
+            self.expect('[')?;
+            self.skip_whitespace()?;
+            if self.identifier() != "enable" {
+                return Err(self.error("Expected `enable`"));
+            }
+            self.expect('(')?;
+            loop {
+                self.skip_whitespace()?;
+                match self.identifier().as_str() {
+                    "implicit_some" => extensions.implicit_some = true,
+                    "unwrap_newtypes" => extensions.unwrap_newtypes = true,
+                    "" => return Err(self.error("Expected an extension")),
+                    other => return Err(self.error(&format!("Unknown extension `{}`", other))),
+                }
+                if !self.eat(',')? {
+                    break;
+                }
+                // Allow a trailing comma
+                self.skip_whitespace()?;
+                if !self.is_ident_start() {
+                    break;
+                }
+            }
+            self.expect(')')?;
+            self.expect(']')?;
+        }
+    }
+
+    fn is_ident_start(&self) -> bool {
+        self.peek().map_or(false, is_ident_char)
+    }
+
+    fn identifier(&mut self) -> String {
+        let mut ident = String::new();
+        while let Some(c) = self.peek() {
+            if is_ident_char(c) {
+                ident.push(c);
+                self.bump();
+            } else {
+                break;
+            }
+        }
+        ident
+    }
+
+    fn value(&mut self) -> Result<Value, ValidationError> {
+        self.skip_whitespace()?;
+        let (line, column) = (self.line, self.column);
+        let kind = match self.peek() {
+            Some('(') => self.parenthesized(None)?,
+            Some('[') => {
+                self.bump();
+                let mut items = Vec::new();
+                while !self.eat(']')? {
+                    items.push(self.value()?);
+                    if !self.eat(',')? {
+                        self.expect(']')?;
+                        break;
+                    }
+                }
+                ValueKind::List(items)
+            }
+            Some('{') => {
+                self.bump();
+                let mut entries = Vec::new();
+                while !self.eat('}')? {
+                    let key = self.value()?;
+                    self.expect(':')?;
+                    entries.push((key, self.value()?));
+                    if !self.eat(',')? {
+                        self.expect('}')?;
+                        break;
+                    }
+                }
+                ValueKind::Map(entries)
+            }
+            Some('"') => ValueKind::String(self.string()?),
+            Some('r') if self.peek_at(1) == Some('"') || self.peek_at(1) == Some('#') => {
+                ValueKind::String(self.raw_string()?)
+            }
+            Some('\'') => {
+                self.bump();
+                let c = match self.bump() {
+                    Some('\\') => self.escape()?,
+                    Some(c) => c,
+                    None => return Err(self.error("Unterminated character")),
+                };
+                if self.bump() != Some('\'') {
+                    return Err(self.error("Unterminated character"));
+                }
+                ValueKind::Char(c)
+            }
+            Some(c) if c.is_ascii_digit() || c == '-' || c == '+' || c == '.' => self.number(),
+            Some(c) if c.is_ascii_alphabetic() || c == '_' => {
+                let ident = self.identifier();
+                match ident.as_str() {
+                    "true" => ValueKind::Bool(true),
+                    "false" => ValueKind::Bool(false),
+                    "None" => ValueKind::Option(None),
+                    "Some" => {
+                        self.expect('(')?;
+                        let inner = self.value()?;
+                        self.expect(')')?;
+                        ValueKind::Option(Some(Box::new(inner)))
+                    }
+                    _ => {
+                        self.skip_whitespace()?;
+                        if self.peek() == Some('(') {
+                            self.parenthesized(Some(ident))?
+                        } else {
+                            ValueKind::Ident(ident)
+                        }
+                    }
+                }
+            }
+            Some(c) => return Err(self.error(&format!("Unexpected character `{}`", c))),
+            None => return Err(self.error("Unexpected end of file")),
+        };
+        Ok(Value { kind, line, column })
+    }
+
+    /// Parses the contents of `(...)`, which are either struct fields or tuple items.
+    fn parenthesized(&mut self, name: Option<String>) -> Result<ValueKind, ValidationError> {
+        self.bump();
+        self.skip_whitespace()?;
+        if self.is_field() {
+            let mut fields = Vec::new();
+            while !self.eat(')')? {
+                self.skip_whitespace()?;
+                let field = Field {
+                    line: self.line,
+                    column: self.column,
+                    name: self.identifier(),
+                };
+                if field.name.is_empty() {
+                    return Err(self.error("Expected a field name"));
+                }
+                self.expect(':')?;
+                fields.push((field, self.value()?));
+                if !self.eat(',')? {
+                    self.expect(')')?;
+                    break;
+                }
+            }
+            Ok(ValueKind::Struct(name, fields))
+        } else {
+            let mut items = Vec::new();
+            while !self.eat(')')? {
+                items.push(self.value()?);
+                if !self.eat(',')? {
+                    self.expect(')')?;
+                    break;
+                }
+            }
+            Ok(ValueKind::Tuple(name, items))
+        }
+    }
+
+    /// Checks if the next characters are an identifier followed by `:`, without consuming them.
+    fn is_field(&self) -> bool {
+        let mut offset = 0;
+        while self.peek_at(offset).map_or(false, is_ident_char) {
+            offset += 1;
+        }
+        if offset == 0 {
+            return false;
+        }
+        while self
+            .peek_at(offset)
+            .map_or(false, |c| c == ' ' || c == '\t' || c == '\n' || c == '\r')
+        {
+            offset += 1;
+        }
+        self.peek_at(offset) == Some(':')
+    }
+
+    fn string(&mut self) -> Result<String, ValidationError> {
+        self.bump();
+        let mut string = String::new();
+        loop {
+            match self.bump() {
+                Some('"') => return Ok(string),
+                Some('\\') => string.push(self.escape()?),
+                Some(c) => string.push(c),
+                None => return Err(self.error("Unterminated string")),
+            }
+        }
+    }
+
+    /// Parses the escape sequence following a `\\`.
+    fn escape(&mut self) -> Result<char, ValidationError> {
+        let c = match self.bump() {
+            Some('n') => '\n',
+            Some('r') => '\r',
+            Some('t') => '\t',
+            Some(c @ '\\') | Some(c @ '\'') | Some(c @ '"') => c,
+            Some('x') => {
+                let code = (0..2).filter_map(|_| self.bump()).collect::<String>();
+                u8::from_str_radix(&code, 16)
+                    .map(char::from)
+                    .map_err(|_| self.error("Invalid byte escape"))?
+            }
+            Some('u') => {
+                self.expect('{')?;
+                let mut code = String::new();
+                while code.len() < 6 && self.peek().map_or(false, |c| c.is_ascii_hexdigit()) {
+                    code.push(self.bump().unwrap());
+                }
+                if self.bump() != Some('}') {
+                    return Err(self.error("Expected `}`"));
+                }
+                u32::from_str_radix(&code, 16)
+                    .ok()
+                    .and_then(std::char::from_u32)
+                    .ok_or_else(|| self.error("Invalid unicode escape"))?
+            }
+            Some(c) => return Err(self.error(&format!("Unknown escape `\\{}`", c))),
+            None => return Err(self.error("Unterminated escape sequence")),
+        };
+        Ok(c)
+    }
+
+    fn raw_string(&mut self) -> Result<String, ValidationError> {
+        self.bump();
+        let mut hashes = 0;
+        while self.peek() == Some('#') {
+            self.bump();
+            hashes += 1;
+        }
+        if self.bump() != Some('"') {
+            return Err(self.error("Expected `\"`"));
+        }
+        let mut string = String::new();
+        loop {
+            match self.bump() {
+                Some('"') if (0..hashes).all(|i| self.peek_at(i) == Some('#')) => {
+                    for _ in 0..hashes {
+                        self.bump();
+                    }
+                    return Ok(string);
+                }
+                Some(c) => string.push(c),
+                None => return Err(self.error("Unterminated raw string")),
+            }
+        }
+    }
+
+    fn number(&mut self) -> ValueKind {
+        let start = self.pos;
+        let mut float = false;
+        let mut hex = false;
+        if let Some('-') | Some('+') = self.peek() {
+            self.bump();
+        }
+        while let Some(c) = self.peek() {
+            match c {
+                '0'..='9' | '_' => {}
+                '.' => float = true,
+                'x' => hex = true,
+                'e' | 'E' if !hex => {
+                    float = true;
+                    if let Some('-') | Some('+') = self.peek_at(1) {
+                        self.bump();
+                    }
+                }
+                'b' | 'o' | 'a'..='f' | 'A'..='F' => {}
+                _ => break,
+            }
+            self.bump();
+        }
+        let text = self.chars[start..self.pos].iter().collect();
+        if float {
+            ValueKind::Float(text)
+        } else {
+            ValueKind::Integer(text)
+        }
+    }
+}
+
+fn is_ident_char(c: char) -> bool {
+    c.is_ascii_alphanumeric() || c == '_'
+}
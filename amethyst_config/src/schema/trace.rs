@@ -0,0 +1,421 @@
+//! Generates a `Schema` by tracing which methods of a `Deserializer` a type calls.
+
+use std::fmt;
+
+use serde::de::{
+    self, DeserializeSeed, Deserializer, EnumAccess, IntoDeserializer, MapAccess, SeqAccess,
+    VariantAccess, Visitor,
+};
+
+use super::{Schema, Variant};
+
+/// Containers nested deeper than this are described as `Schema::Any`, which stops the tracing of
+/// recursive types like trees.
+const MAX_DEPTH: usize = 32;
+
+/// String given to types deserializing a string.
+///
+/// Some types parse the string, so the nil uuid is used because it is also accepted by the
+/// string-parsing types used in assets.
+const SAMPLE_STR: &str = "00000000-0000-0000-0000-000000000000";
+
+/// Error returned by `Deserialize` implementations rejecting the traced values.
+#[derive(Debug)]
+pub struct TraceError(pub String);
+
+impl fmt::Display for TraceError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl std::error::Error for TraceError {}
+
+impl de::Error for TraceError {
+    fn custom<T: fmt::Display>(msg: T) -> Self {
+        TraceError(msg.to_string())
+    }
+}
+
+/// State shared by all passes over a type.
+#[derive(Default)]
+pub struct TraceState {
+    /// Index of the current pass, which selects the variant traced of every enum.
+    pub pass: usize,
+    /// Maximum number of variants of the enums found so far.
+    pub max_variants: usize,
+    depth: usize,
+}
+
+/// `Deserializer` which records the schema of the deserialized type in `slot`.
+pub struct Tracer<'a> {
+    pub state: &'a mut TraceState,
+    pub slot: &'a mut Schema,
+}
+
+impl<'a> Tracer<'a> {
+    fn nested(&self) -> bool {
+        self.state.depth < MAX_DEPTH
+    }
+}
+
+/// Traces a value with a new `Tracer`, returning the value and its schema.
+fn trace_seed<'de, T>(state: &mut TraceState, seed: T) -> Result<(T::Value, Schema), TraceError>
+where
+    T: DeserializeSeed<'de>,
+{
+    let mut schema = Schema::Any;
+    state.depth += 1;
+    let value = seed.deserialize(Tracer {
+        state: &mut *state,
+        slot: &mut schema,
+    });
+    state.depth -= 1;
+    Ok((value?, schema))
+}
+
+macro_rules! trace_primitive {
+    ($($method:ident => $schema:ident, $visit:ident($($value:expr),*);)*) => {
+        $(
+            fn $method<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, TraceError> {
+                *self.slot = Schema::$schema;
+                visitor.$visit($($value),*)
+            }
+        )*
+    };
+}
+
+impl<'de, 'a> Deserializer<'de> for Tracer<'a> {
+    type Error = TraceError;
+
+    trace_primitive! {
+        deserialize_bool => Bool, visit_bool(false);
+        deserialize_i8 => Integer, visit_i8(0);
+        deserialize_i16 => Integer, visit_i16(0);
+        deserialize_i32 => Integer, visit_i32(0);
+        deserialize_i64 => Integer, visit_i64(0);
+        deserialize_u8 => Integer, visit_u8(0);
+        deserialize_u16 => Integer, visit_u16(0);
+        deserialize_u32 => Integer, visit_u32(0);
+        deserialize_u64 => Integer, visit_u64(0);
+        deserialize_f32 => Float, visit_f32(0.0);
+        deserialize_f64 => Float, visit_f64(0.0);
+        deserialize_char => Char, visit_char(' ');
+        deserialize_str => String, visit_str(SAMPLE_STR);
+        deserialize_string => String, visit_str(SAMPLE_STR);
+        deserialize_identifier => String, visit_str(SAMPLE_STR);
+        deserialize_bytes => Bytes, visit_bytes(&[]);
+        deserialize_byte_buf => Bytes, visit_bytes(&[]);
+        deserialize_unit => Unit, visit_unit();
+        deserialize_any => Any, visit_unit();
+        deserialize_ignored_any => Any, visit_unit();
+    }
+
+    fn deserialize_option<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, TraceError> {
+        if !self.nested() {
+            *self.slot = Schema::Option(Box::new(Schema::Any));
+            return visitor.visit_none();
+        }
+        let mut inner = Schema::Any;
+        self.state.depth += 1;
+        let value = visitor.visit_some(Tracer {
+            state: &mut *self.state,
+            slot: &mut inner,
+        });
+        self.state.depth -= 1;
+        *self.slot = Schema::Option(Box::new(inner));
+        value
+    }
+
+    fn deserialize_unit_struct<V: Visitor<'de>>(
+        self,
+        name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, TraceError> {
+        *self.slot = Schema::UnitStruct(name.to_owned());
+        visitor.visit_unit()
+    }
+
+    fn deserialize_newtype_struct<V: Visitor<'de>>(
+        self,
+        name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, TraceError> {
+        let mut inner = Schema::Any;
+        self.state.depth += 1;
+        let value = visitor.visit_newtype_struct(Tracer {
+            state: &mut *self.state,
+            slot: &mut inner,
+        });
+        self.state.depth -= 1;
+        *self.slot = Schema::NewtypeStruct(name.to_owned(), Box::new(inner));
+        value
+    }
+
+    fn deserialize_seq<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, TraceError> {
+        let len = if self.nested() { 1 } else { 0 };
+        let mut items = Vec::new();
+        let value = visitor.visit_seq(TraceSeq {
+            state: &mut *self.state,
+            items: &mut items,
+            len,
+        });
+        *self.slot = Schema::Seq(Box::new(items.pop().unwrap_or(Schema::Any)));
+        value
+    }
+
+    fn deserialize_tuple<V: Visitor<'de>>(
+        self,
+        len: usize,
+        visitor: V,
+    ) -> Result<V::Value, TraceError> {
+        let mut items = Vec::new();
+        let value = visitor.visit_seq(TraceSeq {
+            state: &mut *self.state,
+            items: &mut items,
+            len,
+        });
+        *self.slot = Schema::Tuple(items);
+        value
+    }
+
+    fn deserialize_tuple_struct<V: Visitor<'de>>(
+        self,
+        name: &'static str,
+        len: usize,
+        visitor: V,
+    ) -> Result<V::Value, TraceError> {
+        let mut items = Vec::new();
+        let value = visitor.visit_seq(TraceSeq {
+            state: &mut *self.state,
+            items: &mut items,
+            len,
+        });
+        *self.slot = Schema::TupleStruct(name.to_owned(), items);
+        value
+    }
+
+    fn deserialize_map<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, TraceError> {
+        let mut key = Schema::Any;
+        let mut value_schema = Schema::Any;
+        let value = visitor.visit_map(TraceMap {
+            done: !self.nested(),
+            state: &mut *self.state,
+            key: &mut key,
+            value: &mut value_schema,
+        });
+        *self.slot = Schema::Map(Box::new(key), Box::new(value_schema));
+        value
+    }
+
+    fn deserialize_struct<V: Visitor<'de>>(
+        self,
+        name: &'static str,
+        fields: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, TraceError> {
+        let mut traced = Vec::new();
+        let value = visitor.visit_map(TraceFields {
+            state: &mut *self.state,
+            fields,
+            traced: &mut traced,
+        });
+        *self.slot = Schema::Struct(name.to_owned(), traced);
+        value
+    }
+
+    fn deserialize_enum<V: Visitor<'de>>(
+        self,
+        name: &'static str,
+        variants: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, TraceError> {
+        if variants.is_empty() {
+            return Err(TraceError(format!("Enum {} has no variants", name)));
+        }
+        self.state.max_variants = self.state.max_variants.max(variants.len());
+        let index = self.state.pass % variants.len();
+
+        let mut variant = None;
+        let value = visitor.visit_enum(TraceEnum {
+            state: &mut *self.state,
+            index,
+            variant: &mut variant,
+        });
+        *self.slot = Schema::Enum(
+            name.to_owned(),
+            variants
+                .iter()
+                .enumerate()
+                .map(|(i, v)| {
+                    let traced = if i == index { variant.take() } else { None };
+                    ((*v).to_owned(), traced)
+                })
+                .collect(),
+        );
+        value
+    }
+}
+
+/// Traces `len` elements of a sequence.
+struct TraceSeq<'a> {
+    state: &'a mut TraceState,
+    items: &'a mut Vec<Schema>,
+    len: usize,
+}
+
+impl<'de, 'a> SeqAccess<'de> for TraceSeq<'a> {
+    type Error = TraceError;
+
+    fn next_element_seed<T>(&mut self, seed: T) -> Result<Option<T::Value>, TraceError>
+    where
+        T: DeserializeSeed<'de>,
+    {
+        if self.items.len() == self.len {
+            return Ok(None);
+        }
+        let (value, schema) = trace_seed(self.state, seed)?;
+        self.items.push(schema);
+        Ok(Some(value))
+    }
+
+    fn size_hint(&self) -> Option<usize> {
+        Some(self.len - self.items.len())
+    }
+}
+
+/// Traces a single entry of a map.
+struct TraceMap<'a> {
+    state: &'a mut TraceState,
+    key: &'a mut Schema,
+    value: &'a mut Schema,
+    done: bool,
+}
+
+impl<'de, 'a> MapAccess<'de> for TraceMap<'a> {
+    type Error = TraceError;
+
+    fn next_key_seed<K>(&mut self, seed: K) -> Result<Option<K::Value>, TraceError>
+    where
+        K: DeserializeSeed<'de>,
+    {
+        if self.done {
+            return Ok(None);
+        }
+        self.done = true;
+        let (key, schema) = trace_seed(self.state, seed)?;
+        *self.key = schema;
+        Ok(Some(key))
+    }
+
+    fn next_value_seed<V>(&mut self, seed: V) -> Result<V::Value, TraceError>
+    where
+        V: DeserializeSeed<'de>,
+    {
+        let (value, schema) = trace_seed(self.state, seed)?;
+        *self.value = schema;
+        Ok(value)
+    }
+}
+
+/// Traces all fields of a struct.
+struct TraceFields<'a> {
+    state: &'a mut TraceState,
+    fields: &'static [&'static str],
+    traced: &'a mut Vec<(String, Schema)>,
+}
+
+impl<'de, 'a> MapAccess<'de> for TraceFields<'a> {
+    type Error = TraceError;
+
+    fn next_key_seed<K>(&mut self, seed: K) -> Result<Option<K::Value>, TraceError>
+    where
+        K: DeserializeSeed<'de>,
+    {
+        match self.fields.get(self.traced.len()) {
+            Some(field) => seed.deserialize((*field).into_deserializer()).map(Some),
+            None => Ok(None),
+        }
+    }
+
+    fn next_value_seed<V>(&mut self, seed: V) -> Result<V::Value, TraceError>
+    where
+        V: DeserializeSeed<'de>,
+    {
+        let field = self.fields[self.traced.len()];
+        let (value, schema) = trace_seed(self.state, seed)?;
+        self.traced.push((field.to_owned(), schema));
+        Ok(value)
+    }
+}
+
+/// Traces the variant `index` of an enum.
+struct TraceEnum<'a> {
+    state: &'a mut TraceState,
+    index: usize,
+    variant: &'a mut Option<Variant>,
+}
+
+impl<'de, 'a> EnumAccess<'de> for TraceEnum<'a> {
+    type Error = TraceError;
+    type Variant = Self;
+
+    fn variant_seed<V>(self, seed: V) -> Result<(V::Value, Self), TraceError>
+    where
+        V: DeserializeSeed<'de>,
+    {
+        let index = self.index as u32;
+        let value = seed.deserialize(index.into_deserializer())?;
+        Ok((value, self))
+    }
+}
+
+impl<'de, 'a> VariantAccess<'de> for TraceEnum<'a> {
+    type Error = TraceError;
+
+    fn unit_variant(self) -> Result<(), TraceError> {
+        *self.variant = Some(Variant::Unit);
+        Ok(())
+    }
+
+    fn newtype_variant_seed<T>(self, seed: T) -> Result<T::Value, TraceError>
+    where
+        T: DeserializeSeed<'de>,
+    {
+        let (value, schema) = trace_seed(self.state, seed)?;
+        *self.variant = Some(Variant::Newtype(schema));
+        Ok(value)
+    }
+
+    fn tuple_variant<V>(self, len: usize, visitor: V) -> Result<V::Value, TraceError>
+    where
+        V: Visitor<'de>,
+    {
+        let mut items = Vec::new();
+        let value = visitor.visit_seq(TraceSeq {
+            state: &mut *self.state,
+            items: &mut items,
+            len,
+        });
+        *self.variant = Some(Variant::Tuple(items));
+        value
+    }
+
+    fn struct_variant<V>(
+        self,
+        fields: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, TraceError>
+    where
+        V: Visitor<'de>,
+    {
+        let mut traced = Vec::new();
+        let value = visitor.visit_map(TraceFields {
+            state: &mut *self.state,
+            fields,
+            traced: &mut traced,
+        });
+        *self.variant = Some(Variant::Struct(traced));
+        value
+    }
+}
@@ -0,0 +1,1323 @@
+//! Untyped configuration values, used to merge and migrate configs independent of their type.
+
+use std::fmt;
+
+use serde::{
+    de::{
+        self,
+        value::{MapDeserializer, SeqDeserializer, StringDeserializer},
+        Deserialize, DeserializeOwned, DeserializeSeed, Deserializer, EnumAccess, IntoDeserializer,
+        MapAccess, SeqAccess, VariantAccess, Visitor,
+    },
+    forward_to_deserialize_any,
+    ser::{self, Serialize},
+};
+
+use crate::{
+    schema::parse::{self, ValueKind},
+    ConfigError,
+};
+
+/// An untyped config value, with the structure of a RON value.
+///
+/// Any type implementing `Serialize` can be converted into a `Value` with `Value::from_config`,
+/// and a `Value` can be converted into any type implementing `Deserialize` with
+/// `Value::into_config`. Since RON allows leaving out the names of structs, a `Value` doesn't
+/// know whether a name belongs to a struct or an enum variant; this is resolved when converting
+/// it into a type.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Value {
+    /// `()`, or a unit struct.
+    Unit,
+    /// `true` or `false`.
+    Bool(bool),
+    /// A negative integer, or one written by a `Serialize` implementation as signed.
+    I64(i64),
+    /// A positive integer.
+    U64(u64),
+    /// A floating point number.
+    F64(f64),
+    /// A character.
+    Char(char),
+    /// A string.
+    String(String),
+    /// `Some(value)` or `None`.
+    Option(Option<Box<Value>>),
+    /// A list, e.g. `[1, 2]`.
+    Seq(Vec<Value>),
+    /// A map, e.g. `{"a": 1}`.
+    Map(Vec<(Value, Value)>),
+    /// The elements of a tuple or tuple struct, e.g. `(1, 2)`.
+    Tuple(Vec<Value>),
+    /// The fields of a struct, e.g. `(a: 1, b: 2)`.
+    Struct(Vec<(String, Value)>),
+    /// A named value, which is either an enum variant or a struct with its name, e.g. `Name`,
+    /// `Name(1)` or `Name(a: 1)`.
+    Named(String, Option<Box<Value>>),
+}
+
+impl Value {
+    /// Converts `config` into a `Value`.
+    pub fn from_config<T: Serialize>(config: &T) -> Result<Value, ConfigError> {
+        config
+            .serialize(ValueSerializer)
+            .map_err(|e| ConfigError::Value(e.0))
+    }
+
+    /// Converts the value into `T`.
+    pub fn into_config<T: DeserializeOwned>(self) -> Result<T, ConfigError> {
+        T::deserialize(self).map_err(|e| ConfigError::Value(e.0))
+    }
+
+    /// Parses a RON document into a `Value`.
+    ///
+    /// The syntax is the one accepted by `ron`, with one exception: `Some(..)` can still be
+    /// written out when the `implicit_some` extension is enabled.
+    pub fn from_ron(source: &str) -> Result<Value, ConfigError> {
+        let (_, value) = parse::parse(source).map_err(|e| ConfigError::Value(e.to_string()))?;
+        Self::from_parsed(value)
+    }
+
+    pub(crate) fn from_parsed(value: parse::Value) -> Result<Value, ConfigError> {
+        let (line, column) = (value.line, value.column);
+        let number_error = |text: &str| {
+            ConfigError::Value(format!("{}:{}: Invalid number `{}`", line, column, text))
+        };
+        Ok(match value.kind {
+            ValueKind::Bool(b) => Value::Bool(b),
+            ValueKind::Integer(ref text) => {
+                parse_integer(text).ok_or_else(|| number_error(text.as_str()))?
+            }
+            ValueKind::Float(ref text) => Value::F64(text.parse().map_err(|_| number_error(text))?),
+            ValueKind::Char(c) => Value::Char(c),
+            ValueKind::String(s) => Value::String(s),
+            ValueKind::Option(inner) => Value::Option(match inner {
+                Some(inner) => Some(Box::new(Self::from_parsed(*inner)?)),
+                None => None,
+            }),
+            ValueKind::List(items) => Value::Seq(Self::from_all(items)?),
+            ValueKind::Map(entries) => Value::Map(
+                entries
+                    .into_iter()
+                    .map(|(k, v)| Ok((Self::from_parsed(k)?, Self::from_parsed(v)?)))
+                    .collect::<Result<_, ConfigError>>()?,
+            ),
+            ValueKind::Ident(name) => Value::Named(name, None),
+            ValueKind::Tuple(name, items) => {
+                let tuple = Value::Tuple(Self::from_all(items)?);
+                match name {
+                    Some(name) => Value::Named(name, Some(Box::new(tuple))),
+                    None => tuple,
+                }
+            }
+            ValueKind::Struct(name, fields) => {
+                let fields = Value::Struct(
+                    fields
+                        .into_iter()
+                        .map(|(field, value)| Ok((field.name, Self::from_parsed(value)?)))
+                        .collect::<Result<_, ConfigError>>()?,
+                );
+                match name {
+                    Some(name) => Value::Named(name, Some(Box::new(fields))),
+                    None => fields,
+                }
+            }
+        })
+    }
+
+    fn from_all(values: Vec<parse::Value>) -> Result<Vec<Value>, ConfigError> {
+        values.into_iter().map(Self::from_parsed).collect()
+    }
+
+    /// Returns the fields if this is a struct, or a struct with its name.
+    pub fn fields(&self) -> Option<&Vec<(String, Value)>> {
+        match *self {
+            Value::Struct(ref fields) => Some(fields),
+            Value::Named(_, Some(ref inner)) => inner.fields(),
+            _ => None,
+        }
+    }
+
+    /// Returns the fields if this is a struct, or a struct with its name.
+    pub fn fields_mut(&mut self) -> Option<&mut Vec<(String, Value)>> {
+        match *self {
+            Value::Struct(ref mut fields) => Some(fields),
+            Value::Named(_, Some(ref mut inner)) => inner.fields_mut(),
+            _ => None,
+        }
+    }
+
+    /// Returns the value of the field `name`, if this is a struct containing it.
+    pub fn field(&self, name: &str) -> Option<&Value> {
+        self.fields()?
+            .iter()
+            .find(|(field, _)| field == name)
+            .map(|(_, value)| value)
+    }
+
+    /// Returns the value at a path of fields separated by `.`, e.g. `display.fullscreen`.
+    pub fn get_path(&self, path: &str) -> Option<&Value> {
+        path.split('.')
+            .try_fold(self, |value, field| value.field(field))
+    }
+
+    /// Returns the value of the field `name`, if this is a struct containing it.
+    pub fn field_mut(&mut self, name: &str) -> Option<&mut Value> {
+        self.fields_mut()?
+            .iter_mut()
+            .find(|(field, _)| field == name)
+            .map(|(_, value)| value)
+    }
+
+    /// Returns the value at a path of fields separated by `.`, e.g. `display.fullscreen`.
+    pub fn get_path_mut(&mut self, path: &str) -> Option<&mut Value> {
+        path.split('.')
+            .try_fold(self, |value, field| value.field_mut(field))
+    }
+
+    /// Removes the value at a path of fields separated by `.`, returning it.
+    pub fn remove_path(&mut self, path: &str) -> Option<Value> {
+        let (parent, field) = match path.rfind('.') {
+            Some(i) => (self.get_path_mut(&path[..i])?, &path[i + 1..]),
+            None => (self, path),
+        };
+        let fields = parent.fields_mut()?;
+        let index = fields.iter().position(|(name, _)| name == field)?;
+        Some(fields.remove(index).1)
+    }
+
+    /// Moves the value at the path `from` to the path `to`, e.g. to migrate a renamed field.
+    ///
+    /// Returns `false` and leaves the value unchanged if there is no value at `from` or `to`
+    /// can't be set.
+    pub fn rename_path(&mut self, from: &str, to: &str) -> bool {
+        let value = match self.remove_path(from) {
+            Some(value) => value,
+            None => return false,
+        };
+        if self.set_path(to, value.clone()) {
+            true
+        } else {
+            self.set_path(from, value);
+            false
+        }
+    }
+
+    /// Sets the value at a path of fields separated by `.`, adding fields that don't exist.
+    ///
+    /// Returns `false` if a value on the path is not a struct.
+    pub fn set_path(&mut self, path: &str, new: Value) -> bool {
+        let mut value = self;
+        for field in path.split('.') {
+            let fields = match value.fields_mut() {
+                Some(fields) => fields,
+                None => return false,
+            };
+            let index = match fields.iter().position(|(name, _)| name == field) {
+                Some(index) => index,
+                None => {
+                    fields.push((field.to_owned(), Value::Struct(Vec::new())));
+                    fields.len() - 1
+                }
+            };
+            value = &mut fields[index].1;
+        }
+        *value = new;
+        true
+    }
+
+    /// Merges `other` into this value, field by field.
+    ///
+    /// Fields of structs are merged recursively, fields only present in `other` are added.
+    /// `Some` values are merged with their contents, enum variants with the same name are merged
+    /// with their fields if both have fields. All other values, including lists, maps and
+    /// variants changing between fields and tuple contents, are replaced by `other`.
+    pub fn merge(&mut self, other: Value) {
+        match (self, other) {
+            (Value::Option(Some(inner)), Value::Option(Some(other))) => inner.merge(*other),
+            (Value::Option(Some(inner)), other @ Value::Struct(_)) => inner.merge(other),
+            (this, other) => {
+                let mergeable = match (&*this, &other) {
+                    (Value::Named(name, Some(_)), Value::Named(other_name, Some(_))) => {
+                        name == other_name && this.fields().is_some() == other.fields().is_some()
+                    }
+                    (Value::Named(..), Value::Named(..)) => false,
+                    (this, other) => this.fields().is_some() && other.fields().is_some(),
+                };
+                if !mergeable {
+                    *this = other;
+                    return;
+                }
+                match other.into_fields() {
+                    Ok(fields) => merge_fields(this, fields),
+                    Err(other) => match (this, other) {
+                        (Value::Named(_, Some(inner)), Value::Named(_, Some(other))) => {
+                            inner.merge(*other)
+                        }
+                        (this, other) => *this = other,
+                    },
+                }
+            }
+        }
+    }
+
+    fn into_fields(self) -> Result<Vec<(String, Value)>, Value> {
+        match self {
+            Value::Struct(fields) => Ok(fields),
+            Value::Named(name, Some(inner)) => match *inner {
+                Value::Struct(fields) => Ok(fields),
+                inner => Err(Value::Named(name, Some(Box::new(inner)))),
+            },
+            other => Err(other),
+        }
+    }
+
+    /// Calls `f` with the path of every value which isn't a struct.
+    pub(crate) fn visit_leaves<F: FnMut(String)>(&self, path: &str, f: &mut F) {
+        match self.fields() {
+            Some(fields) => {
+                for (name, value) in fields {
+                    let path = if path.is_empty() {
+                        name.clone()
+                    } else {
+                        format!("{}.{}", path, name)
+                    };
+                    value.visit_leaves(&path, f);
+                }
+            }
+            None => f(path.to_owned()),
+        }
+    }
+}
+
+fn merge_fields(this: &mut Value, other: Vec<(String, Value)>) {
+    let fields = this
+        .fields_mut()
+        .expect("Merging fields into a value without fields");
+    for (name, value) in other {
+        match fields.iter_mut().find(|(field, _)| *field == name) {
+            Some((_, field)) => field.merge(value),
+            None => fields.push((name, value)),
+        }
+    }
+}
+
+fn parse_integer(text: &str) -> Option<Value> {
+    let (negative, digits) = match text.chars().next() {
+        Some('-') => (true, &text[1..]),
+        Some('+') => (false, &text[1..]),
+        _ => (false, text),
+    };
+    let (radix, digits) = match digits.get(..2) {
+        Some("0x") => (16, &digits[2..]),
+        Some("0o") => (8, &digits[2..]),
+        Some("0b") => (2, &digits[2..]),
+        _ => (10, digits),
+    };
+    if digits.starts_with('_') {
+        return None;
+    }
+    let value = u64::from_str_radix(&digits.replace('_', ""), radix).ok()?;
+    if negative {
+        if value <= i64::max_value() as u64 {
+            Some(Value::I64(-(value as i64)))
+        } else if value == i64::max_value() as u64 + 1 {
+            Some(Value::I64(i64::min_value()))
+        } else {
+            None
+        }
+    } else {
+        Some(Value::U64(value))
+    }
+}
+
+impl<'de> Deserialize<'de> for Value {
+    /// Deserializes any self-describing format into a `Value`.
+    ///
+    /// Maps with only string keys become structs, since most formats don't distinguish them.
+    fn deserialize<D: de::Deserializer<'de>>(deserializer: D) -> Result<Value, D::Error> {
+        deserializer.deserialize_any(ValueVisitor)
+    }
+}
+
+struct ValueVisitor;
+
+impl<'de> Visitor<'de> for ValueVisitor {
+    type Value = Value;
+
+    fn expecting(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("any config value")
+    }
+
+    fn visit_bool<E: de::Error>(self, v: bool) -> Result<Value, E> {
+        Ok(Value::Bool(v))
+    }
+
+    fn visit_i64<E: de::Error>(self, v: i64) -> Result<Value, E> {
+        Ok(if v < 0 {
+            Value::I64(v)
+        } else {
+            Value::U64(v as u64)
+        })
+    }
+
+    fn visit_u64<E: de::Error>(self, v: u64) -> Result<Value, E> {
+        Ok(Value::U64(v))
+    }
+
+    fn visit_f64<E: de::Error>(self, v: f64) -> Result<Value, E> {
+        Ok(Value::F64(v))
+    }
+
+    fn visit_char<E: de::Error>(self, v: char) -> Result<Value, E> {
+        Ok(Value::Char(v))
+    }
+
+    fn visit_str<E: de::Error>(self, v: &str) -> Result<Value, E> {
+        Ok(Value::String(v.to_owned()))
+    }
+
+    fn visit_string<E: de::Error>(self, v: String) -> Result<Value, E> {
+        Ok(Value::String(v))
+    }
+
+    fn visit_bytes<E: de::Error>(self, v: &[u8]) -> Result<Value, E> {
+        Ok(Value::Seq(
+            v.iter().map(|b| Value::U64(u64::from(*b))).collect(),
+        ))
+    }
+
+    fn visit_none<E: de::Error>(self) -> Result<Value, E> {
+        Ok(Value::Option(None))
+    }
+
+    fn visit_some<D: de::Deserializer<'de>>(self, deserializer: D) -> Result<Value, D::Error> {
+        Ok(Value::Option(Some(Box::new(Value::deserialize(
+            deserializer,
+        )?))))
+    }
+
+    fn visit_unit<E: de::Error>(self) -> Result<Value, E> {
+        Ok(Value::Unit)
+    }
+
+    fn visit_newtype_struct<D>(self, deserializer: D) -> Result<Value, D::Error>
+    where
+        D: de::Deserializer<'de>,
+    {
+        Ok(Value::Tuple(vec![Value::deserialize(deserializer)?]))
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Value, A::Error> {
+        let mut items = Vec::with_capacity(seq.size_hint().unwrap_or(0));
+        while let Some(item) = seq.next_element()? {
+            items.push(item);
+        }
+        Ok(Value::Seq(items))
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<Value, A::Error> {
+        let mut fields = Vec::with_capacity(map.size_hint().unwrap_or(0));
+        let mut entries = Vec::new();
+        while let Some((key, value)) = map.next_entry::<Value, Value>()? {
+            match key {
+                Value::String(key) => fields.push((key, value)),
+                key => entries.push((key, value)),
+            }
+        }
+        if entries.is_empty() {
+            Ok(Value::Struct(fields))
+        } else {
+            let fields = fields
+                .into_iter()
+                .map(|(key, value)| (Value::String(key), value));
+            Ok(Value::Map(fields.chain(entries).collect()))
+        }
+    }
+}
+
+impl fmt::Display for Value {
+    /// Formats the value as RON.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fn list<T, F>(f: &mut fmt::Formatter<'_>, items: &[T], mut item: F) -> fmt::Result
+        where
+            F: FnMut(&mut fmt::Formatter<'_>, &T) -> fmt::Result,
+        {
+            for (i, value) in items.iter().enumerate() {
+                if i > 0 {
+                    f.write_str(", ")?;
+                }
+                item(f, value)?;
+            }
+            Ok(())
+        }
+
+        match *self {
+            Value::Unit => f.write_str("()"),
+            Value::Bool(b) => write!(f, "{}", b),
+            Value::I64(i) => write!(f, "{}", i),
+            Value::U64(u) => write!(f, "{}", u),
+            Value::F64(x) => write!(f, "{:?}", x),
+            Value::Char(c) => write!(f, "{:?}", c),
+            Value::String(ref s) => write!(f, "{:?}", s),
+            Value::Option(None) => f.write_str("None"),
+            Value::Option(Some(ref inner)) => write!(f, "Some({})", inner),
+            Value::Seq(ref items) => {
+                f.write_str("[")?;
+                list(f, items, |f, v| write!(f, "{}", v))?;
+                f.write_str("]")
+            }
+            Value::Map(ref entries) => {
+                f.write_str("{")?;
+                list(f, entries, |f, (k, v)| write!(f, "{}: {}", k, v))?;
+                f.write_str("}")
+            }
+            Value::Tuple(ref items) => {
+                f.write_str("(")?;
+                list(f, items, |f, v| write!(f, "{}", v))?;
+                f.write_str(")")
+            }
+            Value::Struct(ref fields) => {
+                f.write_str("(")?;
+                list(f, fields, |f, (k, v)| write!(f, "{}: {}", k, v))?;
+                f.write_str(")")
+            }
+            Value::Named(ref name, None) => f.write_str(name),
+            Value::Named(ref name, Some(ref inner)) => write!(f, "{}{}", name, inner),
+        }
+    }
+}
+
+/// Error converting between `Value`s and typed configs.
+#[derive(Debug)]
+pub(crate) struct ValueError(pub String);
+
+impl fmt::Display for ValueError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl std::error::Error for ValueError {}
+
+impl de::Error for ValueError {
+    fn custom<T: fmt::Display>(msg: T) -> Self {
+        ValueError(msg.to_string())
+    }
+}
+
+impl ser::Error for ValueError {
+    fn custom<T: fmt::Display>(msg: T) -> Self {
+        ValueError(msg.to_string())
+    }
+}
+
+impl<'de> IntoDeserializer<'de, ValueError> for Value {
+    type Deserializer = Self;
+
+    fn into_deserializer(self) -> Self {
+        self
+    }
+}
+
+macro_rules! deserialize_integer {
+    ($($method:ident)*) => {
+        $(
+            fn $method<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, ValueError> {
+                match self {
+                    Value::F64(x) if x.fract() == 0.0 && x >= 0.0 => visitor.visit_u64(x as u64),
+                    Value::F64(x) if x.fract() == 0.0 => visitor.visit_i64(x as i64),
+                    other => other.deserialize_any(visitor),
+                }
+            }
+        )*
+    };
+}
+
+impl<'de> de::Deserializer<'de> for Value {
+    type Error = ValueError;
+
+    fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, ValueError> {
+        match self {
+            Value::Unit => visitor.visit_unit(),
+            Value::Bool(b) => visitor.visit_bool(b),
+            Value::I64(i) => visitor.visit_i64(i),
+            Value::U64(u) => visitor.visit_u64(u),
+            Value::F64(x) => visitor.visit_f64(x),
+            Value::Char(c) => visitor.visit_char(c),
+            Value::String(s) => visitor.visit_string(s),
+            Value::Option(None) => visitor.visit_none(),
+            Value::Option(Some(inner)) => visitor.visit_some(*inner),
+            Value::Seq(items) | Value::Tuple(items) => visit_seq(items, visitor),
+            Value::Map(entries) => visit_map(entries, visitor),
+            Value::Struct(fields) => visit_map(fields, visitor),
+            Value::Named(name, None) => visitor.visit_string(name),
+            Value::Named(_, Some(inner)) => inner.deserialize_any(visitor),
+        }
+    }
+
+    deserialize_integer! {
+        deserialize_i8 deserialize_i16 deserialize_i32 deserialize_i64
+        deserialize_u8 deserialize_u16 deserialize_u32 deserialize_u64
+    }
+
+    fn deserialize_option<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, ValueError> {
+        match self {
+            Value::Option(None) => visitor.visit_none(),
+            Value::Option(Some(inner)) => visitor.visit_some(*inner),
+            other => visitor.visit_some(other),
+        }
+    }
+
+    fn deserialize_unit<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, ValueError> {
+        match self {
+            Value::Unit | Value::Named(_, None) => visitor.visit_unit(),
+            Value::Tuple(ref items) if items.is_empty() => visitor.visit_unit(),
+            other => other.deserialize_any(visitor),
+        }
+    }
+
+    fn deserialize_unit_struct<V: Visitor<'de>>(
+        self,
+        _: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, ValueError> {
+        self.deserialize_unit(visitor)
+    }
+
+    fn deserialize_newtype_struct<V: Visitor<'de>>(
+        self,
+        _: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, ValueError> {
+        let value = match self {
+            Value::Named(name, Some(inner)) => {
+                let newtype = match *inner {
+                    Value::Tuple(ref items) => items.len() == 1,
+                    _ => false,
+                };
+                if newtype {
+                    *inner
+                } else {
+                    Value::Named(name, Some(inner))
+                }
+            }
+            other => other,
+        };
+        match value {
+            Value::Tuple(mut items) => {
+                if items.len() == 1 {
+                    visitor.visit_newtype_struct(items.remove(0))
+                } else {
+                    visitor.visit_newtype_struct(Value::Tuple(items))
+                }
+            }
+            other => visitor.visit_newtype_struct(other),
+        }
+    }
+
+    fn deserialize_seq<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, ValueError> {
+        match self {
+            Value::Named(_, Some(inner)) => inner.deserialize_seq(visitor),
+            other => other.deserialize_any(visitor),
+        }
+    }
+
+    fn deserialize_tuple<V: Visitor<'de>>(
+        self,
+        _: usize,
+        visitor: V,
+    ) -> Result<V::Value, ValueError> {
+        self.deserialize_seq(visitor)
+    }
+
+    fn deserialize_tuple_struct<V: Visitor<'de>>(
+        self,
+        _: &'static str,
+        _: usize,
+        visitor: V,
+    ) -> Result<V::Value, ValueError> {
+        self.deserialize_seq(visitor)
+    }
+
+    fn deserialize_map<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, ValueError> {
+        match self {
+            Value::Unit => visit_map(Vec::<(Value, Value)>::new(), visitor),
+            Value::Tuple(ref items) if items.is_empty() => {
+                visit_map(Vec::<(Value, Value)>::new(), visitor)
+            }
+            Value::Named(_, Some(inner)) => inner.deserialize_map(visitor),
+            other => other.deserialize_any(visitor),
+        }
+    }
+
+    fn deserialize_struct<V: Visitor<'de>>(
+        self,
+        _: &'static str,
+        _: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, ValueError> {
+        self.deserialize_map(visitor)
+    }
+
+    fn deserialize_enum<V: Visitor<'de>>(
+        self,
+        name: &'static str,
+        _: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, ValueError> {
+        match self {
+            Value::Named(variant, content) => visitor.visit_enum(Enum {
+                variant,
+                content: content.map(|c| *c),
+            }),
+            Value::String(variant) => visitor.visit_enum(Enum {
+                variant,
+                content: None,
+            }),
+            // Formats like JSON write enums as a map with a single entry.
+            Value::Struct(mut fields) => {
+                if fields.len() == 1 {
+                    let (variant, content) = fields.remove(0);
+                    visitor.visit_enum(Enum {
+                        variant,
+                        content: Some(content),
+                    })
+                } else {
+                    Err(ValueError(format!(
+                        "Expected a variant of enum {}, found {}",
+                        name,
+                        Value::Struct(fields)
+                    )))
+                }
+            }
+            other => Err(ValueError(format!(
+                "Expected a variant of enum {}, found {}",
+                name, other
+            ))),
+        }
+    }
+
+    forward_to_deserialize_any! {
+        bool f32 f64 char str string bytes byte_buf identifier ignored_any
+    }
+}
+
+fn visit_seq<'de, V: Visitor<'de>>(items: Vec<Value>, visitor: V) -> Result<V::Value, ValueError> {
+    let mut seq = SeqDeserializer::<_, ValueError>::new(items.into_iter());
+    let value = visitor.visit_seq(&mut seq)?;
+    seq.end()?;
+    Ok(value)
+}
+
+fn visit_map<'de, K, V>(entries: Vec<(K, Value)>, visitor: V) -> Result<V::Value, ValueError>
+where
+    K: IntoDeserializer<'de, ValueError>,
+    V: Visitor<'de>,
+{
+    let mut map = MapDeserializer::<_, ValueError>::new(entries.into_iter());
+    let value = visitor.visit_map(&mut map)?;
+    map.end()?;
+    Ok(value)
+}
+
+struct Enum {
+    variant: String,
+    content: Option<Value>,
+}
+
+impl<'de> EnumAccess<'de> for Enum {
+    type Error = ValueError;
+    type Variant = Content;
+
+    fn variant_seed<V>(self, seed: V) -> Result<(V::Value, Content), ValueError>
+    where
+        V: DeserializeSeed<'de>,
+    {
+        let variant: StringDeserializer<ValueError> = self.variant.into_deserializer();
+        Ok((seed.deserialize(variant)?, Content(self.content)))
+    }
+}
+
+/// Content of an enum variant.
+struct Content(Option<Value>);
+
+impl<'de> VariantAccess<'de> for Content {
+    type Error = ValueError;
+
+    fn unit_variant(self) -> Result<(), ValueError> {
+        match self.0 {
+            None | Some(Value::Unit) => Ok(()),
+            Some(Value::Tuple(ref items)) if items.is_empty() => Ok(()),
+            Some(other) => Err(ValueError(format!(
+                "Expected a unit variant, found {}",
+                other
+            ))),
+        }
+    }
+
+    fn newtype_variant_seed<T>(self, seed: T) -> Result<T::Value, ValueError>
+    where
+        T: DeserializeSeed<'de>,
+    {
+        match self.0 {
+            Some(Value::Tuple(mut items)) => {
+                if items.len() == 1 {
+                    seed.deserialize(items.remove(0))
+                } else {
+                    seed.deserialize(Value::Tuple(items))
+                }
+            }
+            Some(other) => seed.deserialize(other),
+            None => Err(ValueError("Expected a newtype variant".to_owned())),
+        }
+    }
+
+    fn tuple_variant<V>(self, _: usize, visitor: V) -> Result<V::Value, ValueError>
+    where
+        V: Visitor<'de>,
+    {
+        match self.0 {
+            Some(Value::Tuple(items)) => visit_seq(items, visitor),
+            _ => Err(ValueError("Expected a tuple variant".to_owned())),
+        }
+    }
+
+    fn struct_variant<V>(
+        self,
+        _: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, ValueError>
+    where
+        V: Visitor<'de>,
+    {
+        match self.0 {
+            Some(Value::Struct(fields)) => visit_map(fields, visitor),
+            _ => Err(ValueError("Expected a struct variant".to_owned())),
+        }
+    }
+}
+
+/// `Serializer` converting a type into a `Value`.
+struct ValueSerializer;
+
+macro_rules! serialize_primitive {
+    ($($method:ident($ty:ty) => $variant:ident;)*) => {
+        $(
+            fn $method(self, v: $ty) -> Result<Value, ValueError> {
+                Ok(Value::$variant(v.into()))
+            }
+        )*
+    };
+}
+
+impl ser::Serializer for ValueSerializer {
+    type Ok = Value;
+    type Error = ValueError;
+    type SerializeSeq = SerializeSeq;
+    type SerializeTuple = SerializeSeq;
+    type SerializeTupleStruct = SerializeSeq;
+    type SerializeTupleVariant = SerializeSeq;
+    type SerializeMap = SerializeMap;
+    type SerializeStruct = SerializeStruct;
+    type SerializeStructVariant = SerializeStruct;
+
+    serialize_primitive! {
+        serialize_i8(i8) => I64;
+        serialize_i16(i16) => I64;
+        serialize_i32(i32) => I64;
+        serialize_u8(u8) => U64;
+        serialize_u16(u16) => U64;
+        serialize_u32(u32) => U64;
+        serialize_f32(f32) => F64;
+    }
+
+    fn serialize_i64(self, v: i64) -> Result<Value, ValueError> {
+        Ok(Value::I64(v))
+    }
+
+    fn serialize_u64(self, v: u64) -> Result<Value, ValueError> {
+        Ok(Value::U64(v))
+    }
+
+    fn serialize_f64(self, v: f64) -> Result<Value, ValueError> {
+        Ok(Value::F64(v))
+    }
+
+    fn serialize_bool(self, v: bool) -> Result<Value, ValueError> {
+        Ok(Value::Bool(v))
+    }
+
+    fn serialize_char(self, v: char) -> Result<Value, ValueError> {
+        Ok(Value::Char(v))
+    }
+
+    fn serialize_str(self, v: &str) -> Result<Value, ValueError> {
+        Ok(Value::String(v.to_owned()))
+    }
+
+    fn serialize_bytes(self, v: &[u8]) -> Result<Value, ValueError> {
+        Ok(Value::Seq(
+            v.iter().map(|b| Value::U64(u64::from(*b))).collect(),
+        ))
+    }
+
+    fn serialize_none(self) -> Result<Value, ValueError> {
+        Ok(Value::Option(None))
+    }
+
+    fn serialize_some<T: ?Sized + Serialize>(self, value: &T) -> Result<Value, ValueError> {
+        Ok(Value::Option(Some(Box::new(value.serialize(self)?))))
+    }
+
+    fn serialize_unit(self) -> Result<Value, ValueError> {
+        Ok(Value::Unit)
+    }
+
+    fn serialize_unit_struct(self, _: &'static str) -> Result<Value, ValueError> {
+        Ok(Value::Unit)
+    }
+
+    fn serialize_unit_variant(
+        self,
+        _: &'static str,
+        _: u32,
+        variant: &'static str,
+    ) -> Result<Value, ValueError> {
+        Ok(Value::Named(variant.to_owned(), None))
+    }
+
+    fn serialize_newtype_struct<T: ?Sized + Serialize>(
+        self,
+        _: &'static str,
+        value: &T,
+    ) -> Result<Value, ValueError> {
+        Ok(Value::Tuple(vec![value.serialize(self)?]))
+    }
+
+    fn serialize_newtype_variant<T: ?Sized + Serialize>(
+        self,
+        _: &'static str,
+        _: u32,
+        variant: &'static str,
+        value: &T,
+    ) -> Result<Value, ValueError> {
+        let inner = Value::Tuple(vec![value.serialize(self)?]);
+        Ok(Value::Named(variant.to_owned(), Some(Box::new(inner))))
+    }
+
+    fn serialize_seq(self, len: Option<usize>) -> Result<SerializeSeq, ValueError> {
+        Ok(SerializeSeq {
+            items: Vec::with_capacity(len.unwrap_or(0)),
+            kind: SeqKind::Seq,
+        })
+    }
+
+    fn serialize_tuple(self, len: usize) -> Result<SerializeSeq, ValueError> {
+        Ok(SerializeSeq {
+            items: Vec::with_capacity(len),
+            kind: SeqKind::Tuple,
+        })
+    }
+
+    fn serialize_tuple_struct(
+        self,
+        _: &'static str,
+        len: usize,
+    ) -> Result<SerializeSeq, ValueError> {
+        self.serialize_tuple(len)
+    }
+
+    fn serialize_tuple_variant(
+        self,
+        _: &'static str,
+        _: u32,
+        variant: &'static str,
+        len: usize,
+    ) -> Result<SerializeSeq, ValueError> {
+        Ok(SerializeSeq {
+            items: Vec::with_capacity(len),
+            kind: SeqKind::Variant(variant),
+        })
+    }
+
+    fn serialize_map(self, len: Option<usize>) -> Result<SerializeMap, ValueError> {
+        Ok(SerializeMap {
+            entries: Vec::with_capacity(len.unwrap_or(0)),
+            key: None,
+        })
+    }
+
+    fn serialize_struct(self, _: &'static str, len: usize) -> Result<SerializeStruct, ValueError> {
+        Ok(SerializeStruct {
+            fields: Vec::with_capacity(len),
+            variant: None,
+        })
+    }
+
+    fn serialize_struct_variant(
+        self,
+        _: &'static str,
+        _: u32,
+        variant: &'static str,
+        len: usize,
+    ) -> Result<SerializeStruct, ValueError> {
+        Ok(SerializeStruct {
+            fields: Vec::with_capacity(len),
+            variant: Some(variant),
+        })
+    }
+}
+
+enum SeqKind {
+    Seq,
+    Tuple,
+    Variant(&'static str),
+}
+
+struct SerializeSeq {
+    items: Vec<Value>,
+    kind: SeqKind,
+}
+
+impl SerializeSeq {
+    fn push<T: ?Sized + Serialize>(&mut self, value: &T) -> Result<(), ValueError> {
+        self.items.push(value.serialize(ValueSerializer)?);
+        Ok(())
+    }
+
+    fn finish(self) -> Result<Value, ValueError> {
+        Ok(match self.kind {
+            SeqKind::Seq => Value::Seq(self.items),
+            SeqKind::Tuple => Value::Tuple(self.items),
+            SeqKind::Variant(variant) => {
+                Value::Named(variant.to_owned(), Some(Box::new(Value::Tuple(self.items))))
+            }
+        })
+    }
+}
+
+macro_rules! impl_serialize_seq {
+    ($($trait:ident::$method:ident),*) => {
+        $(
+            impl ser::$trait for SerializeSeq {
+                type Ok = Value;
+                type Error = ValueError;
+
+                fn $method<T: ?Sized + Serialize>(&mut self, value: &T) -> Result<(), ValueError> {
+                    self.push(value)
+                }
+
+                fn end(self) -> Result<Value, ValueError> {
+                    self.finish()
+                }
+            }
+        )*
+    };
+}
+
+impl_serialize_seq!(
+    SerializeSeq::serialize_element,
+    SerializeTuple::serialize_element,
+    SerializeTupleStruct::serialize_field,
+    SerializeTupleVariant::serialize_field
+);
+
+struct SerializeMap {
+    entries: Vec<(Value, Value)>,
+    key: Option<Value>,
+}
+
+impl ser::SerializeMap for SerializeMap {
+    type Ok = Value;
+    type Error = ValueError;
+
+    fn serialize_key<T: ?Sized + Serialize>(&mut self, key: &T) -> Result<(), ValueError> {
+        self.key = Some(key.serialize(ValueSerializer)?);
+        Ok(())
+    }
+
+    fn serialize_value<T: ?Sized + Serialize>(&mut self, value: &T) -> Result<(), ValueError> {
+        let key = self
+            .key
+            .take()
+            .ok_or_else(|| ValueError("Map value serialized before its key".to_owned()))?;
+        self.entries.push((key, value.serialize(ValueSerializer)?));
+        Ok(())
+    }
+
+    fn end(self) -> Result<Value, ValueError> {
+        Ok(Value::Map(self.entries))
+    }
+}
+
+struct SerializeStruct {
+    fields: Vec<(String, Value)>,
+    variant: Option<&'static str>,
+}
+
+impl SerializeStruct {
+    fn push<T: ?Sized + Serialize>(&mut self, key: &str, value: &T) -> Result<(), ValueError> {
+        self.fields
+            .push((key.to_owned(), value.serialize(ValueSerializer)?));
+        Ok(())
+    }
+
+    fn finish(self) -> Result<Value, ValueError> {
+        let fields = Value::Struct(self.fields);
+        Ok(match self.variant {
+            Some(variant) => Value::Named(variant.to_owned(), Some(Box::new(fields))),
+            None => fields,
+        })
+    }
+}
+
+impl ser::SerializeStruct for SerializeStruct {
+    type Ok = Value;
+    type Error = ValueError;
+
+    fn serialize_field<T: ?Sized + Serialize>(
+        &mut self,
+        key: &'static str,
+        value: &T,
+    ) -> Result<(), ValueError> {
+        self.push(key, value)
+    }
+
+    fn end(self) -> Result<Value, ValueError> {
+        self.finish()
+    }
+}
+
+impl ser::SerializeStructVariant for SerializeStruct {
+    type Ok = Value;
+    type Error = ValueError;
+
+    fn serialize_field<T: ?Sized + Serialize>(
+        &mut self,
+        key: &'static str,
+        value: &T,
+    ) -> Result<(), ValueError> {
+        self.push(key, value)
+    }
+
+    fn end(self) -> Result<Value, ValueError> {
+        self.finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{collections::BTreeMap, fmt};
+
+    use serde::{de::DeserializeOwned, Deserialize};
+
+    use super::Value;
+
+    #[derive(Debug, Deserialize, PartialEq)]
+    struct Unit;
+
+    #[derive(Debug, Deserialize, PartialEq)]
+    struct Meters(f32);
+
+    #[derive(Debug, Deserialize, PartialEq)]
+    struct Point(i32, i32);
+
+    #[derive(Debug, Deserialize, PartialEq)]
+    struct Named {
+        a: u32,
+        b: String,
+    }
+
+    #[derive(Debug, Deserialize, PartialEq)]
+    struct Extended {
+        count: Option<u32>,
+        nested: Option<Option<u32>>,
+        missing: Option<u32>,
+        length: Meters,
+    }
+
+    #[derive(Debug, Deserialize, PartialEq)]
+    enum Shape {
+        Empty,
+        Circle(f32),
+        Line(Point, Point),
+        Rect { width: f32, height: f32 },
+    }
+
+    /// Checks `Value::from_ron` reads `source` like `ron` does.
+    fn assert_conforms<T>(source: &str)
+    where
+        T: DeserializeOwned + fmt::Debug + PartialEq,
+    {
+        let expected = ron::de::from_str::<T>(source)
+            .unwrap_or_else(|e| panic!("`ron` rejects `{}`: {}", source, e));
+        let value = Value::from_ron(source)
+            .unwrap_or_else(|e| panic!("`Value::from_ron` rejects `{}`: {}", source, e));
+        assert_eq!(expected, value.into_config::<T>().unwrap(), "{}", source);
+    }
+
+    /// Checks both `Value::from_ron` and `ron` reject `source`.
+    fn assert_rejected<T>(source: &str)
+    where
+        T: DeserializeOwned + fmt::Debug,
+    {
+        assert!(
+            ron::de::from_str::<T>(source).is_err(),
+            "`ron` accepts `{}`",
+            source
+        );
+        assert!(
+            Value::from_ron(source)
+                .and_then(Value::into_config::<T>)
+                .is_err(),
+            "`Value::from_ron` accepts `{}`",
+            source
+        );
+    }
+
+    #[test]
+    fn merge_replaces_variants_changing_contents() {
+        let merged = |base: &str, overlay: &str| {
+            let mut value = Value::from_ron(base).unwrap();
+            value.merge(Value::from_ron(overlay).unwrap());
+            value
+        };
+        let value = |source: &str| Value::from_ron(source).unwrap();
+
+        assert_eq!(
+            value("Rect(width: 3, height: 2)"),
+            merged("Rect(width: 1, height: 2)", "Rect(width: 3)")
+        );
+        assert_eq!(
+            value("Rect(width: 3)"),
+            merged("Rect(1, 2)", "Rect(width: 3)")
+        );
+        assert_eq!(
+            value("Rect(3, 4)"),
+            merged("Rect(width: 1, height: 2)", "Rect(3, 4)")
+        );
+        assert_eq!(value("(a: 3)"), merged("Rect(1, 2)", "(a: 3)"));
+        assert_eq!(
+            value("(a: Some((b: 3, c: 2)))"),
+            merged("(a: Some((b: 1, c: 2)))", "(a: (b: 3))")
+        );
+    }
+
+    #[test]
+    fn from_ron_reads_numbers_like_ron() {
+        assert_conforms::<bool>("true");
+        assert_conforms::<bool>("false");
+        assert_conforms::<u32>("42");
+        assert_conforms::<i32>("-42");
+        assert_conforms::<i32>("+42");
+        assert_conforms::<u32>("1_000_000");
+        assert_conforms::<u8>("0xFF");
+        assert_conforms::<u32>("0xdead_beef");
+        assert_conforms::<i32>("-0x10");
+        assert_conforms::<u8>("0b1010");
+        assert_conforms::<u8>("0o17");
+        assert_conforms::<u64>("18446744073709551615");
+        assert_conforms::<f64>("1.5");
+        assert_conforms::<f64>("-2.25");
+        assert_conforms::<f64>("+0.5");
+        assert_conforms::<f64>(".5");
+        assert_conforms::<f64>("1e3");
+        assert_conforms::<f64>("1.5E-3");
+        assert_conforms::<f32>("3");
+
+        assert_rejected::<u32>("_1");
+        assert_rejected::<u32>("0x_1");
+        assert_rejected::<f64>("1_0.5");
+        assert_rejected::<f64>("inf");
+        assert_rejected::<f64>("NaN");
+    }
+
+    #[test]
+    fn from_ron_reads_strings_like_ron() {
+        assert_conforms::<char>("'a'");
+        assert_conforms::<char>("'é'");
+        assert_conforms::<char>(r"'\n'");
+        assert_conforms::<char>(r"'\''");
+        assert_conforms::<char>(r"'\\'");
+        assert_conforms::<char>(r"'\x41'");
+        assert_conforms::<char>(r"'\u{1F600}'");
+        assert_conforms::<String>(r#""plain""#);
+        assert_conforms::<String>(r#""\"quoted\" \\ \n\r\t \u{e9} \x41 \'""#);
+        assert_conforms::<String>("\"two\nlines\"");
+        assert_conforms::<String>(r#"r"C:\path""#);
+        assert_conforms::<String>(r###"r#"say "hi""#"###);
+        assert_conforms::<String>(r###"r##"a "# b"##"###);
+
+        assert_rejected::<char>(r"'\0'");
+        assert_rejected::<String>(r#""\q""#);
+        assert_rejected::<String>(r#""\u{}""#);
+        assert_rejected::<String>(r#""unterminated"#);
+    }
+
+    #[test]
+    fn from_ron_reads_collections_like_ron() {
+        assert_conforms::<Option<u32>>("None");
+        assert_conforms::<Option<u32>>("Some(3)");
+        assert_conforms::<Option<u32>>("Some ( 3 )");
+        assert_conforms::<Option<Option<u32>>>("Some(None)");
+        assert_conforms::<Vec<u32>>("[]");
+        assert_conforms::<Vec<u32>>("[1, 2, 3]");
+        assert_conforms::<Vec<u32>>("[1, 2, 3,]");
+        assert_conforms::<BTreeMap<String, u32>>("{}");
+        assert_conforms::<BTreeMap<String, u32>>(r#"{"a": 1, "b": 2,}"#);
+        assert_conforms::<BTreeMap<u32, Vec<bool>>>("{1: [true], 2: []}");
+        assert_conforms::<()>("()");
+        assert_conforms::<(u32, String, bool)>(r#"(1, "a", true)"#);
+        assert_conforms::<(u32, u32)>("(1, 2,)");
+
+        assert_rejected::<Option<u32>>("Some(3,)");
+        assert_rejected::<Vec<u32>>("[1 2]");
+        assert_rejected::<Vec<u32>>("[1,,]");
+        assert_rejected::<(u32, u32)>("(1, 2");
+    }
+
+    #[test]
+    fn from_ron_reads_structs_like_ron() {
+        assert_conforms::<Unit>("Unit");
+        assert_conforms::<Unit>("()");
+        assert_conforms::<Meters>("Meters(1.5)");
+        assert_conforms::<Meters>("(1.5)");
+        assert_conforms::<Point>("Point(1, -2)");
+        assert_conforms::<Point>("(1, -2,)");
+        assert_conforms::<Named>(r#"Named(a: 1, b: "x")"#);
+        assert_conforms::<Named>(r#"Named (a: 1, b: "x",)"#);
+        assert_conforms::<Named>(r#"(b: "x", a: 1)"#);
+        assert_conforms::<Vec<Shape>>(
+            "[Empty, Circle(1), Line((0, 0), Point(1, 1)), Rect(width: 2, height: 3.5)]",
+        );
+
+        assert_rejected::<Named>(r#"Named(a: 1 b: "x")"#);
+        assert_rejected::<Shape>("Square");
+    }
+
+    #[test]
+    fn from_ron_reads_extensions_and_comments_like_ron() {
+        assert_conforms::<Extended>(
+            "#![enable(implicit_some, unwrap_newtypes)]
+            (count: 1, nested: 2, missing: None, length: 1.5)",
+        );
+        assert_conforms::<Extended>(
+            "#![enable(implicit_some)]
+            #![enable(unwrap_newtypes,)]
+            (count: 1, nested: None, missing: None, length: 1.5)",
+        );
+        assert_conforms::<Vec<u32>>(
+            "// line comment
+            [
+            \t1, // after a value
+            \r\n\t2, /* block /* nested */ comment */ 3
+            ] // at the end",
+        );
+
+        assert_rejected::<u32>("#![enable()] 1");
+        assert_rejected::<u32>("#![enable(unknown)] 1");
+        assert_rejected::<u32>("/* /* unterminated */ 1");
+        assert_rejected::<u32>("1 2");
+    }
+}
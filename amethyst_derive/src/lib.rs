@@ -20,6 +20,29 @@ pub fn event_reader_derive(input: TokenStream) -> TokenStream {
 /// Deriving a `Prefab` requires that `amethyst::ecs::Entity` and
 /// `amethyst:assets::{PrefabData, PrefabError, ProgressCounter}` are imported
 /// and visible in the current scope. This is due to how Rust macros work.
+///
+/// The derive can be used on structs and enums. Each field is added to the
+/// entity using its own `PrefabData` implementation, or as a component if it
+/// is marked with `#[prefab(Component)]`. For an enum, only the fields of the
+/// variant being used are added, so different variants can apply different
+/// components. The `SystemData` contains the data of all fields of all
+/// variants, so two variants can't use fields with the same storage.
+///
+/// Fields support these attributes:
+///
+/// - `#[prefab(skip)]`: the field is not added to the entity, and is kept as is
+///   when merging
+/// - `#[prefab(add_to_entity = "path::to::function")]`: the field is added by
+///   calling `function(&field, entity, &mut system_data, entities, children)`,
+///   which returns `Result<(), Error>`, instead of the default behaviour. The
+///   system data is the one the field would use without the attribute.
+/// - `#[prefab(default)]`: when merging a prefab variant, the field keeps its
+///   value if the value in the variant is `Default::default()`, so variants
+///   only need to give the fields they change. The field type must implement
+///   `Default` and `PartialEq`.
+///
+/// Marking a variant of an enum with `#[prefab(default)]` implements `Default`
+/// for the enum, returning that variant with default fields.
 #[proc_macro_derive(PrefabData, attributes(prefab))]
 pub fn prefab_data_derive(input: TokenStream) -> TokenStream {
     let ast = parse_macro_input!(input as DeriveInput);
@@ -1,15 +1,51 @@
-use proc_macro2::{Literal, TokenStream};
+use proc_macro2::{Literal, Span, TokenStream};
 use quote::quote;
-use syn::{Attribute, Data, DeriveInput, Generics, Ident, Meta, NestedMeta, Type};
+use syn::{
+    Attribute, Data, DeriveInput, Fields, Generics, Ident, Lit, Member, Meta, NestedMeta, Path,
+    Type,
+};
 
 pub fn impl_prefab_data(ast: &DeriveInput) -> TokenStream {
-    if have_component_attribute(&ast.attrs[..]) {
+    if parse_attributes(&ast.attrs[..]).component {
         impl_prefab_data_component(ast)
     } else {
         impl_prefab_data_aggregate(ast)
     }
 }
 
+/// Options given in `#[prefab(...)]` attributes.
+#[derive(Default)]
+struct PrefabAttributes {
+    /// `#[prefab(Component)]`: the type or field is added to the entity as a component.
+    component: bool,
+    /// `#[prefab(skip)]`: the field is ignored.
+    skip: bool,
+    /// `#[prefab(default)]`: the variant is returned by the generated `Default` implementation,
+    /// or the field is kept when merging a default value into it.
+    default: bool,
+    /// `#[prefab(add_to_entity = "path")]`: function called instead of adding the field.
+    add_to_entity: Option<Path>,
+}
+
+/// A field of the struct or of an enum variant.
+struct FieldData {
+    ty: Type,
+    member: Member,
+    attrs: PrefabAttributes,
+}
+
+impl FieldData {
+    /// Name used to bind the field when matching on an enum variant.
+    fn binding(&self, prefix: &str) -> Ident {
+        match self.member {
+            Member::Named(ref ident) => Ident::new(&format!("{}_{}", prefix, ident), ident.span()),
+            Member::Unnamed(ref index) => {
+                Ident::new(&format!("{}_{}", prefix, index.index), Span::call_site())
+            }
+        }
+    }
+}
+
 fn impl_prefab_data_component(ast: &DeriveInput) -> TokenStream {
     let base = &ast.ident;
     let (_, ty_generics, where_clause) = ast.generics.split_for_impl();
@@ -29,56 +65,64 @@ fn impl_prefab_data_component(ast: &DeriveInput) -> TokenStream {
                 system_data.insert(entity, self.clone()).map(|_| ())?;
                 Ok(())
             }
+
+            fn component_types(&self, types: &mut Vec<::std::any::TypeId>) {
+                types.push(::std::any::TypeId::of::<Self>());
+            }
+
+            fn remove_components(entity: Entity,
+                                 types: &[::std::any::TypeId],
+                                 system_data: &mut Self::SystemData) -> ::std::result::Result<(), Error> {
+                if types.contains(&::std::any::TypeId::of::<Self>()) {
+                    system_data.remove(entity);
+                }
+                Ok(())
+            }
         }
     }
 }
 
 fn impl_prefab_data_aggregate(ast: &DeriveInput) -> TokenStream {
     let base = &ast.ident;
-    let data = collect_field_data(&ast.data);
+    let (_, ty_generics, where_clause) = ast.generics.split_for_impl();
+    let lf_tokens = gen_def_lt_tokens(&ast.generics);
+    let ty_tokens = gen_def_ty_params(&ast.generics);
 
-    let system_datas = data.iter().map(|(ty, _, is_component)| {
-        if *is_component {
-            quote! {
-                WriteStorage<'pfd, #ty>
-            }
-        } else {
-            quote! {
-                <#ty as PrefabData<'pfd>>::SystemData
-            }
+    let (system_datas, body, default) = match ast.data {
+        Data::Struct(ref s) => {
+            let fields = collect_field_data(&s.fields);
+            (system_datas(&fields), impl_struct(&fields), None)
         }
-    });
-    let adds = (0..data.len()).map(|n| {
-        let tuple_index = Literal::usize_unsuffixed(n);
-        let (_, name, is_component) = &data[n];
-        if *is_component {
-            quote! {
-                system_data.#tuple_index.insert(entity, self.#name.clone())?;
-            }
-        } else {
-            quote! {
-                self.#name.add_to_entity(entity, &mut system_data.#tuple_index, entities, children)?;
-            }
+        Data::Enum(ref e) => {
+            let variants = e
+                .variants
+                .iter()
+                .map(|v| {
+                    (
+                        v.ident.clone(),
+                        v.fields.clone(),
+                        collect_field_data(&v.fields),
+                        parse_attributes(&v.attrs[..]),
+                    )
+                })
+                .collect::<Vec<_>>();
+            let (fields, body) = impl_enum(base, &variants);
+            let default = impl_enum_default(base, &variants);
+            (system_datas(fields), body, default)
         }
-    });
-    let subs = (0..data.len()).filter_map(|n| {
-        let (_, name, is_component) = &data[n];
-        if *is_component {
-            None
-        } else {
-            let tuple_index = Literal::usize_unsuffixed(n);
-            Some(quote! {
-                if self.#name.load_sub_assets(progress, &mut system_data.#tuple_index)? {
-                    ret = true;
+        Data::Union(_) => panic!("PrefabData derive does not support unions"),
+    };
+
+    let default = default.map(|(variant, fields)| {
+        quote! {
+            impl<#lf_tokens #ty_tokens> Default for #base #ty_generics #where_clause {
+                fn default() -> Self {
+                    #base::#variant #fields
                 }
-            })
+            }
         }
     });
 
-    let (_, ty_generics, where_clause) = ast.generics.split_for_impl();
-    let lf_tokens = gen_def_lt_tokens(&ast.generics);
-    let ty_tokens = gen_def_ty_params(&ast.generics);
-
     quote! {
         impl<'pfd, #lf_tokens #ty_tokens> PrefabData<'pfd> for #base #ty_generics #where_clause {
             type SystemData = (
@@ -86,44 +130,362 @@ fn impl_prefab_data_aggregate(ast: &DeriveInput) -> TokenStream {
             );
             type Result = ();
 
-            fn add_to_entity(&self,
-                             entity: Entity,
-                             system_data: &mut Self::SystemData,
-                             entities: &[Entity],
-                             children: &[Entity]) -> ::std::result::Result<(), Error> {
-                #(#adds)*
-                Ok(())
+            #body
+        }
+
+        #default
+    }
+}
+
+/// Generates the `SystemData` of each field, in the order of the `SystemData` tuple.
+fn system_datas<I>(fields: I) -> Vec<TokenStream>
+where
+    I: IntoIterator,
+    I::Item: std::borrow::Borrow<FieldData>,
+{
+    use std::borrow::Borrow;
+    fields
+        .into_iter()
+        .filter(|f| !f.borrow().attrs.skip)
+        .map(|f| {
+            let ty = &f.borrow().ty;
+            if f.borrow().attrs.component {
+                quote! {
+                    WriteStorage<'pfd, #ty>
+                }
+            } else {
+                quote! {
+                    <#ty as PrefabData<'pfd>>::SystemData
+                }
             }
+        })
+        .collect()
+}
 
-            fn load_sub_assets(&mut self,
-                               progress: &mut ProgressCounter,
-                               system_data: &mut Self::SystemData) -> ::std::result::Result<bool, Error> {
-                let mut ret = false;
-                #(#subs)*
-                Ok(ret)
+/// Generates adding the field `value` to the entity, using the system data at `index`.
+fn gen_add(field: &FieldData, value: &TokenStream, index: usize) -> TokenStream {
+    let tuple_index = Literal::usize_unsuffixed(index);
+    if let Some(ref hook) = field.attrs.add_to_entity {
+        quote! {
+            #hook(#value, entity, &mut system_data.#tuple_index, entities, children)?;
+        }
+    } else if field.attrs.component {
+        quote! {
+            system_data.#tuple_index.insert(entity, #value.clone())?;
+        }
+    } else {
+        quote! {
+            #value.add_to_entity(entity, &mut system_data.#tuple_index, entities, children)?;
+        }
+    }
+}
+
+/// Generates loading the sub assets of the field `value`, using the system data at `index`.
+fn gen_sub(field: &FieldData, value: &TokenStream, index: usize) -> Option<TokenStream> {
+    if field.attrs.component {
+        return None;
+    }
+    let tuple_index = Literal::usize_unsuffixed(index);
+    Some(quote! {
+        if #value.load_sub_assets(progress, &mut system_data.#tuple_index)? {
+            ret = true;
+        }
+    })
+}
+
+/// Generates appending the component types added by the field `value`.
+///
+/// Fields with an `add_to_entity` hook report no types, because the components added by the
+/// hook are unknown.
+fn gen_types(field: &FieldData, value: &TokenStream) -> Option<TokenStream> {
+    let ty = &field.ty;
+    if field.attrs.add_to_entity.is_some() {
+        None
+    } else if field.attrs.component {
+        Some(quote! {
+            types.push(::std::any::TypeId::of::<#ty>());
+        })
+    } else {
+        Some(quote! {
+            #value.component_types(types);
+        })
+    }
+}
+
+/// Generates removing the components of the field with the given `types`, using the system data
+/// at `index`.
+fn gen_remove(field: &FieldData, index: usize) -> Option<TokenStream> {
+    let ty = &field.ty;
+    let tuple_index = Literal::usize_unsuffixed(index);
+    if field.attrs.add_to_entity.is_some() {
+        None
+    } else if field.attrs.component {
+        Some(quote! {
+            if types.contains(&::std::any::TypeId::of::<#ty>()) {
+                system_data.#tuple_index.remove(entity);
             }
+        })
+    } else {
+        Some(quote! {
+            <#ty as PrefabData<'pfd>>::remove_components(entity, types, &mut system_data.#tuple_index)?;
+        })
+    }
+}
+
+/// Generates merging `other` into the field `value`.
+fn gen_merge(field: &FieldData, value: &TokenStream, other: &TokenStream) -> TokenStream {
+    let merge = if field.attrs.component {
+        quote! {
+            *#value = #other;
+        }
+    } else {
+        quote! {
+            #value.merge(#other);
+        }
+    };
+    if field.attrs.default {
+        let ty = &field.ty;
+        quote! {
+            if #other != <#ty as Default>::default() {
+                #merge
+            }
+        }
+    } else {
+        merge
+    }
+}
+
+fn impl_struct(fields: &[FieldData]) -> TokenStream {
+    let mut adds = Vec::new();
+    let mut subs = Vec::new();
+    let mut merges = Vec::new();
+    let mut types = Vec::new();
+    let mut removes = Vec::new();
+    for (index, field) in fields.iter().filter(|f| !f.attrs.skip).enumerate() {
+        let member = &field.member;
+        let value = quote! { self.#member };
+        adds.push(gen_add(field, &quote! { (&self.#member) }, index));
+        subs.extend(gen_sub(field, &value, index));
+        types.extend(gen_types(field, &value));
+        removes.extend(gen_remove(field, index));
+        merges.push(gen_merge(
+            field,
+            &quote! { (&mut self.#member) },
+            &quote! { other.#member },
+        ));
+    }
+
+    quote! {
+        fn add_to_entity(&self,
+                         entity: Entity,
+                         system_data: &mut Self::SystemData,
+                         entities: &[Entity],
+                         children: &[Entity]) -> ::std::result::Result<(), Error> {
+            #(#adds)*
+            Ok(())
+        }
+
+        fn load_sub_assets(&mut self,
+                           progress: &mut ProgressCounter,
+                           system_data: &mut Self::SystemData) -> ::std::result::Result<bool, Error> {
+            let mut ret = false;
+            #(#subs)*
+            Ok(ret)
+        }
+
+        fn merge(&mut self, other: Self) {
+            #(#merges)*
+        }
+
+        #[allow(unused_variables)]
+        fn component_types(&self, types: &mut Vec<::std::any::TypeId>) {
+            #(#types)*
+        }
+
+        #[allow(unused_variables)]
+        fn remove_components(entity: Entity,
+                             types: &[::std::any::TypeId],
+                             system_data: &mut Self::SystemData) -> ::std::result::Result<(), Error> {
+            #(#removes)*
+            Ok(())
+        }
+    }
+}
+
+/// Generates the pattern matching all fields of a variant, binding them with `prefix`.
+fn variant_pattern(
+    base: &Ident,
+    variant: &Ident,
+    shape: &Fields,
+    fields: &[FieldData],
+    prefix: &str,
+) -> TokenStream {
+    let bindings = fields.iter().map(|f| f.binding(prefix));
+    match *shape {
+        Fields::Named(_) => {
+            let members = fields.iter().map(|f| &f.member);
+            quote! { #base::#variant { #(#members: #bindings),* } }
+        }
+        Fields::Unnamed(_) => quote! { #base::#variant ( #(#bindings),* ) },
+        Fields::Unit => quote! { #base::#variant },
+    }
+}
+
+type VariantData = (Ident, Fields, Vec<FieldData>, PrefabAttributes);
+
+/// Generates the body of the `PrefabData` implementation of an enum, and returns it together
+/// with the fields whose `SystemData` make up the `SystemData` tuple.
+///
+/// Fields of the same type share their `SystemData`, so variants can add the same component
+/// without fetching its storage twice. Types are compared by their tokens, so a type alias or
+/// two prefab types writing the same component still fetch the storage twice, which panics.
+fn impl_enum<'a>(base: &Ident, variants: &'a [VariantData]) -> (Vec<&'a FieldData>, TokenStream) {
+    let mut add_arms = Vec::new();
+    let mut sub_arms = Vec::new();
+    let mut merge_arms = Vec::new();
+    let mut type_arms = Vec::new();
+    let mut removes = Vec::new();
+    let mut shared: Vec<(String, &FieldData)> = Vec::new();
+    for (variant, shape, fields, _) in variants {
+        let mut adds = Vec::new();
+        let mut subs = Vec::new();
+        let mut merges = Vec::new();
+        let mut types = Vec::new();
+        for field in fields.iter().filter(|f| !f.attrs.skip) {
+            let ty = &field.ty;
+            let key = format!("{} {}", field.attrs.component, quote! { #ty });
+            let index = match shared.iter().position(|(k, _)| *k == key) {
+                Some(index) => index,
+                None => {
+                    removes.extend(gen_remove(field, shared.len()));
+                    shared.push((key, field));
+                    shared.len() - 1
+                }
+            };
+            let binding = field.binding("field");
+            let other = field.binding("other");
+            let value = quote! { #binding };
+            adds.push(gen_add(field, &value, index));
+            subs.extend(gen_sub(field, &value, index));
+            types.extend(gen_types(field, &value));
+            merges.push(gen_merge(field, &value, &quote! { #other }));
         }
+
+        let pattern = variant_pattern(base, variant, shape, fields, "field");
+        let other_pattern = variant_pattern(base, variant, shape, fields, "other");
+        add_arms.push(quote! {
+            #pattern => {
+                #(#adds)*
+            }
+        });
+        sub_arms.push(quote! {
+            #pattern => {
+                #(#subs)*
+            }
+        });
+        merge_arms.push(quote! {
+            (#pattern, #other_pattern) => {
+                #(#merges)*
+            }
+        });
+        type_arms.push(quote! {
+            #pattern => {
+                #(#types)*
+            }
+        });
     }
+
+    let body = quote! {
+        #[allow(unused_variables)]
+        fn add_to_entity(&self,
+                         entity: Entity,
+                         system_data: &mut Self::SystemData,
+                         entities: &[Entity],
+                         children: &[Entity]) -> ::std::result::Result<(), Error> {
+            match self {
+                #(#add_arms)*
+            }
+            Ok(())
+        }
+
+        #[allow(unused_variables)]
+        fn load_sub_assets(&mut self,
+                           progress: &mut ProgressCounter,
+                           system_data: &mut Self::SystemData) -> ::std::result::Result<bool, Error> {
+            let mut ret = false;
+            match self {
+                #(#sub_arms)*
+            }
+            Ok(ret)
+        }
+
+        #[allow(unused_variables, unreachable_patterns)]
+        fn merge(&mut self, other: Self) {
+            match (self, other) {
+                #(#merge_arms)*
+                (this, other) => *this = other,
+            }
+        }
+
+        #[allow(unused_variables)]
+        fn component_types(&self, types: &mut Vec<::std::any::TypeId>) {
+            match self {
+                #(#type_arms)*
+            }
+        }
+
+        // The components of all variants are removed, because a reloaded prefab may use a
+        // different variant.
+        #[allow(unused_variables)]
+        fn remove_components(entity: Entity,
+                             types: &[::std::any::TypeId],
+                             system_data: &mut Self::SystemData) -> ::std::result::Result<(), Error> {
+            #(#removes)*
+            Ok(())
+        }
+    };
+    (shared.into_iter().map(|(_, field)| field).collect(), body)
 }
 
-fn collect_field_data(ast: &Data) -> Vec<(Type, Ident, bool)> {
-    match *ast {
-        Data::Struct(ref s) => s
-            .fields
-            .iter()
-            .map(|f| {
-                (
-                    f.ty.clone(),
-                    f.ident
-                        .as_ref()
-                        .expect("PrefabData derive only support named fields")
-                        .clone(),
-                    have_component_attribute(&f.attrs[..]),
-                )
-            })
-            .collect(),
-        _ => panic!("PrefabData aggregate derive only support structs"),
+/// Returns the variant marked with `#[prefab(default)]`, and the tokens constructing its fields.
+fn impl_enum_default(base: &Ident, variants: &[VariantData]) -> Option<(Ident, TokenStream)> {
+    let mut defaults = variants.iter().filter(|(_, _, _, attrs)| attrs.default);
+    let (variant, shape, fields, _) = defaults.next()?;
+    if defaults.next().is_some() {
+        panic!(
+            "Only one variant of {} can be marked with #[prefab(default)]",
+            base
+        );
     }
+
+    let values = fields.iter().map(|_| quote! { Default::default() });
+    let fields = match *shape {
+        Fields::Named(_) => {
+            let members = fields.iter().map(|f| &f.member);
+            quote! { { #(#members: #values),* } }
+        }
+        Fields::Unnamed(_) => quote! { ( #(#values),* ) },
+        Fields::Unit => quote! {},
+    };
+    Some((variant.clone(), fields))
+}
+
+fn collect_field_data(fields: &Fields) -> Vec<FieldData> {
+    fields
+        .iter()
+        .enumerate()
+        .map(|(index, f)| {
+            let member = match f.ident {
+                Some(ref ident) => Member::Named(ident.clone()),
+                None => Member::Unnamed(index.into()),
+            };
+            FieldData {
+                ty: f.ty.clone(),
+                member,
+                attrs: parse_attributes(&f.attrs[..]),
+            }
+        })
+        .collect()
 }
 
 fn gen_def_lt_tokens(generics: &Generics) -> TokenStream {
@@ -158,7 +520,8 @@ fn gen_def_ty_params(generics: &Generics) -> TokenStream {
     quote! { #( #ty_params ),* }
 }
 
-fn have_component_attribute(attrs: &[Attribute]) -> bool {
+fn parse_attributes(attrs: &[Attribute]) -> PrefabAttributes {
+    let mut parsed = PrefabAttributes::default();
     for meta in attrs
         .iter()
         .filter(|attr| attr.path.segments[0].ident == "prefab")
@@ -173,7 +536,26 @@ fn have_component_attribute(attrs: &[Attribute]) -> bool {
                     match *nested_meta {
                         NestedMeta::Meta(Meta::Word(ref word)) => {
                             if word == "Component" {
-                                return true;
+                                parsed.component = true;
+                            } else if word == "skip" {
+                                parsed.skip = true;
+                            } else if word == "default" {
+                                parsed.default = true;
+                            } else {
+                                panic!("Unknown prefab attribute {}", word);
+                            }
+                        }
+                        NestedMeta::Meta(Meta::NameValue(ref name_value))
+                            if name_value.ident == "add_to_entity" =>
+                        {
+                            match name_value.lit {
+                                Lit::Str(ref path) => {
+                                    parsed.add_to_entity = Some(
+                                        path.parse()
+                                            .expect("add_to_entity must be the path of a function"),
+                                    );
+                                }
+                                _ => panic!("add_to_entity must be a string literal"),
                             }
                         }
                         _ => panic!("prefab attribute does not contain a single word value"),
@@ -183,5 +565,5 @@ fn have_component_attribute(attrs: &[Attribute]) -> bool {
             _ => (),
         };
     }
-    false
+    parsed
 }
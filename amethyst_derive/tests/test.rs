@@ -2,7 +2,10 @@ use amethyst_derive::{EventReader, PrefabData};
 
 use amethyst_assets::{PrefabData, ProgressCounter};
 use amethyst_core::{
-    ecs::{Component, DenseVecStorage, Entity, Read, Resources, SystemData, WriteStorage},
+    ecs::{
+        Builder, Component, DenseVecStorage, Entity, Read, Resources, SystemData, World,
+        WriteStorage,
+    },
     shrev::{EventChannel, ReaderId},
     EventReader,
 };
@@ -21,7 +24,7 @@ pub enum TestEvent {
     Two(TestEvent2),
 }
 
-#[derive(Clone, PrefabData, Default)]
+#[derive(Clone, Debug, PrefabData, Default, PartialEq)]
 #[prefab(Component)]
 pub struct Stuff<T>
 where
@@ -59,3 +62,128 @@ pub struct Outer {
     #[prefab(Component)]
     external: External,
 }
+
+fn add_stuff(
+    stuff: &Stuff<u32>,
+    entity: Entity,
+    storage: &mut WriteStorage<'_, Stuff<u32>>,
+    _: &[Entity],
+    _: &[Entity],
+) -> Result<(), Error> {
+    storage.insert(
+        entity,
+        Stuff {
+            inner: stuff.inner + 1,
+        },
+    )?;
+    Ok(())
+}
+
+#[derive(PrefabData, Clone)]
+pub enum EnumPrefab {
+    #[prefab(default)]
+    Empty,
+    Thing {
+        #[prefab(Component)]
+        external: External,
+        #[prefab(skip)]
+        #[allow(dead_code)]
+        comment: String,
+    },
+    Creature(#[prefab(add_to_entity = "add_stuff")] Stuff<u32>),
+    Tagged {
+        #[prefab(Component)]
+        external: External,
+        stuff: Stuff<u32>,
+    },
+}
+
+#[derive(PrefabData, Clone, Default)]
+pub struct Overridable {
+    #[prefab(Component, default)]
+    kept: Stuff<u32>,
+    #[prefab(Component)]
+    replaced: Stuff<u32>,
+}
+
+#[test]
+fn merge_keeps_default_fields() {
+    let mut base = Overridable {
+        kept: Stuff { inner: 1 },
+        replaced: Stuff { inner: 1 },
+    };
+    base.merge(Overridable::default());
+    assert_eq!(Stuff { inner: 1 }, base.kept);
+    assert_eq!(Stuff { inner: 0 }, base.replaced);
+
+    base.merge(Overridable {
+        kept: Stuff { inner: 2 },
+        replaced: Stuff { inner: 3 },
+    });
+    assert_eq!(Stuff { inner: 2 }, base.kept);
+    assert_eq!(Stuff { inner: 3 }, base.replaced);
+}
+
+#[test]
+fn enum_default_is_marked_variant() {
+    match EnumPrefab::default() {
+        EnumPrefab::Empty => {}
+        _ => panic!("Expected the variant marked with #[prefab(default)]"),
+    }
+}
+
+#[test]
+fn enum_variants_add_to_entity() {
+    let prefabs = vec![
+        EnumPrefab::Empty,
+        EnumPrefab::Thing {
+            external: External { inner: 1 },
+            comment: String::from("skipped"),
+        },
+        EnumPrefab::Creature(Stuff { inner: 1 }),
+        EnumPrefab::Tagged {
+            external: External { inner: 2 },
+            stuff: Stuff { inner: 5 },
+        },
+    ];
+
+    let mut world = World::new();
+    <EnumPrefab as PrefabData<'_>>::SystemData::setup(&mut world.res);
+    let entities = prefabs
+        .iter()
+        .map(|_| world.create_entity().build())
+        .collect::<Vec<_>>();
+    {
+        let mut data = <EnumPrefab as PrefabData<'_>>::SystemData::fetch(&world.res);
+        for (prefab, entity) in prefabs.iter().zip(&entities) {
+            prefab
+                .add_to_entity(*entity, &mut data, &entities, &[])
+                .unwrap();
+        }
+    }
+
+    let externals = world.read_storage::<External>();
+    let stuffs = world.read_storage::<Stuff<u32>>();
+    let external = |i: usize| externals.get(entities[i]).map(|e| e.inner);
+    let stuff = |i: usize| stuffs.get(entities[i]).map(|s| s.inner);
+    assert_eq!((None, None), (external(0), stuff(0)));
+    assert_eq!((Some(1), None), (external(1), stuff(1)));
+    // The `add_to_entity` hook increments the value.
+    assert_eq!((None, Some(2)), (external(2), stuff(2)));
+    assert_eq!((Some(2), Some(5)), (external(3), stuff(3)));
+}
+
+#[test]
+fn enum_component_types_ignore_skipped_and_hooked_fields() {
+    let mut types = Vec::new();
+    EnumPrefab::Thing {
+        external: External { inner: 1 },
+        comment: String::new(),
+    }
+    .component_types(&mut types);
+    assert_eq!(vec![::std::any::TypeId::of::<External>()], types);
+
+    types.clear();
+    EnumPrefab::Creature(Stuff { inner: 1 }).component_types(&mut types);
+    assert!(types.is_empty());
+}
@@ -0,0 +1,24 @@
+use amethyst_assets::Processor;
+use amethyst_core::{bundle::SystemBundle, ecs::prelude::DispatcherBuilder};
+use amethyst_error::Error;
+
+use crate::{Locale, LocalizationSystem};
+
+/// Bundle adding the asset processor for `Locale` and the `LocalizationSystem`.
+#[derive(Debug, Default)]
+pub struct LocaleBundle;
+
+impl LocaleBundle {
+    /// Creates a new `LocaleBundle`.
+    pub fn new() -> Self {
+        LocaleBundle
+    }
+}
+
+impl<'a, 'b> SystemBundle<'a, 'b> for LocaleBundle {
+    fn build(self, builder: &mut DispatcherBuilder<'a, 'b>) -> Result<(), Error> {
+        builder.add(Processor::<Locale>::new(), "locale_processor", &[]);
+        builder.add(LocalizationSystem::new(), "localization_system", &[]);
+        Ok(())
+    }
+}
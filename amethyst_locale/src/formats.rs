@@ -0,0 +1,318 @@
+use std::sync::Arc;
+
+use fluent::{bundle::FluentBundle, errors::FluentError};
+
+use amethyst_assets::{Format, FormatValue, Reload, SimpleFormat, Source};
+use amethyst_error::{format_err, Error, ResultExt};
+
+use crate::Locale;
+
+/// Loads the strings from localisation files.
+///
+/// The options are the language of the file, e.g. `"pt-BR"`, which selects the plural rules
+/// used to format messages.
+#[derive(Clone)]
+pub struct LocaleFormat;
+
+impl SimpleFormat<Locale> for LocaleFormat {
+    const NAME: &'static str = "FTL";
+
+    type Options = String;
+
+    fn import(&self, bytes: Vec<u8>, language: String) -> Result<Locale, Error> {
+        let s = String::from_utf8(bytes)?;
+
+        let mut locale = Locale::new(&language);
+        add_messages(&mut locale.bundle, &s)?;
+        Ok(locale)
+    }
+}
+
+/// Loads a locale from several localisation files, listed in a RON manifest.
+///
+/// The manifest is a list of FTL files, relative to the directory of the manifest:
+///
+/// ```ron,ignore
+/// // locale/en.ron
+/// ["en/ui.ftl", "en/items.ftl", "en/dialogue.ftl"]
+/// ```
+///
+/// All files are loaded from the same source as the manifest. With hot reloading enabled, the
+/// locale is reloaded when the manifest or any of the files changed.
+///
+/// Like for `LocaleFormat`, the options are the language of the files.
+#[derive(Clone)]
+pub struct LocaleManifestFormat;
+
+impl LocaleManifestFormat {
+    fn import_files(
+        name: &str,
+        source: &Arc<dyn Source>,
+        language: &str,
+        files: &mut Vec<String>,
+    ) -> Result<Locale, Error> {
+        let manifest = source
+            .load(name)
+            .with_context(|_| format_err!("Failed loading locale manifest {:?}", name))?;
+        let paths: Vec<String> = ron::de::from_bytes(&manifest)
+            .with_context(|_| format_err!("Failed parsing locale manifest {:?}", name))?;
+        files.push(name.to_owned());
+
+        let dir = match name.rfind('/') {
+            Some(i) => &name[..=i],
+            None => "",
+        };
+        let mut locale = Locale::new(language);
+        for path in paths {
+            let path = format!("{}{}", dir, path);
+            let bytes = source
+                .load(&path)
+                .with_context(|_| format_err!("Failed loading localisation file {:?}", path))?;
+            let s = String::from_utf8(bytes)?;
+            add_messages(&mut locale.bundle, &s)
+                .with_context(|_| format_err!("Failed parsing localisation file {:?}", path))?;
+            files.push(path);
+        }
+        Ok(locale)
+    }
+}
+
+impl Format<Locale> for LocaleManifestFormat {
+    const NAME: &'static str = "FTL_MANIFEST";
+
+    type Options = String;
+
+    fn import(
+        &self,
+        name: String,
+        source: Arc<dyn Source>,
+        language: String,
+        create_reload: bool,
+    ) -> Result<FormatValue<Locale>, Error> {
+        let mut files = Vec::new();
+        let data = Self::import_files(&name, &source, &language, &mut files)?;
+
+        let reload = if create_reload {
+            let files = files
+                .into_iter()
+                .map(|file| {
+                    let modified = source.modified(&file).unwrap_or(0);
+                    (file, modified)
+                })
+                .collect();
+            Some(Box::new(ManifestReload {
+                name,
+                source,
+                language,
+                files,
+            }) as Box<dyn Reload<Locale>>)
+        } else {
+            None
+        };
+
+        Ok(FormatValue { data, reload })
+    }
+}
+
+/// Reload instructions of `LocaleManifestFormat`, which reload the locale if the manifest or any
+/// of its files changed.
+#[derive(Clone)]
+struct ManifestReload {
+    name: String,
+    source: Arc<dyn Source>,
+    language: String,
+    files: Vec<(String, u64)>,
+}
+
+impl Reload<Locale> for ManifestReload {
+    fn needs_reload(&self) -> bool {
+        self.files
+            .iter()
+            .any(|(file, modified)| self.source.modified(file).unwrap_or(0) != *modified)
+    }
+
+    fn name(&self) -> String {
+        self.name.clone()
+    }
+
+    fn format(&self) -> &'static str {
+        LocaleManifestFormat::NAME
+    }
+
+    fn reload(self: Box<Self>) -> Result<FormatValue<Locale>, Error> {
+        let ManifestReload {
+            name,
+            source,
+            language,
+            ..
+        } = *self;
+        LocaleManifestFormat.import(name, source, language, true)
+    }
+}
+
+/// Adds the messages of an FTL file to the bundle, returning all errors with their line.
+fn add_messages(bundle: &mut FluentBundle<'static>, source: &str) -> Result<(), Error> {
+    bundle.add_messages(source).map_err(|errors| {
+        let errors = errors
+            .iter()
+            .map(describe_error)
+            .collect::<Vec<_>>()
+            .join("\n");
+        format_err!("Invalid localisation file:\n{}", errors)
+    })
+}
+
+fn describe_error(error: &FluentError) -> String {
+    match *error {
+        FluentError::ParserError(ref error) => match error.info {
+            Some(ref info) => {
+                // `info.line` is the line where the failing entry starts, and `info.pos` the
+                // offset of the error in that entry.
+                let entry = info.slice.get(..info.pos).unwrap_or(&info.slice);
+                let line = info.line + entry.matches('\n').count() + 1;
+                format!("line {}: {:?}", line, error.kind)
+            }
+            None => format!("{:?}", error.kind),
+        },
+        FluentError::Overriding { kind, ref id } => {
+            format!("{} `{}` is defined more than once", kind, id)
+        }
+        ref error => format!("{:?}", error),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        collections::HashMap,
+        sync::{Arc, Mutex},
+    };
+
+    use fluent::types::FluentValue;
+
+    use amethyst_assets::{Format, SimpleFormat, Source};
+    use amethyst_error::Error;
+
+    use crate::Locale;
+
+    use super::{LocaleFormat, LocaleManifestFormat};
+
+    /// A `Source` keeping its files in memory, whose clones share the files.
+    #[derive(Clone, Default)]
+    struct Files(Arc<Mutex<HashMap<String, (String, u64)>>>);
+
+    impl Files {
+        /// Sets the contents of `path`, increasing its modification time.
+        fn set(&self, path: &str, contents: &str) {
+            let mut files = self.0.lock().unwrap();
+            let modified = files
+                .get(path)
+                .map(|(_, modified)| modified + 1)
+                .unwrap_or(1);
+            files.insert(path.to_owned(), (contents.to_owned(), modified));
+        }
+    }
+
+    impl Source for Files {
+        fn modified(&self, path: &str) -> Result<u64, Error> {
+            self.0
+                .lock()
+                .unwrap()
+                .get(path)
+                .map(|(_, modified)| *modified)
+                .ok_or_else(|| Error::from_string("Not found"))
+        }
+
+        fn load(&self, path: &str) -> Result<Vec<u8>, Error> {
+            self.0
+                .lock()
+                .unwrap()
+                .get(path)
+                .map(|(contents, _)| contents.as_bytes().to_vec())
+                .ok_or_else(|| Error::from_string("Not found"))
+        }
+    }
+
+    #[test]
+    fn invalid_files_are_errors() {
+        let valid = "hello = Hello\n".as_bytes().to_vec();
+        assert!(LocaleFormat.import(valid, "en".to_owned()).is_ok());
+
+        let invalid = "hello = Hello\nhello = Hello again\n".as_bytes().to_vec();
+        let error = LocaleFormat
+            .import(invalid, "en".to_owned())
+            .err()
+            .expect("no error");
+        assert!(error.to_string().contains("`hello`"));
+    }
+
+    #[test]
+    fn syntax_errors_report_their_line() {
+        let invalid = "hello = Hello\nmulti =\n    continued\n= missing id\n"
+            .as_bytes()
+            .to_vec();
+        let error = LocaleFormat
+            .import(invalid, "en".to_owned())
+            .err()
+            .expect("no error");
+        assert!(
+            error.to_string().contains("line 4:"),
+            "unexpected error: {}",
+            error
+        );
+    }
+
+    #[test]
+    fn manifest_loads_all_files_and_reloads_on_changes() {
+        let files = Files::default();
+        files.set("locale/en.ron", r#"["en/ui.ftl", "en/items.ftl"]"#);
+        files.set("locale/en/ui.ftl", "hello = Hello\n");
+        files.set("locale/en/items.ftl", "apple = Apple\n");
+
+        let value = LocaleManifestFormat
+            .import(
+                "locale/en.ron".to_owned(),
+                Arc::new(files.clone()),
+                "en".to_owned(),
+                true,
+            )
+            .unwrap();
+        let message =
+            |locale: &Locale, id: &str| locale.bundle.format(id, None).map(|(message, _)| message);
+        assert_eq!(Some("Hello".to_owned()), message(&value.data, "hello"));
+        assert_eq!(Some("Apple".to_owned()), message(&value.data, "apple"));
+
+        let reload = value
+            .reload
+            .expect("no reload instructions with hot reloading");
+        assert!(!reload.needs_reload());
+        files.set("locale/en/items.ftl", "apple = Green apple\n");
+        assert!(reload.needs_reload());
+
+        let locale = reload.reload().unwrap().data;
+        assert_eq!(Some("Green apple".to_owned()), message(&locale, "apple"));
+        assert_eq!(Some("Hello".to_owned()), message(&locale, "hello"));
+    }
+
+    #[test]
+    fn plural_rules_follow_language() {
+        let source = "
+apples = { $count ->
+    [one] one apple
+    [few] few apples
+   *[other] many apples
+}
+";
+        let format = |language: &str, count: u32| {
+            let locale = LocaleFormat
+                .import(source.as_bytes().to_vec(), language.to_owned())
+                .unwrap();
+            assert_eq!(language, locale.language);
+            let mut args = HashMap::new();
+            args.insert("count", FluentValue::Number(count.to_string()));
+            locale.bundle.format("apples", Some(&args)).unwrap().0
+        };
+        assert_eq!("many apples", format("en", 3));
+        assert_eq!("few apples", format("pl", 3));
+    }
+}
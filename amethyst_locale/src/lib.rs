@@ -1,34 +1,35 @@
 //! # amethyst_locale
 //!
 //! Localisation binding a `Fluent` file to an Asset<Locale> via the use of amethyst_assets.
+//!
+//! The `Localization` resource holds the locales of several languages, and formats messages
+//! through a fallback chain of languages.
 
 #![warn(missing_docs, rust_2018_idioms, rust_2018_compatibility)]
 
+use std::sync::atomic::{AtomicUsize, Ordering};
+
 use fluent::bundle::FluentBundle;
 
-use amethyst_assets::{Asset, Handle, ProcessingState, SimpleFormat};
+use amethyst_assets::{Asset, Handle, ProcessingState};
 use amethyst_core::ecs::prelude::VecStorage;
 use amethyst_error::Error;
 
-/// Loads the strings from localisation files.
-#[derive(Clone)]
-pub struct LocaleFormat;
-
-impl SimpleFormat<Locale> for LocaleFormat {
-    const NAME: &'static str = "FTL";
+pub use fluent::types::FluentValue;
 
-    type Options = ();
+pub use crate::{
+    bundle::LocaleBundle,
+    formats::{LocaleFormat, LocaleManifestFormat},
+    localization::{
+        fallback_chain, normalize_language, system_language, LanguageChanged, Localization,
+    },
+    system::LocalizationSystem,
+};
 
-    fn import(&self, bytes: Vec<u8>, _: ()) -> Result<Locale, Error> {
-        let s = String::from_utf8(bytes)?;
-
-        let mut bundle = FluentBundle::new::<&'static str>(&[]);
-        bundle
-            .add_messages(&s)
-            .expect("Error creating fluent bundle!");
-        Ok(Locale { bundle })
-    }
-}
+mod bundle;
+mod formats;
+mod localization;
+mod system;
 
 impl Into<Result<ProcessingState<Locale>, Error>> for Locale {
     fn into(self) -> Result<ProcessingState<Locale>, Error> {
@@ -41,8 +42,32 @@ pub type LocaleHandle = Handle<Locale>;
 
 /// A loaded locale.
 pub struct Locale {
+    /// The language of the locale, which selects the plural rules of the bundle.
+    pub language: String,
     /// The message context.
     pub bundle: FluentBundle<'static>,
+    id: usize,
+}
+
+impl Locale {
+    /// Creates a locale without messages for `language`.
+    pub fn new(language: &str) -> Self {
+        static NEXT_ID: AtomicUsize = AtomicUsize::new(0);
+
+        Locale {
+            language: language.to_owned(),
+            bundle: FluentBundle::new(&[language]),
+            id: NEXT_ID.fetch_add(1, Ordering::Relaxed),
+        }
+    }
+
+    /// Returns an id unique to this locale.
+    ///
+    /// A hot-reloaded locale replaces the old one in the `AssetStorage` under the same handle,
+    /// but has a new id, so comparing ids tells whether messages have to be formatted again.
+    pub fn id(&self) -> usize {
+        self.id
+    }
 }
 
 impl Asset for Locale {
@@ -0,0 +1,269 @@
+use std::{collections::HashMap, env};
+
+use fluent::types::FluentValue;
+use log::{error, warn};
+
+use amethyst_assets::{AssetStorage, Format, Loader, Progress};
+
+use crate::{Locale, LocaleHandle};
+
+/// Event sent by the `LocalizationSystem` when the language of the `Localization` changed.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct LanguageChanged {
+    /// The new language.
+    pub language: String,
+}
+
+/// Resource holding the locales of all languages and the active language.
+///
+/// Messages are looked up through a fallback chain: the active language, the language without
+/// its region and variants, and finally the default language. For example with the active
+/// language `pt-BR` and the default language `en`, a message is looked up in `pt-BR`, `pt` and
+/// `en`, using the first locale which is loaded and contains the message.
+///
+/// ### Example
+///
+/// ```rust,ignore
+/// let mut localization = Localization::new("en");
+/// localization.load_locale("en", "locale/en.ftl", LocaleFormat, (), &loader, &storage);
+/// localization.load_locale("pt", "locale/pt.ftl", LocaleFormat, (), &loader, &storage);
+/// localization.use_system_language();
+/// world.add_resource(localization);
+///
+/// // later
+/// let text = localization.format(&storage, "hello", None);
+/// ```
+#[derive(Clone, Debug)]
+pub struct Localization {
+    locales: HashMap<String, LocaleHandle>,
+    default_language: String,
+    language: String,
+    chain: Vec<String>,
+    changed: bool,
+}
+
+impl Default for Localization {
+    fn default() -> Self {
+        Localization::new("en")
+    }
+}
+
+impl Localization {
+    /// Creates a localization without locales, using `default_language` as the last fallback
+    /// and as the active language.
+    pub fn new(default_language: &str) -> Self {
+        let default_language = normalize_language(default_language);
+        Localization {
+            locales: HashMap::new(),
+            chain: fallback_chain(&default_language, &default_language),
+            language: default_language.clone(),
+            default_language,
+            changed: false,
+        }
+    }
+
+    /// Adds the locale of `language`, replacing any previous locale of that language.
+    ///
+    /// The locale has to be loaded with `language` as options of its format, otherwise it uses
+    /// the wrong plural rules; `load_locale` takes care of that. Locales of another language are
+    /// logged and skipped when formatting messages.
+    pub fn add_locale(&mut self, language: &str, locale: LocaleHandle) {
+        self.locales.insert(normalize_language(language), locale);
+    }
+
+    /// Loads the locale of `language` from `name` and adds it, see `add_locale`.
+    ///
+    /// `format` is usually `LocaleFormat` or `LocaleManifestFormat`, which are given the
+    /// language as options.
+    pub fn load_locale<F, N, P>(
+        &mut self,
+        language: &str,
+        name: N,
+        format: F,
+        progress: P,
+        loader: &Loader,
+        storage: &AssetStorage<Locale>,
+    ) -> LocaleHandle
+    where
+        F: Format<Locale, Options = String>,
+        N: Into<String>,
+        P: Progress,
+    {
+        let language = normalize_language(language);
+        let handle = loader.load(name, format, language.clone(), progress, storage);
+        self.locales.insert(language, handle.clone());
+        handle
+    }
+
+    /// Returns the locale of `language`, if one was added.
+    pub fn locale(&self, language: &str) -> Option<&LocaleHandle> {
+        self.locales.get(&normalize_language(language))
+    }
+
+    /// Returns the languages which have a locale.
+    pub fn languages(&self) -> impl Iterator<Item = &str> {
+        self.locales.keys().map(String::as_str)
+    }
+
+    /// Returns the default language, which is the last fallback of every language.
+    pub fn default_language(&self) -> &str {
+        &self.default_language
+    }
+
+    /// Returns the active language.
+    pub fn language(&self) -> &str {
+        &self.language
+    }
+
+    /// Sets the active language.
+    ///
+    /// The `LocalizationSystem` sends a `LanguageChanged` event if the language differs from the
+    /// previous one.
+    pub fn set_language(&mut self, language: &str) {
+        let language = normalize_language(language);
+        if language != self.language {
+            self.chain = fallback_chain(&language, &self.default_language);
+            self.language = language;
+            self.changed = true;
+        }
+    }
+
+    /// Sets the active language to the language of the system, see `system_language`.
+    ///
+    /// Returns `false` and keeps the active language if the system language is unknown.
+    pub fn use_system_language(&mut self) -> bool {
+        match system_language() {
+            Some(language) => {
+                self.set_language(&language);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Returns the languages messages are looked up in, in order.
+    pub fn fallback_chain(&self) -> &[String] {
+        &self.chain
+    }
+
+    /// Returns the locales messages are looked up in, in the order of the fallback chain.
+    ///
+    /// Languages without a locale or with a locale which isn't loaded yet are skipped.
+    pub fn locales<'a>(
+        &'a self,
+        storage: &'a AssetStorage<Locale>,
+    ) -> impl Iterator<Item = &'a Locale> + 'a {
+        self.chain.iter().filter_map(move |language| {
+            let locale = storage.get(self.locales.get(language)?)?;
+            if normalize_language(&locale.language) == *language {
+                Some(locale)
+            } else {
+                error!(
+                    "Skipping the locale of `{}`, it was loaded for `{}`",
+                    language, locale.language
+                );
+                None
+            }
+        })
+    }
+
+    /// Returns `true` if any locale of the fallback chain contains the message `id`.
+    pub fn has_message(&self, storage: &AssetStorage<Locale>, id: &str) -> bool {
+        self.locales(storage)
+            .any(|locale| locale.bundle.has_message(id))
+    }
+
+    /// Formats the message `id` with the first locale of the fallback chain containing it.
+    ///
+    /// Errors while formatting, like missing arguments, are logged, and the message is
+    /// formatted with placeholders for the failed parts.
+    pub fn format(
+        &self,
+        storage: &AssetStorage<Locale>,
+        id: &str,
+        args: Option<&HashMap<&str, FluentValue>>,
+    ) -> Option<String> {
+        let locale = self
+            .locales(storage)
+            .find(|locale| locale.bundle.has_message(id))?;
+        let (text, errors) = locale.bundle.format(id, args)?;
+        for error in errors {
+            warn!("Error formatting message `{}`: {:?}", id, error);
+        }
+        Some(text)
+    }
+
+    /// Returns whether the language changed since the last call.
+    pub(crate) fn take_changed(&mut self) -> bool {
+        std::mem::replace(&mut self.changed, false)
+    }
+}
+
+/// Normalizes a language identifier, e.g. `pt_BR.UTF-8` into `pt-BR`.
+///
+/// The encoding and modifiers of POSIX locale names are removed, and `_` is replaced by `-`.
+pub fn normalize_language(language: &str) -> String {
+    language
+        .split(|c| c == '.' || c == '@')
+        .next()
+        .unwrap_or("")
+        .trim()
+        .replace('_', "-")
+}
+
+/// Returns the fallback chain of `language`, ending with `default_language`.
+///
+/// The subtags of the language are removed one by one, e.g. `zh-Hant-TW` falls back to
+/// `zh-Hant`, `zh` and finally `default_language`.
+pub fn fallback_chain(language: &str, default_language: &str) -> Vec<String> {
+    let mut chain = Vec::new();
+    let mut language = language;
+    while !language.is_empty() {
+        chain.push(language.to_owned());
+        language = match language.rfind('-') {
+            Some(i) => &language[..i],
+            None => "",
+        };
+    }
+    if chain.iter().all(|l| l != default_language) {
+        chain.push(default_language.to_owned());
+    }
+    chain
+}
+
+/// Returns the language of the system, read from the environment variables `LC_ALL`,
+/// `LC_MESSAGES` and `LANG`.
+///
+/// Returns `None` if none of them is set, or they select the POSIX locale. Only these POSIX
+/// variables are read, which are usually not set on Windows or for applications started from the
+/// macOS Finder; games should let players pick the language in that case.
+pub fn system_language() -> Option<String> {
+    ["LC_ALL", "LC_MESSAGES", "LANG"]
+        .iter()
+        .filter_map(|var| env::var(var).ok())
+        .map(|value| normalize_language(&value))
+        .find(|language| !language.is_empty())
+        .filter(|language| language != "C" && language != "POSIX")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{fallback_chain, normalize_language, Localization};
+
+    #[test]
+    fn fallback_chain_removes_subtags() {
+        assert_eq!(fallback_chain("pt-BR", "en"), ["pt-BR", "pt", "en"]);
+        assert_eq!(fallback_chain("en-US", "en"), ["en-US", "en"]);
+        assert_eq!(normalize_language("pt_BR.UTF-8"), "pt-BR");
+
+        let mut localization = Localization::new("en");
+        localization.set_language("zh_Hant_TW");
+        assert_eq!(
+            localization.fallback_chain(),
+            ["zh-Hant-TW", "zh-Hant", "zh", "en"]
+        );
+        assert!(localization.take_changed());
+        localization.set_language("zh-Hant-TW");
+        assert!(!localization.take_changed());
+    }
+}
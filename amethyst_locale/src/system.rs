@@ -0,0 +1,45 @@
+use amethyst_assets::AssetLanguages;
+use amethyst_core::{
+    ecs::prelude::{Read, System, Write},
+    shrev::EventChannel,
+};
+
+use crate::{LanguageChanged, Localization};
+
+/// System sending a `LanguageChanged` event when the language of the `Localization` changed.
+///
+/// It also keeps the `AssetLanguages` in sync with the fallback chain of the active language, so
+/// assets loaded with a `LocalizedFormat` switch to the variant of the new language.
+#[derive(Debug, Default)]
+pub struct LocalizationSystem {
+    synced: bool,
+}
+
+impl LocalizationSystem {
+    /// Creates a new `LocalizationSystem`.
+    pub fn new() -> Self {
+        Default::default()
+    }
+}
+
+impl<'a> System<'a> for LocalizationSystem {
+    type SystemData = (
+        Write<'a, Localization>,
+        Write<'a, EventChannel<LanguageChanged>>,
+        Read<'a, AssetLanguages>,
+    );
+
+    fn run(&mut self, (mut localization, mut events, asset_languages): Self::SystemData) {
+        let changed = localization.take_changed();
+        if changed {
+            events.single_write(LanguageChanged {
+                language: localization.language().to_owned(),
+            });
+        }
+
+        if changed || !self.synced {
+            asset_languages.set_languages(localization.fallback_chain().iter().cloned());
+            self.synced = true;
+        }
+    }
+}
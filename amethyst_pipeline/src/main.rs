@@ -0,0 +1,90 @@
+//! Imports the assets of a directory into an `ArtifactCache`, so games can load them with
+//! `Loader::load_cached` without decoding the source files.
+//!
+//! ```text
+//! amethyst_pipeline [--linear] <assets dir> [<cache dir>]
+//! ```
+//!
+//! The cache directory defaults to `<assets dir>/.artifacts`. Meshes are imported from `obj`
+//! files, textures from `png`, `jpg`, `jpeg`, `bmp` and `tga` files. Textures use
+//! `TextureMetadata::srgb()`, or `TextureMetadata::unorm()` with `--linear`; the game has to load
+//! them with the same options, otherwise the artifacts are not used.
+//!
+//! The process exits with status 1 if any file failed to import.
+
+#![warn(missing_docs, rust_2018_idioms, rust_2018_compatibility)]
+
+use std::{env, path::PathBuf, process};
+
+use amethyst_assets::{ArtifactCache, AssetPipeline};
+use amethyst_renderer::{
+    BmpFormat, JpgFormat, Mesh, ObjFormat, PngFormat, Texture, TextureMetadata, TgaFormat,
+};
+
+const USAGE: &str = "Usage: amethyst_pipeline [--linear] <assets dir> [<cache dir>]";
+
+fn main() {
+    let mut linear = false;
+    let mut paths = Vec::new();
+    for arg in env::args().skip(1) {
+        match arg.as_str() {
+            "--linear" => linear = true,
+            "-h" | "--help" => {
+                println!("{}", USAGE);
+                return;
+            }
+            _ if arg.starts_with("--") => {
+                eprintln!("Unknown option `{}`\n{}", arg, USAGE);
+                process::exit(2);
+            }
+            _ => paths.push(PathBuf::from(arg)),
+        }
+    }
+
+    let (root, cache_dir) = match paths.len() {
+        1 => (paths[0].clone(), paths[0].join(".artifacts")),
+        2 => (paths[0].clone(), paths[1].clone()),
+        _ => {
+            eprintln!("{}", USAGE);
+            process::exit(2);
+        }
+    };
+
+    let metadata = if linear {
+        TextureMetadata::unorm()
+    } else {
+        TextureMetadata::srgb()
+    };
+
+    let report = AssetPipeline::new(root, ArtifactCache::new(cache_dir))
+        .with_importer::<Mesh, _>("obj", ObjFormat, ())
+        .with_importer::<Texture, _>("png", PngFormat, metadata.clone())
+        .with_importer::<Texture, _>("jpg", JpgFormat, metadata.clone())
+        .with_importer::<Texture, _>("jpeg", JpgFormat, metadata.clone())
+        .with_importer::<Texture, _>("bmp", BmpFormat, metadata.clone())
+        .with_importer::<Texture, _>("tga", TgaFormat, metadata)
+        .run();
+
+    for name in &report.imported {
+        println!("imported    {}", name);
+    }
+    for name in &report.up_to_date {
+        println!("up to date  {}", name);
+    }
+    for (name, error) in &report.failed {
+        eprintln!("failed      {}: {}", name, error);
+        for cause in error.causes().skip(1) {
+            eprintln!("            caused by: {}", cause);
+        }
+    }
+    println!(
+        "{} imported, {} up to date, {} failed",
+        report.imported.len(),
+        report.up_to_date.len(),
+        report.failed.len(),
+    );
+
+    if !report.failed.is_empty() {
+        process::exit(1);
+    }
+}
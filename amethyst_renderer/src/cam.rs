@@ -1,6 +1,6 @@
 //! Camera type with support for perspective and orthographic projections.
 
-use amethyst_assets::PrefabData;
+use amethyst_assets::{ExtractPrefabData, PrefabData};
 use amethyst_core::{
     ecs::prelude::{Component, Entity, HashMapStorage, Write, WriteStorage},
     math::{Matrix4, Orthographic3, Perspective3, Point2, Point3},
@@ -133,6 +133,28 @@ pub enum CameraPrefab {
     Matrix(Matrix4<f32>),
 }
 
+impl CameraPrefab {
+    /// Create the prefab for a camera projection matrix.
+    ///
+    /// Matrices created by `Orthographic3` or `Perspective3` are recognized by their layout and
+    /// give the matching variant, any other matrix gives `CameraPrefab::Matrix`.
+    #[allow(clippy::float_cmp)] // matrices built by nalgebra contain exactly 1 and -1
+    pub fn from_projection(proj: Matrix4<f32>) -> Self {
+        let zero_except = |nonzero: &[(usize, usize)]| {
+            (0..4).all(|r| (0..4).all(|c| nonzero.contains(&(r, c)) || proj[(r, c)] == 0.0))
+        };
+        if proj[(3, 2)] == -1.0 && zero_except(&[(0, 0), (1, 1), (2, 2), (2, 3), (3, 2)]) {
+            CameraPrefab::Perspective(Perspective3::from_matrix_unchecked(proj))
+        } else if proj[(3, 3)] == 1.0
+            && zero_except(&[(0, 0), (1, 1), (2, 2), (0, 3), (1, 3), (2, 3), (3, 3)])
+        {
+            CameraPrefab::Orthographic(Orthographic3::from_matrix_unchecked(proj))
+        } else {
+            CameraPrefab::Matrix(proj)
+        }
+    }
+}
+
 impl<'a> PrefabData<'a> for CameraPrefab {
     type SystemData = WriteStorage<'a, Camera>;
     type Result = ();
@@ -154,6 +176,18 @@ impl<'a> PrefabData<'a> for CameraPrefab {
     }
 }
 
+impl<'a> ExtractPrefabData<'a> for CameraPrefab {
+    fn extract_from_entity(
+        entity: Entity,
+        storage: &Self::SystemData,
+        _: &[Entity],
+    ) -> Result<Option<Self>, Error> {
+        Ok(storage
+            .get(entity)
+            .map(|camera| CameraPrefab::from_projection(camera.proj)))
+    }
+}
+
 /// Active camera prefab
 pub struct ActiveCameraPrefab(usize);
 
@@ -1,6 +1,8 @@
 use std::fmt::Debug;
 
-use amethyst_assets::{Asset, AssetStorage, Loader, PrefabData, ProcessingState, SimpleFormat};
+use amethyst_assets::{
+    ArtifactFormat, Asset, AssetStorage, Loader, PrefabData, ProcessingState, SimpleFormat,
+};
 use amethyst_core::{
     ecs::prelude::{Component, Entity, Read, ReadExpect, VecStorage, WriteStorage},
     math::{Vector2, Vector3},
@@ -136,6 +138,10 @@ impl SimpleFormat<Mesh> for ObjFormat {
     }
 }
 
+impl ArtifactFormat<Mesh> for ObjFormat {
+    const VERSION: u32 = 1;
+}
+
 fn convert(
     object: &Object,
     vi: VertexIndex,
@@ -5,9 +5,9 @@ pub use self::{mesh::*, mtl::*, texture::*};
 
 use serde::{de::DeserializeOwned, Deserialize, Serialize};
 
-use amethyst_assets::{AssetPrefab, Format, PrefabData, ProgressCounter};
+use amethyst_assets::{AssetPrefab, ExtractPrefabData, Format, PrefabData, ProgressCounter};
 use amethyst_core::ecs::prelude::Entity;
-use amethyst_error::Error;
+use amethyst_error::{format_err, Error};
 
 use crate::{shape::InternalShape, Mesh, ShapePrefab, Texture};
 
@@ -84,7 +84,7 @@ where
                 m.add_to_entity(entity, &mut system_data.0, entities, children)?;
             }
             MeshPrefab::Shape(ref s) => {
-                s.add_to_entity(entity, &mut system_data.0, entities, children)?;
+                s.insert_handle(entity, &mut (system_data.0).1)?;
             }
         }
         self.material
@@ -99,7 +99,11 @@ where
     ) -> Result<bool, Error> {
         let load_mesh = match self.mesh {
             MeshPrefab::Asset(ref mut m) => m.load_sub_assets(progress, &mut system_data.0)?,
-            MeshPrefab::Shape(ref mut s) => s.load_sub_assets(progress, &mut system_data.0)?,
+            MeshPrefab::Shape(ref mut s) => {
+                let (ref loader, _, ref mesh_storage, _) = system_data.0;
+                s.upload_shape(progress, loader, mesh_storage);
+                true
+            }
         };
 
         let load_material = self
@@ -109,3 +113,33 @@ where
         Ok(load_mesh || load_material)
     }
 }
+
+impl<'a, V, M, T> ExtractPrefabData<'a> for GraphicsPrefab<V, M, T>
+where
+    M: Format<Mesh> + Clone,
+    M::Options: Clone + DeserializeOwned + Serialize,
+    T: Format<Texture, Options = TextureMetadata> + Sync + Clone,
+    V: From<InternalShape> + Into<MeshData>,
+{
+    /// The mesh is always extracted as `MeshPrefab::Asset`, so it must have been loaded from a
+    /// file with format `M`, meshes generated from a `ShapePrefab` can't be extracted.
+    fn extract_from_entity(
+        entity: Entity,
+        system_data: &Self::SystemData,
+        entities: &[Entity],
+    ) -> Result<Option<Self>, Error> {
+        let mesh = AssetPrefab::<Mesh, M>::extract_from_entity(entity, &system_data.0, entities)?;
+        let material = MaterialPrefab::<T>::extract_from_entity(entity, &system_data.1, entities)?;
+        match (mesh, material) {
+            (Some(mesh), Some(material)) => Ok(Some(GraphicsPrefab {
+                mesh: MeshPrefab::Asset(mesh),
+                material,
+            })),
+            (None, None) => Ok(None),
+            _ => Err(format_err!(
+                "Can't extract graphics from entity {:?}, it needs both a mesh and a material",
+                entity
+            )),
+        }
+    }
+}
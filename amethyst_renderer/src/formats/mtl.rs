@@ -1,4 +1,4 @@
-use amethyst_assets::{Format, Handle, PrefabData, ProgressCounter};
+use amethyst_assets::{ExtractPrefabData, Format, Handle, PrefabData, ProgressCounter};
 use amethyst_core::ecs::prelude::{Entity, ReadExpect, WriteStorage};
 use amethyst_error::Error;
 
@@ -192,3 +192,60 @@ where
         Ok(ret)
     }
 }
+
+fn extract_handle<F>(
+    handle: &Handle<Texture>,
+    tp_data: &<TexturePrefab<F> as PrefabData<'_>>::SystemData,
+    def: &Handle<Texture>,
+) -> Result<Option<TexturePrefab<F>>, Error>
+where
+    F: Format<Texture, Options = TextureMetadata> + Sync + Clone,
+{
+    if handle == def {
+        Ok(None)
+    } else {
+        TexturePrefab::<F>::from_handle(handle, tp_data).map(Some)
+    }
+}
+
+impl<'a, F> ExtractPrefabData<'a> for MaterialPrefab<F>
+where
+    F: Format<Texture, Options = TextureMetadata> + Sync + Clone,
+{
+    /// Textures that are the `MaterialDefaults` textures are extracted as `None`, all other
+    /// textures must have been loaded from a file by a `TexturePrefab` with format `F`.
+    fn extract_from_entity(
+        entity: Entity,
+        system_data: &Self::SystemData,
+        _: &[Entity],
+    ) -> Result<Option<Self>, Error> {
+        let (ref material, ref mat_default, ref tp_data, ref transparent) = *system_data;
+        let mtl = match material.get(entity) {
+            Some(mtl) => mtl,
+            None => return Ok(None),
+        };
+        let def = &mat_default.0;
+        Ok(Some(MaterialPrefab {
+            albedo: extract_handle(&mtl.albedo, tp_data, &def.albedo)?,
+            albedo_offset: mtl.albedo_offset.clone(),
+            emission: extract_handle(&mtl.emission, tp_data, &def.emission)?,
+            emission_offset: mtl.emission_offset.clone(),
+            normal: extract_handle(&mtl.normal, tp_data, &def.normal)?,
+            normal_offset: mtl.normal_offset.clone(),
+            metallic: extract_handle(&mtl.metallic, tp_data, &def.metallic)?,
+            metallic_offset: mtl.metallic_offset.clone(),
+            roughness: extract_handle(&mtl.roughness, tp_data, &def.roughness)?,
+            roughness_offset: mtl.roughness_offset.clone(),
+            ambient_occlusion: extract_handle(
+                &mtl.ambient_occlusion,
+                tp_data,
+                &def.ambient_occlusion,
+            )?,
+            ambient_occlusion_offset: mtl.ambient_occlusion_offset.clone(),
+            caveat: extract_handle(&mtl.caveat, tp_data, &def.caveat)?,
+            caveat_offset: mtl.caveat_offset.clone(),
+            transparent: transparent.contains(entity),
+            alpha_cutoff: mtl.alpha_cutoff,
+        }))
+    }
+}
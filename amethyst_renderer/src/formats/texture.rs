@@ -7,8 +7,8 @@ use image::{DynamicImage, ImageFormat, RgbaImage};
 use serde::{Deserialize, Serialize};
 
 use amethyst_assets::{
-    AssetStorage, Format, Handle, Loader, PrefabData, ProcessingState, ProgressCounter,
-    SimpleFormat,
+    ArtifactFormat, AssetPrefab, AssetStorage, Format, Handle, Loader, PrefabData, ProcessingState,
+    ProgressCounter, SimpleFormat, Uuid,
 };
 use amethyst_core::ecs::prelude::{Entity, Read, ReadExpect};
 use amethyst_error::{Error, ResultExt};
@@ -178,6 +178,22 @@ impl TextureData {
     pub fn color(value: [f32; 4]) -> Self {
         TextureData::Rgba(value, TextureMetadata::srgb())
     }
+
+    /// Converts decoded image data into raw `U8` data, which unlike `Image` can be serialized.
+    /// Other variants, and images too large for a texture, are returned unchanged.
+    pub fn into_raw(self) -> Self {
+        match self {
+            TextureData::Image(image, options) => {
+                let (w, h) = image.rgba.dimensions();
+                if w > u32::from(u16::max_value()) || h > u32::from(u16::max_value()) {
+                    return TextureData::Image(image, options);
+                }
+                let options = options.with_size(w as u16, h as u16);
+                TextureData::U8(image.rgba.into_raw(), options)
+            }
+            other => other,
+        }
+    }
 }
 
 impl<'a> PrefabData<'a> for TextureData {
@@ -215,11 +231,37 @@ where
     /// Load file with format
     File(String, F, TextureMetadata),
 
+    /// Load the file with the given id in the `AssetManifest` with format
+    Id(Uuid, F, TextureMetadata),
+
     /// Clone handle only
     #[serde(skip)]
     Handle(Handle<Texture>),
 }
 
+impl<F> TexturePrefab<F>
+where
+    F: Format<Texture, Options = TextureMetadata> + Clone + Sync,
+{
+    /// Create the prefab for a texture `handle` loaded by a `TexturePrefab`, for extracting
+    /// prefab data that holds texture handles, e.g. `MaterialPrefab`.
+    ///
+    /// Fails if the texture was not loaded from a file with format `F`, or if the texture storage
+    /// doesn't record formats, see `AssetStorage::record_formats`.
+    pub fn from_handle<'a>(
+        handle: &Handle<Texture>,
+        system_data: &<Self as PrefabData<'a>>::SystemData,
+    ) -> Result<Self, Error> {
+        let prefab =
+            AssetPrefab::<Texture, F>::from_handle(handle, &system_data.0, &system_data.1)?;
+        Ok(match prefab {
+            AssetPrefab::File(name, format, options) => TexturePrefab::File(name, format, options),
+            AssetPrefab::Id(id, format, options) => TexturePrefab::Id(id, format, options),
+            AssetPrefab::Handle(handle) => TexturePrefab::Handle(handle),
+        })
+    }
+}
+
 impl<'a, F> PrefabData<'a> for TexturePrefab<F>
 where
     F: Format<Texture, Options = TextureMetadata> + Clone + Sync,
@@ -242,7 +284,7 @@ where
                     .load_from_data(data.clone(), (), &system_data.1)
             }
 
-            TexturePrefab::File(..) => unreachable!(),
+            TexturePrefab::File(..) | TexturePrefab::Id(..) => unreachable!(),
 
             TexturePrefab::Handle(ref handle) => handle.clone(),
         };
@@ -269,9 +311,24 @@ where
                 &system_data.1,
             )),
 
+            TexturePrefab::Id(ref id, ref format, ref options) => Some(system_data.0.load_by_id(
+                id,
+                format.clone(),
+                options.clone(),
+                progress,
+                &system_data.1,
+            )),
+
             TexturePrefab::Handle(_) => None,
         };
         if let Some(handle) = handle {
+            match *self {
+                TexturePrefab::File(_, ref format, ref options)
+                | TexturePrefab::Id(_, ref format, ref options) => {
+                    system_data.1.record_format(&handle, format, options);
+                }
+                _ => {}
+            }
             *self = TexturePrefab::Handle(handle);
             Ok(true)
         } else {
@@ -521,6 +578,22 @@ impl SimpleFormat<Texture> for TextureFormat {
     }
 }
 
+macro_rules! impl_artifact_format {
+    ($($format:ty),*) => {
+        $(
+            impl ArtifactFormat<Texture> for $format {
+                const VERSION: u32 = 1;
+
+                fn prepare_artifact(data: TextureData) -> TextureData {
+                    data.into_raw()
+                }
+            }
+        )*
+    };
+}
+
+impl_artifact_format!(JpgFormat, PngFormat, BmpFormat, TgaFormat, TextureFormat);
+
 mod serde_helper {
     use crate::tex::{FilterMethod, WrapMode};
 
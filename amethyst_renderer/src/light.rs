@@ -2,7 +2,7 @@
 //!
 //! TODO: Remove redundant padding once `#[repr(align(...))]` stabilizes.
 
-use amethyst_assets::{PrefabData, ProgressCounter};
+use amethyst_assets::{ExtractPrefabData, PrefabData, ProgressCounter};
 use amethyst_core::ecs::prelude::{Component, DenseVecStorage, Entity, WriteStorage};
 use amethyst_derive::PrefabData;
 use amethyst_error::Error;
@@ -29,6 +29,16 @@ pub enum Light {
     Sun(SunLight),
 }
 
+impl<'a> ExtractPrefabData<'a> for Light {
+    fn extract_from_entity(
+        entity: Entity,
+        storage: &Self::SystemData,
+        _: &[Entity],
+    ) -> Result<Option<Self>, Error> {
+        Ok(storage.get(entity).cloned())
+    }
+}
+
 /// A directional light source.
 #[repr(C)]
 #[derive(Clone, ConstantBuffer, Debug, Deserialize, PartialEq, Serialize)]
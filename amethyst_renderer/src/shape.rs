@@ -42,6 +42,36 @@ pub struct ShapePrefab<V> {
     _m: PhantomData<V>,
 }
 
+impl<V> ShapePrefab<V>
+where
+    V: From<InternalShape> + Into<MeshData>,
+{
+    pub(crate) fn insert_handle(
+        &self,
+        entity: Entity,
+        meshes: &mut WriteStorage<'_, Handle<Mesh>>,
+    ) -> Result<(), Error> {
+        let self_handle = self.handle.as_ref().expect(
+            "`ShapePrefab::load_sub_assets` was not called before `ShapePrefab::add_to_entity`",
+        );
+        meshes.insert(entity, self_handle.clone()).map(|_| ())?;
+        Ok(())
+    }
+
+    pub(crate) fn upload_shape(
+        &mut self,
+        progress: &mut ProgressCounter,
+        loader: &Loader,
+        mesh_storage: &AssetStorage<Mesh>,
+    ) {
+        self.handle = Some(loader.load_from_data(
+            self.shape.generate::<V>(self.shape_scale),
+            progress,
+            mesh_storage,
+        ));
+    }
+}
+
 impl<'a, V> PrefabData<'a> for ShapePrefab<V>
 where
     V: From<InternalShape> + Into<MeshData>,
@@ -60,12 +90,7 @@ where
         _: &[Entity],
         _: &[Entity],
     ) -> Result<(), Error> {
-        let (_, ref mut meshes, _) = system_data;
-        let self_handle = self.handle.as_ref().expect(
-            "`ShapePrefab::load_sub_assets` was not called before `ShapePrefab::add_to_entity`",
-        );
-        meshes.insert(entity, self_handle.clone()).map(|_| ())?;
-        Ok(())
+        self.insert_handle(entity, &mut system_data.1)
     }
 
     fn load_sub_assets(
@@ -74,11 +99,7 @@ where
         system_data: &mut <Self as PrefabData<'_>>::SystemData,
     ) -> Result<bool, Error> {
         let (loader, _, mesh_storage) = system_data;
-        self.handle = Some(loader.load_from_data(
-            self.shape.generate::<V>(self.shape_scale),
-            progress,
-            &mesh_storage,
-        ));
+        self.upload_shape(progress, loader, mesh_storage);
         Ok(true)
     }
 }
@@ -110,6 +110,13 @@ where
             &["ui_sound_system"],
         );
 
+        #[cfg(feature = "locale")]
+        builder.add(
+            crate::UiLocalizedTextSystem::new(),
+            "ui_localized_text_system",
+            &[],
+        );
+
         // Required for text editing. You want the cursor image to blink.
         builder.add(BlinkSystem, "blink_system", &[]);
 
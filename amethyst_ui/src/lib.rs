@@ -32,6 +32,11 @@ pub use self::{
     widgets::{Widget, WidgetId, Widgets},
 };
 
+pub use self::localized_text::{LocalizedArg, UiLocalizedText};
+
+#[cfg(feature = "locale")]
+pub use self::localized_text::UiLocalizedTextSystem;
+
 pub(crate) use amethyst_core::ecs::prelude::Entity;
 pub(crate) use paste;
 
@@ -43,6 +48,7 @@ mod font;
 mod format;
 mod label;
 mod layout;
+mod localized_text;
 mod pass;
 mod prefab;
 mod resize;
@@ -0,0 +1,344 @@
+use std::collections::BTreeMap;
+#[cfg(feature = "locale")]
+use std::collections::HashMap;
+
+use serde::{Deserialize, Serialize};
+
+use amethyst_core::ecs::prelude::{Component, DenseVecStorage};
+
+#[cfg(feature = "locale")]
+use amethyst_assets::AssetStorage;
+#[cfg(feature = "locale")]
+use amethyst_core::{
+    ecs::{prelude::Join, Read, Resources, System, SystemData, WriteStorage},
+    shrev::{EventChannel, ReaderId},
+};
+#[cfg(feature = "locale")]
+use amethyst_locale::{FluentValue, LanguageChanged, Locale, Localization};
+
+#[cfg(feature = "locale")]
+use crate::UiText;
+
+/// Argument of a localized message.
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+#[serde(untagged)]
+pub enum LocalizedArg {
+    /// A number, formatted with the plural rules of the language.
+    Number(f64),
+    /// A string.
+    String(String),
+}
+
+impl From<f64> for LocalizedArg {
+    fn from(number: f64) -> Self {
+        LocalizedArg::Number(number)
+    }
+}
+
+impl From<i32> for LocalizedArg {
+    fn from(number: i32) -> Self {
+        LocalizedArg::Number(f64::from(number))
+    }
+}
+
+impl From<String> for LocalizedArg {
+    fn from(string: String) -> Self {
+        LocalizedArg::String(string)
+    }
+}
+
+impl<'a> From<&'a str> for LocalizedArg {
+    fn from(string: &'a str) -> Self {
+        LocalizedArg::String(string.to_owned())
+    }
+}
+
+/// Component displaying a localized message in the `UiText` of its entity.
+///
+/// The `UiLocalizedTextSystem` formats the message from the `Localization` into `UiText::text`
+/// when the component is added, when its message or arguments change, when the language
+/// changes and when a locale is loaded or hot-reloaded. The system requires the `locale`
+/// feature; without it, the component is only stored.
+///
+/// In a UI prefab, it is added with the `localized` field of the text:
+///
+/// ```ron,ignore
+/// text: (
+///     text: "",
+///     font_size: 25.,
+///     color: (1., 1., 1., 1.),
+///     localized: Some((id: "greeting", args: {"name": "Ferris"})),
+/// ),
+/// ```
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+pub struct UiLocalizedText {
+    id: String,
+    #[serde(default)]
+    args: BTreeMap<String, LocalizedArg>,
+    #[serde(skip)]
+    #[cfg_attr(not(feature = "locale"), allow(dead_code))]
+    rendered: bool,
+}
+
+impl UiLocalizedText {
+    /// Creates a component displaying the message `id` without arguments.
+    pub fn new<S: Into<String>>(id: S) -> Self {
+        UiLocalizedText {
+            id: id.into(),
+            args: BTreeMap::new(),
+            rendered: false,
+        }
+    }
+
+    /// Adds the argument `name` to the message.
+    pub fn with_arg<S, A>(mut self, name: S, value: A) -> Self
+    where
+        S: Into<String>,
+        A: Into<LocalizedArg>,
+    {
+        self.set_arg(name, value);
+        self
+    }
+
+    /// Returns the id of the message.
+    pub fn id(&self) -> &str {
+        &self.id
+    }
+
+    /// Sets the id of the message.
+    pub fn set_id<S: Into<String>>(&mut self, id: S) {
+        let id = id.into();
+        if id != self.id {
+            self.id = id;
+            self.rendered = false;
+        }
+    }
+
+    /// Returns the argument `name` of the message.
+    pub fn arg(&self, name: &str) -> Option<&LocalizedArg> {
+        self.args.get(name)
+    }
+
+    /// Sets the argument `name` of the message.
+    pub fn set_arg<S, A>(&mut self, name: S, value: A)
+    where
+        S: Into<String>,
+        A: Into<LocalizedArg>,
+    {
+        let value = value.into();
+        let name = name.into();
+        if self.args.get(&name) != Some(&value) {
+            self.args.insert(name, value);
+            self.rendered = false;
+        }
+    }
+
+    /// Removes the argument `name` of the message.
+    pub fn remove_arg(&mut self, name: &str) -> Option<LocalizedArg> {
+        let removed = self.args.remove(name);
+        if removed.is_some() {
+            self.rendered = false;
+        }
+        removed
+    }
+
+    #[cfg(feature = "locale")]
+    fn fluent_args(&self) -> HashMap<&str, FluentValue> {
+        self.args
+            .iter()
+            .map(|(name, value)| {
+                let value = match *value {
+                    LocalizedArg::Number(number) => FluentValue::Number(number.to_string()),
+                    LocalizedArg::String(ref string) => FluentValue::String(string.clone()),
+                };
+                (name.as_str(), value)
+            })
+            .collect()
+    }
+}
+
+impl Component for UiLocalizedText {
+    type Storage = DenseVecStorage<Self>;
+}
+
+/// System formatting `UiLocalizedText`s into the `UiText` of their entity.
+///
+/// Messages are formatted again as soon as a locale of the fallback chain is loaded or
+/// hot-reloaded.
+#[cfg(feature = "locale")]
+#[derive(Debug, Default)]
+pub struct UiLocalizedTextSystem {
+    language_reader: Option<ReaderId<LanguageChanged>>,
+    /// The ids of the locales of the fallback chain when messages were last formatted.
+    locale_ids: Vec<usize>,
+}
+
+#[cfg(feature = "locale")]
+impl UiLocalizedTextSystem {
+    /// Creates a new `UiLocalizedTextSystem`.
+    pub fn new() -> Self {
+        Default::default()
+    }
+}
+
+#[cfg(feature = "locale")]
+impl<'a> System<'a> for UiLocalizedTextSystem {
+    type SystemData = (
+        Read<'a, Localization>,
+        Read<'a, AssetStorage<Locale>>,
+        Read<'a, EventChannel<LanguageChanged>>,
+        WriteStorage<'a, UiLocalizedText>,
+        WriteStorage<'a, UiText>,
+    );
+
+    fn setup(&mut self, res: &mut Resources) {
+        Self::SystemData::setup(res);
+        self.language_reader = Some(
+            res.fetch_mut::<EventChannel<LanguageChanged>>()
+                .register_reader(),
+        );
+    }
+
+    fn run(
+        &mut self,
+        (localization, locales, language_events, mut localized_texts, mut texts): Self::SystemData,
+    ) {
+        let language_reader = self.language_reader.as_mut().expect(
+            "`UiLocalizedTextSystem::setup` was not called before `UiLocalizedTextSystem::run`",
+        );
+        let language_changed = language_events.read(language_reader).count() > 0;
+
+        let locale_ids = localization
+            .fallback_chain()
+            .iter()
+            .filter_map(|language| locales.get(localization.locale(language)?))
+            .map(Locale::id)
+            .collect::<Vec<_>>();
+        let locales_changed = locale_ids != self.locale_ids;
+        self.locale_ids = locale_ids;
+
+        for (localized, text) in (&mut localized_texts, &mut texts).join() {
+            if localized.rendered && !language_changed && !locales_changed {
+                continue;
+            }
+            let formatted = {
+                let args = localized.fluent_args();
+                localization.format(&locales, &localized.id, Some(&args))
+            };
+            match formatted {
+                Some(formatted) => {
+                    text.text = formatted;
+                    localized.rendered = true;
+                }
+                // Try again once the locale is loaded.
+                None => localized.rendered = false,
+            }
+        }
+    }
+}
+
+#[cfg(all(test, feature = "locale"))]
+mod tests {
+    use std::sync::Arc;
+
+    use rayon::ThreadPoolBuilder;
+
+    use amethyst_assets::{AssetStorage, Loader, SimpleFormat};
+    use amethyst_core::ecs::prelude::{Builder, RunNow, System, World};
+    use amethyst_locale::{Locale, LocaleFormat, Localization, LocalizationSystem};
+
+    use crate::{FontAsset, TtfFormat, UiText};
+
+    use super::{UiLocalizedText, UiLocalizedTextSystem};
+
+    const EN: &str = "
+apples = { $count ->
+    [one] one apple
+   *[other] { $count } apples
+}
+";
+
+    const FR: &str = "
+apples = { $count ->
+    [one] { $count } pomme
+   *[other] { $count } pommes
+}
+";
+
+    fn locale(language: &str, source: &str) -> Locale {
+        LocaleFormat
+            .import(source.as_bytes().to_vec(), language.to_owned())
+            .unwrap()
+    }
+
+    #[test]
+    fn formats_on_argument_language_and_locale_changes() {
+        let pool = Arc::new(ThreadPoolBuilder::new().num_threads(1).build().unwrap());
+        let loader = Loader::new(".", pool.clone());
+        let mut world = World::new();
+        let mut localization_system = LocalizationSystem::new();
+        let mut system = UiLocalizedTextSystem::new();
+        System::setup(&mut localization_system, &mut world.res);
+        System::setup(&mut system, &mut world.res);
+
+        let fr = {
+            let mut storage = world.write_resource::<AssetStorage<Locale>>();
+            let en = loader.load_from_data(locale("en", EN), (), &storage);
+            let fr = loader.load_from_data(locale("fr", FR), (), &storage);
+            storage.process(Into::into, 0, &pool, None);
+
+            let mut localization = world.write_resource::<Localization>();
+            localization.add_locale("en", en);
+            localization.add_locale("fr", fr.clone());
+            fr
+        };
+
+        let font = {
+            let data = TtfFormat
+                .import(include_bytes!("font/square.ttf").to_vec(), ())
+                .unwrap();
+            loader.load_from_data(data, (), &AssetStorage::<FontAsset>::new())
+        };
+        let entity = world
+            .create_entity()
+            .with(UiText::new(font, String::new(), [1.; 4], 10.))
+            .with(UiLocalizedText::new("apples").with_arg("count", 1))
+            .build();
+
+        let mut run = |world: &mut World| {
+            localization_system.run_now(&world.res);
+            system.run_now(&world.res);
+            world.maintain();
+            world
+                .read_storage::<UiText>()
+                .get(entity)
+                .unwrap()
+                .text
+                .clone()
+        };
+        assert_eq!("one apple", run(&mut world));
+
+        world
+            .write_storage::<UiText>()
+            .get_mut(entity)
+            .unwrap()
+            .text = "unchanged".to_owned();
+        assert_eq!("unchanged", run(&mut world));
+
+        world
+            .write_storage::<UiLocalizedText>()
+            .get_mut(entity)
+            .unwrap()
+            .set_arg("count", 2);
+        assert_eq!("2 apples", run(&mut world));
+
+        world.write_resource::<Localization>().set_language("fr");
+        assert_eq!("2 pommes", run(&mut world));
+
+        *world
+            .write_resource::<AssetStorage<Locale>>()
+            .get_mut(&fr)
+            .unwrap() = locale("fr", "apples = { $count } pommes vertes\n");
+        assert_eq!("2 pommes vertes", run(&mut world));
+    }
+}
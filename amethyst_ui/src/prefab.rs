@@ -19,7 +19,7 @@ use serde::{Deserialize, Serialize};
 use crate::{
     get_default_font, Anchor, FontAsset, FontFormat, Interactable, LineMode, Selectable, Stretch,
     TextEditing, UiButton, UiButtonAction, UiButtonActionRetrigger, UiButtonActionType,
-    UiPlaySoundAction, UiSoundRetrigger, UiText, UiTransform, WidgetId, Widgets,
+    UiLocalizedText, UiPlaySoundAction, UiSoundRetrigger, UiText, UiTransform, WidgetId, Widgets,
 };
 
 /// Loadable `UiTransform` data.
@@ -209,6 +209,10 @@ where
     /// Optionally make the text editable
     #[serde(default)]
     pub editable: Option<TextEditingPrefab>,
+    /// Optionally display a localized message instead of `text`, which requires the `locale`
+    /// feature
+    #[serde(default)]
+    pub localized: Option<UiLocalizedText>,
 }
 
 /// Loadable `TextEditing` data
@@ -244,6 +248,7 @@ where
         WriteStorage<'a, UiText>,
         WriteStorage<'a, TextEditing>,
         <AssetPrefab<FontAsset, F> as PrefabData<'a>>::SystemData,
+        WriteStorage<'a, UiLocalizedText>,
     );
     type Result = ();
 
@@ -254,7 +259,8 @@ where
         _: &[Entity],
         _: &[Entity],
     ) -> Result<(), Error> {
-        let (ref mut texts, ref mut editables, ref mut fonts) = system_data;
+        let (ref mut texts, ref mut editables, ref mut fonts, ref mut localized_texts) =
+            system_data;
         let font_handle = self
             .font
             .as_ref()
@@ -283,6 +289,9 @@ where
                 ),
             )?;
         }
+        if let Some(ref localized) = self.localized {
+            localized_texts.insert(entity, localized.clone())?;
+        }
         Ok(())
     }
 
@@ -291,7 +300,7 @@ where
         progress: &mut ProgressCounter,
         system_data: &mut Self::SystemData,
     ) -> Result<bool, Error> {
-        let (_, _, ref mut fonts) = system_data;
+        let (_, _, ref mut fonts, _) = system_data;
 
         self.font
             .get_or_insert_with(|| {
@@ -842,6 +851,7 @@ fn walk_ui_tree<A, I, F, C, W>(
                 line_mode: None,
                 text: button.text.clone(),
                 font_size: button.font_size,
+                localized: None,
             };
 
             prefab
@@ -34,7 +34,7 @@ impl SimpleState for Example {
                 loader.load(
                     "locale/locale_en.ftl",
                     LocaleFormat,
-                    (),
+                    "en".to_owned(),
                     &mut progress_counter,
                     &storage,
                 )
@@ -45,7 +45,7 @@ impl SimpleState for Example {
                 loader.load(
                     "locale/locale_fr.ftl",
                     LocaleFormat,
-                    (),
+                    "fr".to_owned(),
                     &mut progress_counter,
                     &storage,
                 )
@@ -12,7 +12,7 @@ use winit::Event;
 use thread_profiler::{profile_scope, register_thread_with_profiler, write_profile};
 
 use crate::{
-    assets::{Loader, Source},
+    assets::{LoadDiagnostics, Loader, Source},
     callback_queue::CallbackQueue,
     core::{
         frame_limiter::{FrameLimiter, FrameRateLimitConfig, FrameRateLimitStrategy},
@@ -25,6 +25,7 @@ use crate::{
         prelude::{Component, Read, World, Write},
     },
     error::Error,
+    error_handler::{self, ErrorHandler},
     game_data::DataInit,
     state::{State, StateData, StateMachine, TransEvent},
     state_event::{StateEvent, StateEventReader},
@@ -62,6 +63,8 @@ where
     states: StateMachine<'a, T, E>,
     ignore_window_close: bool,
     data: T,
+    #[derivative(Debug = "ignore")]
+    error_handler: Option<Box<dyn ErrorHandler<T, E>>>,
 }
 
 /// An Application is the root object of the game engine. It binds the OS
@@ -231,6 +234,7 @@ where
                 let mut time = self.world.write_resource::<Time>();
                 time.increment_frame_number();
                 time.set_delta_time(elapsed);
+                crate::logger::set_frame_number(time.frame_number());
             }
             let mut stopwatch = self.world.write_resource::<Stopwatch>();
             stopwatch.stop();
@@ -361,10 +365,28 @@ where
         profile_scope!("maintain");
         self.world.maintain();
 
-        // TODO: replace this with a more customizable method.
-        // TODO: effectively, the user should have more control over error handling here
-        // TODO: because right now the app will just exit in case of an error.
-        self.world.write_resource::<Errors>().print_and_exit();
+        self.handle_errors();
+    }
+
+    /// Passes the errors reported by systems to the error handler, or prints them and exits if
+    /// there is none.
+    fn handle_errors(&mut self) {
+        let handler = match self.error_handler {
+            Some(ref mut handler) => handler,
+            None => {
+                self.world.write_resource::<Errors>().print_and_exit();
+                return;
+            }
+        };
+
+        let errors = self.world.write_resource::<Errors>().collect();
+        for reported in errors {
+            let error = error_handler::from_reported(reported);
+            let trans =
+                handler.handle_error(error, StateData::new(&mut self.world, &mut self.data));
+            self.states
+                .transition(trans, StateData::new(&mut self.world, &mut self.data));
+        }
     }
 
     /// Cleans up after the quit signal is received.
@@ -397,6 +419,7 @@ pub struct ApplicationBuilder<S, T, E, R> {
     /// Used by bundles to access the world directly
     pub world: World,
     ignore_window_close: bool,
+    error_handler: Option<Box<dyn ErrorHandler<T, E>>>,
     phantom: PhantomData<(T, E, R)>,
 }
 
@@ -509,6 +532,7 @@ where
             initial_state,
             world,
             ignore_window_close: false,
+            error_handler: None,
             phantom: PhantomData,
         })
     }
@@ -737,6 +761,47 @@ where
         self
     }
 
+    /// Records the timing of every asset loaded from now on in a `LoadDiagnostics` resource.
+    ///
+    /// The timings are kept for the whole run of the application, so this is meant for profiling
+    /// loading times, not for release builds.
+    ///
+    /// # Returns
+    ///
+    /// This function returns the ApplicationBuilder after modifying it.
+    ///
+    /// # Examples
+    ///
+    /// ~~~no_run
+    /// use amethyst::prelude::*;
+    /// use amethyst::assets::LoadDiagnostics;
+    ///
+    /// let mut game = Application::build("assets/", LoadingState)
+    ///     .expect("Failed to initialize")
+    ///     .with_load_diagnostics()
+    ///     .build(GameDataBuilder::default())
+    ///     .expect("Failed to build game")
+    ///     .run();
+    ///
+    /// struct LoadingState;
+    /// impl SimpleState for LoadingState {
+    ///     fn on_stop(&mut self, data: StateData<'_, GameData<'_, '_>>) {
+    ///         let diagnostics = data.world.read_resource::<LoadDiagnostics>();
+    ///         diagnostics
+    ///             .write_report("load_report.txt")
+    ///             .expect("Failed to write report");
+    ///     }
+    /// }
+    /// ~~~
+    pub fn with_load_diagnostics(mut self) -> Self {
+        let diagnostics = LoadDiagnostics::new();
+        self.world
+            .write_resource::<Loader>()
+            .set_diagnostics(diagnostics.clone());
+        self.world.add_resource(diagnostics);
+        self
+    }
+
     /// Sets the maximum frames per second of this game.
     ///
     /// # Parameters
@@ -797,6 +862,18 @@ where
         self
     }
 
+    /// Sets the handler deciding what happens when systems report errors to the `Errors`
+    /// resource.
+    ///
+    /// By default, the errors are printed and the process exits. See `ErrorHandler`.
+    pub fn with_error_handler<H>(mut self, handler: H) -> Self
+    where
+        H: ErrorHandler<T, E> + 'static,
+    {
+        self.error_handler = Some(Box::new(handler));
+        self
+    }
+
     /// Build an `Application` object using the `ApplicationBuilder` as configured.
     ///
     /// # Returns
@@ -850,6 +927,7 @@ where
             events: Vec::new(),
             ignore_window_close: self.ignore_window_close,
             data,
+            error_handler: self.error_handler,
             event_reader_id,
             trans_reader_id,
         })
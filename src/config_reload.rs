@@ -0,0 +1,230 @@
+//! Config resources which are reloaded when their file changes.
+
+use std::{
+    fs,
+    marker::PhantomData,
+    path::{Path, PathBuf},
+    time::{Duration, Instant, SystemTime},
+};
+
+use log::{error, info};
+
+use crate::{
+    config::Config,
+    ecs::prelude::{Resources, System, SystemData, Write},
+    error::Error,
+    shrev::EventChannel,
+};
+
+/// Event sent by a `ConfigReloadSystem<T>` after it replaced the resource `T`.
+///
+/// Systems depending on the config can register a reader on `EventChannel<ConfigChanged<T>>` to
+/// react to the new values, e.g. to resize the window.
+#[derive(Debug)]
+pub struct ConfigChanged<T> {
+    /// The file the config was reloaded from.
+    pub path: PathBuf,
+    marker: PhantomData<fn() -> T>,
+}
+
+impl<T> Clone for ConfigChanged<T> {
+    fn clone(&self) -> Self {
+        ConfigChanged {
+            path: self.path.clone(),
+            marker: PhantomData,
+        }
+    }
+}
+
+type Validator<T> = Box<dyn Fn(&T) -> Result<(), Error> + Send + Sync>;
+
+/// System keeping the config resource `T` in sync with a file.
+///
+/// The resource is loaded from the file when the system is set up, unless it was already added
+/// to the world, e.g. with `ApplicationBuilder::with_resource`. If that fails, the error is logged
+/// and the default is used. Afterwards the system checks the modification time of the file periodically,
+/// and replaces the resource when the file changed. If the new file can't be loaded or is
+/// rejected by the validator, the error is logged and the previous value is kept.
+///
+/// After replacing the resource a `ConfigChanged<T>` event is sent.
+///
+/// ### Example
+///
+/// ```rust,ignore
+/// let game_data = GameDataBuilder::default().with(
+///     ConfigReloadSystem::<GameplayConfig>::new("resources/gameplay.ron")
+///         .with_validator(|config| config.validate()),
+///     "gameplay_config_reload",
+///     &[],
+/// );
+/// ```
+pub struct ConfigReloadSystem<T> {
+    path: PathBuf,
+    interval: Duration,
+    last_check: Instant,
+    modified: Option<SystemTime>,
+    validator: Option<Validator<T>>,
+}
+
+impl<T> ConfigReloadSystem<T>
+where
+    T: Config + Default + Send + Sync + 'static,
+{
+    /// Creates a system reloading `T` from `path`, checking for changes every second.
+    pub fn new<P: AsRef<Path>>(path: P) -> Self {
+        ConfigReloadSystem {
+            path: path.as_ref().to_path_buf(),
+            interval: Duration::from_secs(1),
+            last_check: Instant::now(),
+            modified: None,
+            validator: None,
+        }
+    }
+
+    /// Sets the interval between checking the file for changes.
+    pub fn with_interval(mut self, interval: Duration) -> Self {
+        self.interval = interval;
+        self
+    }
+
+    /// Sets a function validating the config before it replaces the resource.
+    ///
+    /// The validator is not used for the initial load, since there is no previous value to keep.
+    pub fn with_validator<F>(mut self, validator: F) -> Self
+    where
+        F: Fn(&T) -> Result<(), Error> + Send + Sync + 'static,
+    {
+        self.validator = Some(Box::new(validator));
+        self
+    }
+
+    fn modified(&self) -> Option<SystemTime> {
+        fs::metadata(&self.path)
+            .and_then(|metadata| metadata.modified())
+            .ok()
+    }
+
+    fn reload(&self) -> Result<T, Error> {
+        let config = T::load_no_fallback(&self.path)?;
+        if let Some(ref validator) = self.validator {
+            validator(&config)?;
+        }
+        Ok(config)
+    }
+}
+
+impl<'a, T> System<'a> for ConfigReloadSystem<T>
+where
+    T: Config + Default + Send + Sync + 'static,
+{
+    type SystemData = (Write<'a, T>, Write<'a, EventChannel<ConfigChanged<T>>>);
+
+    fn run(&mut self, (mut config, mut changed): Self::SystemData) {
+        if self.last_check.elapsed() < self.interval {
+            return;
+        }
+        self.last_check = Instant::now();
+
+        let modified = self.modified();
+        if modified.is_none() || modified == self.modified {
+            return;
+        }
+        self.modified = modified;
+
+        match self.reload() {
+            Ok(new) => {
+                info!("Reloaded config {:?}", self.path);
+                *config = new;
+                changed.single_write(ConfigChanged {
+                    path: self.path.clone(),
+                    marker: PhantomData,
+                });
+            }
+            Err(e) => error!(
+                "Failed to reload config {:?}, keeping the previous values: {}",
+                self.path, e
+            ),
+        }
+    }
+
+    fn setup(&mut self, res: &mut Resources) {
+        self.modified = self.modified();
+        if !res.has_value::<T>() {
+            match T::load_no_fallback(&self.path) {
+                Ok(config) => res.insert(config),
+                Err(e) => error!(
+                    "Failed to load config {:?}, using the default values: {}",
+                    self.path, e
+                ),
+            }
+        }
+        Self::SystemData::setup(res);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{env, fs, process};
+
+    use serde::{Deserialize, Serialize};
+
+    use crate::{
+        ecs::prelude::{RunNow, System, World},
+        shrev::EventChannel,
+    };
+
+    use super::{ConfigChanged, ConfigReloadSystem};
+
+    #[derive(Debug, Default, Deserialize, PartialEq, Serialize)]
+    struct Difficulty {
+        level: u32,
+    }
+
+    #[test]
+    fn reloads_changed_file_and_keeps_value_on_error() {
+        let path = env::temp_dir().join(format!("amethyst_config_reload_{}.ron", process::id()));
+        fs::write(&path, "(level: 1)").unwrap();
+        let mut world = World::new();
+        let mut system =
+            ConfigReloadSystem::<Difficulty>::new(&path).with_interval(Default::default());
+        System::setup(&mut system, &mut world.res);
+        assert_eq!(
+            Difficulty { level: 1 },
+            *world.read_resource::<Difficulty>()
+        );
+        let mut reader = world
+            .write_resource::<EventChannel<ConfigChanged<Difficulty>>>()
+            .register_reader();
+
+        let mut change = |system: &mut ConfigReloadSystem<Difficulty>, content: &str| {
+            fs::write(&path, content).unwrap();
+            // The modification time may be too coarse to notice the change.
+            system.modified = None;
+            system.run_now(&world.res);
+            world
+                .read_resource::<EventChannel<ConfigChanged<Difficulty>>>()
+                .read(&mut reader)
+                .count()
+        };
+        assert_eq!(1, change(&mut system, "(level: 2)"));
+        assert_eq!(0, change(&mut system, "(level: "));
+        assert_eq!(
+            Difficulty { level: 2 },
+            *world.read_resource::<Difficulty>()
+        );
+
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn keeps_existing_resource() {
+        let mut world = World::new();
+        world.add_resource(Difficulty { level: 3 });
+        let mut system = ConfigReloadSystem::<Difficulty>::new("missing.ron");
+        System::setup(&mut system, &mut world.res);
+        assert_eq!(
+            Difficulty { level: 3 },
+            *world.read_resource::<Difficulty>()
+        );
+    }
+}
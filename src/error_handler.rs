@@ -0,0 +1,264 @@
+//! Handling of errors reported by systems.
+
+use std::{error, fmt};
+
+use log::error;
+
+use crate::{
+    ecs::common::BoxedErr,
+    error::Error,
+    state::{StateData, Trans},
+};
+
+/// Decides what the application does when systems reported errors to the `Errors` resource.
+///
+/// The handler is called once per error at the end of the frame, and the returned transition is
+/// applied to the state machine: `Trans::None` keeps running, `Trans::Push` can show an error
+/// state, and `Trans::Quit` stops the application gracefully.
+///
+/// It is registered with `ApplicationBuilder::with_error_handler`. Without a handler, the errors
+/// are printed and the process exits, like `Errors::print_and_exit`.
+///
+/// Closures with the signature of `handle_error` implement this trait.
+///
+/// ### Example
+///
+/// ```rust,ignore
+/// let game = Application::build(assets_dir, LoadingState)?
+///     .with_error_handler(|error: Error, _: StateData<'_, GameData<'_, '_>>| {
+///         log_error(&error);
+///         Trans::Push(Box::new(ErrorScreen::new(error.to_string())))
+///     })
+///     .build(game_data)?;
+/// ```
+pub trait ErrorHandler<T, E> {
+    /// Handles an error reported by a system.
+    ///
+    /// The error wraps a `ReportedError`, which gives access to the original error.
+    fn handle_error(&mut self, error: Error, data: StateData<'_, T>) -> Trans<T, E>;
+}
+
+impl<T, E, F> ErrorHandler<T, E> for F
+where
+    F: FnMut(Error, StateData<'_, T>) -> Trans<T, E>,
+{
+    fn handle_error(&mut self, error: Error, data: StateData<'_, T>) -> Trans<T, E> {
+        self(error, data)
+    }
+}
+
+/// An error reported by a system to the `Errors` resource, as passed to the `ErrorHandler`.
+///
+/// The original error can be downcast to apply different policies to different errors, and its
+/// sources are available through `std::error::Error::source`.
+///
+/// The backtrace of the `Error` passed to the handler, if enabled, is captured at the end of the
+/// frame when the error is handled, so it doesn't show where the error occurred.
+///
+/// ### Example
+///
+/// ```rust,ignore
+/// fn handle_error(error: Error, _: StateData<'_, GameData<'_, '_>>) -> SimpleTrans {
+///     match ReportedError::of(&error).and_then(|e| e.downcast_ref::<ConnectionLost>()) {
+///         Some(_) => Trans::Push(Box::new(ReconnectState)),
+///         None => Trans::Quit,
+///     }
+/// }
+/// ```
+pub struct ReportedError(BoxedErr);
+
+impl ReportedError {
+    /// Returns the reported error wrapped by `error`, if `error` was reported by a system.
+    pub fn of(error: &Error) -> Option<&ReportedError> {
+        // `Error::new` keeps the error in a box.
+        error
+            .as_error()
+            .downcast_ref::<Box<ReportedError>>()
+            .map(|reported| &**reported)
+    }
+
+    /// Returns the original error.
+    pub fn get_ref(&self) -> &(dyn error::Error + Send + Sync + 'static) {
+        &*self.0
+    }
+
+    /// Returns the original error if it is of type `T`.
+    pub fn downcast_ref<T: error::Error + 'static>(&self) -> Option<&T> {
+        self.0.downcast_ref()
+    }
+
+    /// Returns the original error, consuming the wrapper.
+    pub fn into_inner(self) -> BoxedErr {
+        self.0
+    }
+}
+
+impl fmt::Debug for ReportedError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(&self.0, f)
+    }
+}
+
+impl fmt::Display for ReportedError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Display::fmt(&self.0, f)
+    }
+}
+
+impl error::Error for ReportedError {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        self.0.source()
+    }
+}
+
+/// Logs an error with all its causes and their sources, and its backtrace if it has one.
+pub fn log_error(error: &Error) {
+    let mut causes = Vec::new();
+    for cause in error.causes() {
+        let mut source = Some(cause.as_error());
+        while let Some(error) = source {
+            causes.push(error.to_string());
+            source = error.source();
+        }
+    }
+    let mut message = causes.join("\n  caused by: ");
+    if let Some(backtrace) = error.backtrace() {
+        message.push_str(&format!("\n{:?}", backtrace));
+    }
+    error!("{}", message);
+}
+
+/// Wraps an error reported to the `Errors` resource in a `ReportedError`.
+pub(crate) fn from_reported(reported: BoxedErr) -> Error {
+    Error::new(ReportedError(reported))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        env, error, fmt,
+        sync::{
+            atomic::{AtomicUsize, Ordering},
+            Arc,
+        },
+    };
+
+    use crate::{
+        ecs::common::Errors, error::Error, Application, GameData, GameDataBuilder, SimpleState,
+        SimpleTrans, StateData, StateEvent, Trans,
+    };
+
+    use super::{from_reported, ErrorHandler, ReportedError};
+
+    #[derive(Debug)]
+    struct Failure(&'static str, Option<Box<Failure>>);
+
+    impl fmt::Display for Failure {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            f.write_str(self.0)
+        }
+    }
+
+    impl error::Error for Failure {
+        fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+            self.1
+                .as_ref()
+                .map(|e| &**e as &(dyn error::Error + 'static))
+        }
+    }
+
+    #[test]
+    fn keeps_original_error_and_sources() {
+        let reported = Failure(
+            "system failed",
+            Some(Box::new(Failure(
+                "file not found",
+                Some(Box::new(Failure("os error", None))),
+            ))),
+        );
+        let error = from_reported(Box::new(reported));
+
+        let failure = ReportedError::of(&error)
+            .and_then(|e| e.downcast_ref::<Failure>())
+            .expect("not a reported `Failure`");
+        assert_eq!(failure.0, "system failed");
+
+        let mut causes = Vec::new();
+        let mut source = Some(error.as_error());
+        while let Some(error) = source {
+            causes.push(error.to_string());
+            source = error.source();
+        }
+        assert_eq!(causes, ["system failed", "file not found", "os error"]);
+    }
+
+    /// Reports an error every frame, and quits after three frames.
+    struct Failing(Arc<AtomicUsize>);
+
+    impl SimpleState for Failing {
+        fn update(&mut self, data: &mut StateData<'_, GameData<'_, '_>>) -> SimpleTrans {
+            data.world
+                .read_resource::<Errors>()
+                .add(Box::new(Failure("system failed", None)));
+            if self.0.fetch_add(1, Ordering::Relaxed) + 1 == 3 {
+                Trans::Quit
+            } else {
+                Trans::None
+            }
+        }
+    }
+
+    struct Handler {
+        quit: bool,
+        handled: Arc<AtomicUsize>,
+    }
+
+    impl ErrorHandler<GameData<'static, 'static>, StateEvent> for Handler {
+        fn handle_error(
+            &mut self,
+            error: Error,
+            _: StateData<'_, GameData<'static, 'static>>,
+        ) -> SimpleTrans {
+            assert!(ReportedError::of(&error)
+                .and_then(|e| e.downcast_ref::<Failure>())
+                .is_some());
+            self.handled.fetch_add(1, Ordering::Relaxed);
+            if self.quit {
+                Trans::Quit
+            } else {
+                Trans::None
+            }
+        }
+    }
+
+    /// Runs an application with `Failing` and `Handler`, returning the number of frames and of
+    /// handled errors.
+    fn run(quit: bool) -> (usize, usize) {
+        let frames = Arc::new(AtomicUsize::new(0));
+        let handled = Arc::new(AtomicUsize::new(0));
+        let mut game = Application::build(env::temp_dir(), Failing(frames.clone()))
+            .unwrap()
+            .with_error_handler(Handler {
+                quit,
+                handled: handled.clone(),
+            })
+            .build(GameDataBuilder::default())
+            .unwrap();
+        game.run();
+
+        (
+            frames.load(Ordering::Relaxed),
+            handled.load(Ordering::Relaxed),
+        )
+    }
+
+    #[test]
+    fn handler_keeps_running() {
+        assert_eq!(run(false), (3, 3));
+    }
+
+    #[test]
+    fn handler_quits() {
+        assert_eq!(run(true), (1, 1));
+    }
+}
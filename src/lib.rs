@@ -84,9 +84,14 @@ pub use crate::derive::*;
 pub use self::{
     app::{Application, ApplicationBuilder, CoreApplication},
     callback_queue::{Callback, CallbackQueue},
+    config_reload::{ConfigChanged, ConfigReloadSystem},
     error::Error,
+    error_handler::{log_error, ErrorHandler, ReportedError},
     game_data::{DataInit, GameData, GameDataBuilder},
-    logger::{start_logger, LevelFilter as LogLevelFilter, Logger, LoggerConfig, StdoutLog},
+    logger::{
+        start_logger, LevelFilter as LogLevelFilter, LogBuffer, LogEntry, LogFormat, LogOutput,
+        LogTarget, Logger, LoggerConfig, StdoutLog,
+    },
     state::{
         EmptyState, EmptyTrans, SimpleState, SimpleTrans, State, StateData, StateMachine, Trans,
         TransEvent,
@@ -98,9 +103,13 @@ pub use self::{
 pub type Result<T> = std::result::Result<T, error::Error>;
 
 pub mod prelude;
+#[cfg(feature = "saveload")]
+pub mod saveload;
 
 mod app;
 mod callback_queue;
+mod config_reload;
+mod error_handler;
 mod game_data;
 mod logger;
 mod state;
@@ -1,9 +1,35 @@
 pub use log::LevelFilter;
 
-use log::debug;
+use log::{debug, Level, Log, Metadata, Record};
 use serde::{Deserialize, Serialize};
 
-use std::{env, io, path::PathBuf, str::FromStr};
+use std::{
+    collections::VecDeque,
+    env,
+    fmt::Arguments,
+    fs::{self, File, OpenOptions},
+    io::{self, Write},
+    path::PathBuf,
+    str::FromStr,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc, Mutex, PoisonError,
+    },
+    thread,
+    time::SystemTime,
+};
+
+/// The frame number included in log messages, updated by the `Application`.
+static FRAME_NUMBER: AtomicUsize = AtomicUsize::new(0);
+
+/// Sets the frame number included in log messages.
+pub(crate) fn set_frame_number(frame_number: u64) {
+    FRAME_NUMBER.store(frame_number as usize, Ordering::Relaxed);
+}
+
+fn frame_number() -> u64 {
+    FRAME_NUMBER.load(Ordering::Relaxed) as u64
+}
 
 /// An enum that contains options for logging to the terminal.
 #[derive(Clone, Copy, PartialEq, Eq, Debug, Serialize, Deserialize)]
@@ -16,6 +42,113 @@ pub enum StdoutLog {
     Colored,
 }
 
+/// The format of log messages.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Serialize, Deserialize)]
+pub enum LogFormat {
+    /// `[level][target] message`.
+    Text,
+    /// `[timestamp][thread][frame][level][target] message`, with the local time.
+    Detailed,
+    /// One JSON object per line, with the fields `timestamp` (RFC 3339), `level`, `target`,
+    /// `thread`, `frame` and `message`.
+    Json,
+}
+
+impl Default for LogFormat {
+    fn default() -> Self {
+        LogFormat::Text
+    }
+}
+
+/// The destination of a `LogOutput`.
+#[derive(Clone, PartialEq, Eq, Debug, Serialize, Deserialize)]
+pub enum LogTarget {
+    /// Logs to the standard output.
+    Stdout,
+    /// Logs to the standard error.
+    Stderr,
+    /// Logs to a file, appending to it if it exists.
+    File {
+        /// The path of the file.
+        path: PathBuf,
+        /// If set, the file is rotated once it reaches this size in bytes: `game.log` is renamed
+        /// to `game.log.1`, `game.log.1` to `game.log.2` and so on, and a new `game.log` is
+        /// started.
+        #[serde(default)]
+        max_size: Option<u64>,
+        /// The number of rotated files which are kept.
+        #[serde(default = "default_max_files")]
+        max_files: usize,
+    },
+}
+
+fn default_max_files() -> usize {
+    5
+}
+
+/// An additional output of the logger, with its own format and level filters.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct LogOutput {
+    /// Where the messages are written to.
+    pub target: LogTarget,
+    /// The format of the messages.
+    #[serde(default)]
+    pub format: LogFormat,
+    /// Sets the level filter of this output, or uses `LoggerConfig::level_filter` if `None`.
+    ///
+    /// This can also be less restrictive than `LoggerConfig::level_filter`: the logger lets
+    /// through the messages of the least restrictive output, and every output filters them
+    /// again with its own level.
+    #[serde(default)]
+    pub level_filter: Option<LevelFilter>,
+    /// Sets different levels for individual modules in this output.
+    #[serde(default)]
+    pub level_for: Vec<(String, LevelFilter)>,
+}
+
+impl LogOutput {
+    /// Creates an output writing messages to `target` in the given format, with the level
+    /// filter of the logger.
+    pub fn new(target: LogTarget, format: LogFormat) -> Self {
+        LogOutput {
+            target,
+            format,
+            level_filter: None,
+            level_for: Vec::new(),
+        }
+    }
+
+    /// Sets the level filter of this output.
+    pub fn with_level(mut self, level_filter: LevelFilter) -> Self {
+        self.level_filter = Some(level_filter);
+        self
+    }
+
+    fn dispatch(self, default_level: LevelFilter) -> io::Result<fern::Dispatch> {
+        let mut dispatch = formatted(self.format).level(self.level_filter.unwrap_or(default_level));
+        for (module, level) in self.level_for {
+            dispatch = dispatch.level_for(module, level);
+        }
+        Ok(match self.target {
+            LogTarget::Stdout => dispatch.chain(io::stdout()),
+            LogTarget::Stderr => dispatch.chain(io::stderr()),
+            LogTarget::File {
+                path,
+                max_size: None,
+                ..
+            } => dispatch.chain(fern::log_file(path)?),
+            LogTarget::File {
+                path,
+                max_size: Some(max_size),
+                max_files,
+            } => {
+                let file = RotatingFile::open(path, max_size, max_files)?;
+                dispatch.chain(Box::new(file) as Box<dyn Write + Send>)
+            }
+        })
+    }
+}
+
 /// Logger configuration object.
 #[derive(Clone, Serialize, Deserialize)]
 pub struct LoggerConfig {
@@ -29,6 +162,16 @@ pub struct LoggerConfig {
     pub allow_env_override: bool,
     /// Sets a different level for gfx_device_gl if Some
     pub log_gfx_device_level: Option<LevelFilter>,
+    /// Sets the format of the messages logged to the terminal and to `log_file`.
+    #[serde(default)]
+    pub format: LogFormat,
+    /// Additional outputs, e.g. a JSON log file with a size limit.
+    #[serde(default)]
+    pub outputs: Vec<LogOutput>,
+    /// If set, keeps the given number of most recent messages in a `LogBuffer`, see
+    /// `Logger::buffer`.
+    #[serde(default)]
+    pub buffer_capacity: Option<usize>,
 }
 
 impl Default for LoggerConfig {
@@ -39,8 +182,143 @@ impl Default for LoggerConfig {
             log_file: None,
             allow_env_override: true,
             log_gfx_device_level: Some(LevelFilter::Warn),
+            format: LogFormat::Text,
+            outputs: Vec::new(),
+            buffer_capacity: None,
+        }
+    }
+}
+
+/// A log message kept in a `LogBuffer`.
+#[derive(Clone, Debug)]
+pub struct LogEntry {
+    /// The level of the message.
+    pub level: Level,
+    /// The target of the message, usually the module it was logged from.
+    pub target: String,
+    /// The message.
+    pub message: String,
+    /// The name of the thread which logged the message, if it has one.
+    pub thread: Option<String>,
+    /// The frame the message was logged in.
+    pub frame: u64,
+    /// The time the message was logged at.
+    pub time: SystemTime,
+}
+
+#[derive(Debug)]
+struct Entries {
+    entries: VecDeque<LogEntry>,
+    capacity: usize,
+    total: u64,
+}
+
+/// In-memory sink keeping the most recent log messages, e.g. for an in-game console.
+///
+/// Clones share the same messages. The `LogBuffer` of a `Logger` is created with
+/// `LoggerConfig::buffer_capacity` and can be added to the world as a resource:
+///
+/// ```rust,ignore
+/// let logger = Logger::from_config(LoggerConfig {
+///     buffer_capacity: Some(500),
+///     ..Default::default()
+/// });
+/// let log_buffer = logger.buffer().expect("`buffer_capacity` is set");
+/// logger.start();
+///
+/// let mut game = Application::build(assets_dir, ExampleState)?
+///     .with_resource(log_buffer)
+///     .build(game_data)?;
+/// ```
+#[derive(Clone, Debug)]
+pub struct LogBuffer {
+    inner: Arc<Mutex<Entries>>,
+}
+
+impl LogBuffer {
+    /// Creates a buffer keeping the `capacity` most recent messages.
+    pub fn new(capacity: usize) -> Self {
+        LogBuffer {
+            inner: Arc::new(Mutex::new(Entries {
+                entries: VecDeque::with_capacity(capacity),
+                capacity,
+                total: 0,
+            })),
         }
     }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, Entries> {
+        self.inner.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// Returns the number of messages the buffer keeps.
+    pub fn capacity(&self) -> usize {
+        self.lock().capacity
+    }
+
+    /// Returns the number of messages logged since the buffer was created, including the ones
+    /// which were dropped already.
+    pub fn total(&self) -> u64 {
+        self.lock().total
+    }
+
+    /// Returns the kept messages, oldest first.
+    pub fn entries(&self) -> Vec<LogEntry> {
+        self.lock().entries.iter().cloned().collect()
+    }
+
+    /// Returns the kept messages which were logged after the first `total` messages, oldest first.
+    ///
+    /// Together with `total` this allows reading only new messages each frame.
+    pub fn entries_since(&self, total: u64) -> Vec<LogEntry> {
+        let inner = self.lock();
+        let new = inner
+            .total
+            .saturating_sub(total)
+            .min(inner.entries.len() as u64) as usize;
+        inner
+            .entries
+            .iter()
+            .skip(inner.entries.len() - new)
+            .cloned()
+            .collect()
+    }
+
+    /// Removes all kept messages.
+    pub fn clear(&self) {
+        self.lock().entries.clear();
+    }
+
+    fn push(&self, entry: LogEntry) {
+        let mut inner = self.lock();
+        inner.total += 1;
+        if inner.capacity == 0 {
+            return;
+        }
+        if inner.entries.len() == inner.capacity {
+            inner.entries.pop_front();
+        }
+        inner.entries.push_back(entry);
+    }
+}
+
+impl Log for LogBuffer {
+    fn enabled(&self, _: &Metadata<'_>) -> bool {
+        true
+    }
+
+    fn log(&self, record: &Record<'_>) {
+        self.push(LogEntry {
+            level: record.level(),
+            target: record.target().to_owned(),
+            message: record.args().to_string(),
+            thread: thread::current().name().map(str::to_owned),
+            frame: frame_number(),
+            time: SystemTime::now(),
+        });
+    }
+
+    fn flush(&self) {}
 }
 
 /// Allows the creation of a logger with a set of custom configurations. If no custom configuration
@@ -55,19 +333,15 @@ impl Default for LoggerConfig {
 /// ```
 pub struct Logger {
     dispatch: fern::Dispatch,
+    buffer: Option<LogBuffer>,
 }
 
 impl Logger {
     fn new() -> Self {
-        let dispatch = fern::Dispatch::new().format(|out, message, record| {
-            out.finish(format_args!(
-                "[{level}][{target}] {message}",
-                level = record.level(),
-                target = record.target(),
-                message = message,
-            ))
-        });
-        Logger { dispatch }
+        Logger {
+            dispatch: fern::Dispatch::new(),
+            buffer: None,
+        }
     }
 
     /// Create a new Logger from [`LoggerConfig`]
@@ -77,14 +351,31 @@ impl Logger {
         }
 
         let mut logger = Logger::new();
-        logger.dispatch = logger.dispatch.level(config.level_filter);
+        // The outputs filter the messages themselves, so the logger lets through everything any
+        // of them needs.
+        let max_level = config
+            .outputs
+            .iter()
+            .filter_map(|output| output.level_filter)
+            .fold(config.level_filter, std::cmp::max);
+        logger.dispatch = logger.dispatch.level(max_level);
 
         match config.stdout {
-            StdoutLog::Plain => logger.dispatch = logger.dispatch.chain(io::stdout()),
+            StdoutLog::Plain => logger.chain(
+                formatted(config.format)
+                    .level(config.level_filter)
+                    .chain(io::stdout()),
+            ),
             StdoutLog::Colored => {
-                logger.dispatch = logger
-                    .dispatch
-                    .chain(colored_stdout(fern::colors::ColoredLevelConfig::new()))
+                let stdout = match config.format {
+                    LogFormat::Json => fern::Dispatch::new().chain(io::stdout()),
+                    _ => colored_stdout(fern::colors::ColoredLevelConfig::new()),
+                };
+                logger.chain(
+                    formatted(config.format)
+                        .level(config.level_filter)
+                        .chain(stdout),
+                )
             }
             StdoutLog::Off => {}
         }
@@ -97,14 +388,44 @@ impl Logger {
 
         if let Some(path) = config.log_file {
             match fern::log_file(path) {
-                Ok(log_file) => logger.dispatch = logger.dispatch.chain(log_file),
+                Ok(log_file) => logger.chain(
+                    formatted(config.format)
+                        .level(config.level_filter)
+                        .chain(log_file),
+                ),
                 Err(_) => eprintln!("Unable to access the log file, as such it will not be used"),
             }
         }
 
+        for output in config.outputs {
+            let target = output.target.clone();
+            match output.dispatch(config.level_filter) {
+                Ok(dispatch) => logger.chain(dispatch),
+                Err(e) => eprintln!(
+                    "Unable to use the log output {:?}, as such it will not be used: {}",
+                    target, e
+                ),
+            }
+        }
+
+        if let Some(capacity) = config.buffer_capacity {
+            let buffer = LogBuffer::new(capacity);
+            logger.chain(
+                fern::Dispatch::new()
+                    .level(config.level_filter)
+                    .chain(Box::new(buffer.clone()) as Box<dyn Log>),
+            );
+            logger.buffer = Some(buffer);
+        }
+
         logger
     }
 
+    fn chain(&mut self, output: fern::Dispatch) {
+        let dispatch = std::mem::replace(&mut self.dispatch, fern::Dispatch::new());
+        self.dispatch = dispatch.chain(output);
+    }
+
     /// Set individual log levels for modules.
     pub fn level_for<T: Into<std::borrow::Cow<'static, str>>>(
         mut self,
@@ -115,6 +436,12 @@ impl Logger {
         self
     }
 
+    /// Returns the `LogBuffer` keeping the most recent messages, if
+    /// `LoggerConfig::buffer_capacity` was set.
+    pub fn buffer(&self) -> Option<LogBuffer> {
+        self.buffer.clone()
+    }
+
     /// Starts [`Logger`] by consuming it.
     pub fn start(self) {
         self.dispatch.apply().unwrap_or_else(|_| {
@@ -142,6 +469,10 @@ impl Logger {
 ///     * "trace" everything
 /// * AMETHYST_LOG_FILE_PATH - if set, enables logging to the file at the path
 ///     * the value is expected to be a path to the logging file
+/// * AMETHYST_LOG_FORMAT - sets the format of the terminal and file output
+///     * "text" logs `[level][target] message`
+///     * "detailed" also logs the time, thread and frame number
+///     * "json" logs one JSON object per line
 pub fn start_logger(config: LoggerConfig) {
     Logger::from_config(config).start();
 }
@@ -163,6 +494,132 @@ fn env_var_override(config: &mut LoggerConfig) {
     if let Ok(path) = env::var("AMETHYST_LOG_FILE_PATH") {
         config.log_file = Some(PathBuf::from(path));
     }
+    if let Ok(var) = env::var("AMETHYST_LOG_FORMAT") {
+        match var.to_lowercase().as_ref() {
+            "text" => config.format = LogFormat::Text,
+            "detailed" => config.format = LogFormat::Detailed,
+            "json" => config.format = LogFormat::Json,
+            _ => {}
+        }
+    }
+}
+
+/// Creates a dispatch formatting the messages in `format`.
+fn formatted(format: LogFormat) -> fern::Dispatch {
+    let dispatch = fern::Dispatch::new();
+    match format {
+        LogFormat::Text => dispatch.format(|out, message, record| {
+            out.finish(format_args!(
+                "[{level}][{target}] {message}",
+                level = record.level(),
+                target = record.target(),
+                message = message,
+            ))
+        }),
+        LogFormat::Detailed => dispatch.format(|out, message, record| {
+            out.finish(format_args!(
+                "[{time}][{thread}][{frame}][{level}][{target}] {message}",
+                time = chrono::Local::now().format("%Y-%m-%d %H:%M:%S%.3f"),
+                thread = thread::current().name().unwrap_or("<unnamed>"),
+                frame = frame_number(),
+                level = record.level(),
+                target = record.target(),
+                message = message,
+            ))
+        }),
+        LogFormat::Json => dispatch.format(|out, message, record| {
+            let line = json_line(message, record);
+            out.finish(format_args!("{}", line))
+        }),
+    }
+}
+
+fn json_line(message: &Arguments<'_>, record: &Record<'_>) -> String {
+    #[derive(Serialize)]
+    struct JsonRecord<'a> {
+        timestamp: String,
+        level: Level,
+        target: &'a str,
+        thread: Option<&'a str>,
+        frame: u64,
+        message: String,
+    }
+
+    let current = thread::current();
+    let json = JsonRecord {
+        timestamp: chrono::Local::now().to_rfc3339(),
+        level: record.level(),
+        target: record.target(),
+        thread: current.name(),
+        frame: frame_number(),
+        message: message.to_string(),
+    };
+    serde_json::to_string(&json).unwrap_or_else(|_| json.message)
+}
+
+/// A log file which is rotated once it reaches a size limit.
+struct RotatingFile {
+    path: PathBuf,
+    max_size: u64,
+    max_files: usize,
+    file: File,
+    size: u64,
+}
+
+impl RotatingFile {
+    fn open(path: PathBuf, max_size: u64, max_files: usize) -> io::Result<Self> {
+        let file = OpenOptions::new().create(true).append(true).open(&path)?;
+        let size = file.metadata()?.len();
+        Ok(RotatingFile {
+            path,
+            max_size,
+            max_files,
+            file,
+            size,
+        })
+    }
+
+    fn rotated_path(&self, n: usize) -> PathBuf {
+        let mut path = self.path.clone().into_os_string();
+        path.push(format!(".{}", n));
+        path.into()
+    }
+
+    fn rotate(&mut self) -> io::Result<()> {
+        for n in (1..self.max_files).rev() {
+            let from = self.rotated_path(n);
+            if from.exists() {
+                fs::rename(&from, self.rotated_path(n + 1))?;
+            }
+        }
+        if self.max_files > 0 {
+            fs::rename(&self.path, self.rotated_path(1))?;
+        }
+        self.file = OpenOptions::new()
+            .create(true)
+            .write(true)
+            .truncate(true)
+            .open(&self.path)?;
+        self.size = 0;
+        Ok(())
+    }
+}
+
+impl Write for RotatingFile {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let written = self.file.write(buf)?;
+        self.size += written as u64;
+        Ok(written)
+    }
+
+    // The logger flushes after every message, so rotating here never splits a message.
+    fn flush(&mut self) -> io::Result<()> {
+        self.file.flush()?;
+        if self.size >= self.max_size {
+            self.rotate()?;
+        }
+        Ok(())
+    }
 }
 
 fn colored_stdout(color_config: fern::colors::ColoredLevelConfig) -> fern::Dispatch {
@@ -195,4 +652,69 @@ mod tests {
 
         assert_eq!(config.stdout, StdoutLog::Plain);
     }
+
+    #[test]
+    fn buffer_keeps_recent_entries() {
+        let buffer = LogBuffer::new(2);
+        for i in 0..3 {
+            buffer.log(
+                &Record::builder()
+                    .args(format_args!("message {}", i))
+                    .level(Level::Info)
+                    .build(),
+            );
+        }
+
+        assert_eq!(buffer.total(), 3);
+        let messages = |entries: Vec<LogEntry>| {
+            entries
+                .into_iter()
+                .map(|entry| entry.message)
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(messages(buffer.entries()), ["message 1", "message 2"]);
+        assert_eq!(messages(buffer.entries_since(2)), ["message 2"]);
+        assert!(buffer.entries_since(3).is_empty());
+    }
+
+    #[test]
+    fn rotates_log_files() {
+        let dir = env::temp_dir().join(format!("amethyst_log_rotation_{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("game.log");
+
+        let mut file = RotatingFile::open(path.clone(), 10, 1).unwrap();
+        for line in &["first line\n", "second line\n", "third\n"] {
+            file.write_all(line.as_bytes()).unwrap();
+            file.flush().unwrap();
+        }
+
+        assert_eq!(fs::read_to_string(&path).unwrap(), "third\n");
+        assert_eq!(
+            fs::read_to_string(dir.join("game.log.1")).unwrap(),
+            "second line\n"
+        );
+        assert!(!dir.join("game.log.2").exists());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn outputs_inherit_level_filter() {
+        let max_level = |output: LogOutput| {
+            let config = LoggerConfig {
+                stdout: StdoutLog::Off,
+                allow_env_override: false,
+                outputs: vec![output],
+                ..Default::default()
+            };
+            Logger::from_config(config).dispatch.into_log().0
+        };
+        let output = LogOutput::new(LogTarget::Stderr, LogFormat::Json);
+
+        assert_eq!(max_level(output.clone()), LevelFilter::Info);
+        assert_eq!(
+            max_level(output.with_level(LevelFilter::Trace)),
+            LevelFilter::Trace
+        );
+    }
 }
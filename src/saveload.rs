@@ -0,0 +1,617 @@
+//! Saving and restoring entities of the `World`, e.g. for save games.
+//!
+//! Entities marked with the `Saveable` component are saved by `SaveGame::save`, together with
+//! their `Parent`s and the components selected by a `SaveData` type. Components are selected by
+//! wrapping them in `Persist`, and asset handles by wrapping them in `PersistAsset`, which saves
+//! the path, format and options the asset was loaded with and loads it again when the save game
+//! is restored.
+//!
+//! A `SaveGame` implements `Config`, so it can be written to and loaded from RON files like any
+//! other config. `write_binary` and `load_binary` use bincode instead, which is smaller and faster.
+//!
+//! Saved entities are identified by their index in the save game, so unlike the `saveload`
+//! module of specs, no marker component with persistent ids or marker allocator is needed, and
+//! components don't need to implement `ConvertSaveload`. Asset handles and `Parent`s need access
+//! to resources while saving and restoring, which `SaveData` gets through its `SystemData`.
+//!
+//! ### Example
+//!
+//! ```rust,ignore
+//! type MySave = (Persist<Transform>, Persist<Player>, PersistAsset<Mesh, ObjFormat>);
+//!
+//! // saving
+//! let save = SaveGame::save::<MySave>(&world, SAVE_VERSION)?;
+//! save.write("save.ron")?;
+//!
+//! // loading, migrating save games written with the `SaveData` of version 1
+//! let save = match SaveHeader::load("save.ron")?.version {
+//!     1 => SaveGame::load_no_fallback("save.ron")?.migrate(SAVE_VERSION, migrate_v1)?,
+//!     _ => SaveGame::load_no_fallback("save.ron")?,
+//! };
+//! save.restore::<MySave>(&mut world)?;
+//! ```
+
+use std::{collections::HashMap, fs, marker::PhantomData, path::Path};
+
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+
+use crate::{
+    assets::{Asset, AssetStorage, Format, Handle, Loader},
+    config::Config,
+    core::Parent,
+    ecs::prelude::{
+        Builder, Component, Entities, Entity, Join, NullStorage, Read, ReadExpect, ReadStorage,
+        SystemData, World, WriteStorage,
+    },
+    error::{format_err, Error, ResultExt},
+};
+
+/// Marker component for entities which are saved by `SaveGame::save`.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Saveable;
+
+impl Component for Saveable {
+    type Storage = NullStorage<Self>;
+}
+
+/// Header of a save game.
+#[derive(Clone, Debug, Default, Deserialize, Eq, PartialEq, Serialize)]
+pub struct SaveHeader {
+    /// Version of the save game layout, as passed to `SaveGame::save`.
+    ///
+    /// The version should be increased whenever the `SaveData` changes, so old save games
+    /// can be detected and migrated with `SaveGame::migrate`.
+    pub version: u32,
+}
+
+/// Only the header of a save game, for reading the header before the `SaveData` is known.
+#[derive(Default, Deserialize, Serialize)]
+struct HeaderOnly {
+    header: SaveHeader,
+}
+
+impl SaveHeader {
+    /// Read only the header of a save game written with `Config::write`.
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self, Error> {
+        let path = path.as_ref();
+        let header = HeaderOnly::load_no_fallback(path)
+            .with_context(|_| format_err!("Failed reading save game header {:?}", path))?;
+        Ok(header.header)
+    }
+
+    /// Read only the header of a save game written with `SaveGame::write_binary`.
+    pub fn load_binary<P: AsRef<Path>>(path: P) -> Result<Self, Error> {
+        let path = path.as_ref();
+        let bytes =
+            fs::read(path).with_context(|_| format_err!("Failed reading save game {:?}", path))?;
+        // The header is serialized first, bincode ignores the rest of the save game
+        let header = bincode::deserialize(&bytes)
+            .with_context(|_| format_err!("Failed deserializing save game header {:?}", path))?;
+        Ok(header)
+    }
+}
+
+/// Data which is saved for every `Saveable` entity.
+///
+/// Implemented by `Persist`, `PersistAsset` and tuples of them.
+pub trait SaveData<'a> {
+    /// `SystemData` needed for saving and restoring.
+    type SystemData: SystemData<'a>;
+    /// The serialized representation of the data.
+    type Saved: Default + Serialize + DeserializeOwned;
+
+    /// Save the data of `entities`.
+    ///
+    /// The index of an entity in `entities` identifies it in the save game.
+    fn save(system_data: &Self::SystemData, entities: &[Entity]) -> Result<Self::Saved, Error>;
+
+    /// Restore the data onto `entities`, which were created for the saved entities in the
+    /// same order.
+    fn restore(
+        saved: Self::Saved,
+        system_data: &mut Self::SystemData,
+        entities: &[Entity],
+    ) -> Result<(), Error>;
+}
+
+/// `SaveData` persisting the component `C`.
+pub struct Persist<C>(PhantomData<C>);
+
+impl<'a, C> SaveData<'a> for Persist<C>
+where
+    C: Component + Clone + Serialize + DeserializeOwned,
+{
+    type SystemData = WriteStorage<'a, C>;
+    type Saved = Vec<(usize, C)>;
+
+    fn save(storage: &Self::SystemData, entities: &[Entity]) -> Result<Self::Saved, Error> {
+        Ok(entities
+            .iter()
+            .enumerate()
+            .filter_map(|(index, entity)| storage.get(*entity).map(|c| (index, c.clone())))
+            .collect())
+    }
+
+    fn restore(
+        saved: Self::Saved,
+        storage: &mut Self::SystemData,
+        entities: &[Entity],
+    ) -> Result<(), Error> {
+        for (index, component) in saved {
+            storage.insert(entity_at(entities, index)?, component)?;
+        }
+        Ok(())
+    }
+}
+
+/// `SaveData` persisting `Handle<A>` components by the path, format and options their asset was
+/// loaded with.
+///
+/// The format and options are looked up with `AssetStorage::format`, so the storage has to record
+/// formats (see `AssetStorage::record_formats`). They are recorded when assets are loaded by an
+/// `AssetPrefab`; assets loaded in other ways need to be recorded with `record_format` to be saved.
+pub struct PersistAsset<A, F>(PhantomData<(A, F)>);
+
+impl<'a, A, F> SaveData<'a> for PersistAsset<A, F>
+where
+    A: Asset,
+    F: Format<A> + Clone + Serialize + DeserializeOwned,
+    F::Options: Clone + Serialize + DeserializeOwned,
+{
+    type SystemData = (
+        ReadExpect<'a, Loader>,
+        WriteStorage<'a, Handle<A>>,
+        Read<'a, AssetStorage<A>>,
+    );
+    type Saved = Vec<(usize, String, F, F::Options)>;
+
+    fn save(system_data: &Self::SystemData, entities: &[Entity]) -> Result<Self::Saved, Error> {
+        let mut saved = Vec::new();
+        for (index, entity) in entities.iter().enumerate() {
+            if let Some(handle) = system_data.1.get(*entity) {
+                let name = system_data.2.name(handle).ok_or_else(|| {
+                    format_err!(
+                        "Can't save {:?} asset, it is not loaded from a file",
+                        A::NAME
+                    )
+                })?;
+                let (format, options) = system_data.2.format(handle).ok_or_else(|| {
+                    format_err!(
+                        "Can't save {:?} asset {:?}, no format {:?} was recorded for it",
+                        A::NAME,
+                        name,
+                        F::NAME
+                    )
+                })?;
+                saved.push((index, name.to_owned(), format, options));
+            }
+        }
+        Ok(saved)
+    }
+
+    fn restore(
+        saved: Self::Saved,
+        system_data: &mut Self::SystemData,
+        entities: &[Entity],
+    ) -> Result<(), Error> {
+        for (index, name, format, options) in saved {
+            let handle =
+                system_data
+                    .0
+                    .load(name, format.clone(), options.clone(), (), &system_data.2);
+            system_data.2.record_format(&handle, &format, &options);
+            system_data.1.insert(entity_at(entities, index)?, handle)?;
+        }
+        Ok(())
+    }
+}
+
+fn entity_at(entities: &[Entity], index: usize) -> Result<Entity, Error> {
+    entities
+        .get(index)
+        .cloned()
+        .ok_or_else(|| format_err!("Save game refers to missing entity {}", index))
+}
+
+macro_rules! impl_save_data {
+    ( $($ty:ident:$i:tt),* ) => {
+        impl<'a, $($ty),*> SaveData<'a> for ( $( $ty , )* )
+            where $( $ty : SaveData<'a> ),*
+        {
+            type SystemData = ( $( $ty::SystemData, )* );
+            type Saved = ( $( $ty::Saved, )* );
+
+            fn save(
+                system_data: &Self::SystemData,
+                entities: &[Entity],
+            ) -> Result<Self::Saved, Error> {
+                Ok(( $( $ty::save(&system_data.$i, entities)?, )* ))
+            }
+
+            fn restore(
+                saved: Self::Saved,
+                system_data: &mut Self::SystemData,
+                entities: &[Entity],
+            ) -> Result<(), Error> {
+                $(
+                    $ty::restore(saved.$i, &mut system_data.$i, entities)?;
+                )*
+                Ok(())
+            }
+        }
+    };
+}
+
+impl_save_data!(A:0);
+impl_save_data!(A:0, B:1);
+impl_save_data!(A:0, B:1, C:2);
+impl_save_data!(A:0, B:1, C:2, D:3);
+impl_save_data!(A:0, B:1, C:2, D:3, E:4);
+impl_save_data!(A:0, B:1, C:2, D:3, E:4, F:5);
+impl_save_data!(A:0, B:1, C:2, D:3, E:4, F:5, G:6);
+impl_save_data!(A:0, B:1, C:2, D:3, E:4, F:5, G:6, H:7);
+impl_save_data!(A:0, B:1, C:2, D:3, E:4, F:5, G:6, H:7, I:8);
+impl_save_data!(A:0, B:1, C:2, D:3, E:4, F:5, G:6, H:7, I:8, J:9);
+impl_save_data!(A:0, B:1, C:2, D:3, E:4, F:5, G:6, H:7, I:8, J:9, K:10);
+impl_save_data!(A:0, B:1, C:2, D:3, E:4, F:5, G:6, H:7, I:8, J:9, K:10, L:11);
+
+/// A saved set of entities.
+///
+/// ### Type parameters:
+///
+/// - `D`: The `Saved` data of the `SaveData` used for saving
+#[derive(Default, Deserialize, Serialize)]
+pub struct SaveGame<D> {
+    /// Header of the save game.
+    pub header: SaveHeader,
+    /// Index of the parent of every saved entity, if the parent was saved as well.
+    parents: Vec<Option<usize>>,
+    data: D,
+}
+
+impl<D> SaveGame<D>
+where
+    D: Default + Serialize + DeserializeOwned,
+{
+    /// Save all `Saveable` entities of `world`, with the components selected by `T`.
+    pub fn save<'a, T>(world: &'a World, version: u32) -> Result<Self, Error>
+    where
+        T: SaveData<'a, Saved = D>,
+    {
+        let (entities, saveable, parents) = <(
+            Entities<'a>,
+            ReadStorage<'a, Saveable>,
+            ReadStorage<'a, Parent>,
+        )>::fetch(&world.res);
+        let saved = (&entities, &saveable)
+            .join()
+            .map(|(entity, _)| entity)
+            .collect::<Vec<_>>();
+        let indices = saved
+            .iter()
+            .enumerate()
+            .map(|(index, entity)| (*entity, index))
+            .collect::<HashMap<_, _>>();
+        let parent_indices = saved
+            .iter()
+            .map(|entity| {
+                parents
+                    .get(*entity)
+                    .and_then(|parent| indices.get(&parent.entity).cloned())
+            })
+            .collect();
+        let data = T::save(&T::SystemData::fetch(&world.res), &saved)?;
+
+        Ok(SaveGame {
+            header: SaveHeader { version },
+            parents: parent_indices,
+            data,
+        })
+    }
+
+    /// Create the saved entities in `world`, returning them in the order they were saved.
+    ///
+    /// The entities are marked `Saveable` again, so the game can be saved again later.
+    pub fn restore<'a, T>(self, world: &'a mut World) -> Result<Vec<Entity>, Error>
+    where
+        T: SaveData<'a, Saved = D>,
+    {
+        world.register::<Saveable>();
+        world.register::<Parent>();
+        let entities = self
+            .parents
+            .iter()
+            .map(|_| world.create_entity().with(Saveable).build())
+            .collect::<Vec<_>>();
+        {
+            let mut parents = world.write_storage::<Parent>();
+            for (index, parent) in self.parents.iter().enumerate() {
+                if let Some(parent) = *parent {
+                    let parent = Parent {
+                        entity: entity_at(&entities, parent)?,
+                    };
+                    parents.insert(entities[index], parent)?;
+                }
+            }
+        }
+
+        T::SystemData::setup(&mut world.res);
+        let world: &'a World = world;
+        T::restore(self.data, &mut T::SystemData::fetch(&world.res), &entities)?;
+
+        Ok(entities)
+    }
+
+    /// Convert the data of a save game of an older version to the layout of `version`.
+    ///
+    /// Load the save game with the `Saved` data of its own version, which can be found with
+    /// `SaveHeader::load` or `SaveHeader::load_binary`, then migrate it to the current version
+    /// before restoring it.
+    pub fn migrate<N, F>(self, version: u32, migrate: F) -> Result<SaveGame<N>, Error>
+    where
+        N: Default + Serialize + DeserializeOwned,
+        F: FnOnce(u32, D) -> Result<N, Error>,
+    {
+        let from = self.header.version;
+        let data = migrate(from, self.data).with_context(|_| {
+            format_err!(
+                "Failed migrating save game from version {} to {}",
+                from,
+                version
+            )
+        })?;
+        Ok(SaveGame {
+            header: SaveHeader { version },
+            parents: self.parents,
+            data,
+        })
+    }
+
+    /// Write the save game to a file using bincode.
+    pub fn write_binary<P: AsRef<Path>>(&self, path: P) -> Result<(), Error> {
+        let path = path.as_ref();
+        let bytes = bincode::serialize(self)
+            .with_context(|_| format_err!("Failed serializing save game"))?;
+        fs::write(path, bytes)
+            .with_context(|_| format_err!("Failed writing save game {:?}", path))?;
+        Ok(())
+    }
+
+    /// Load a save game written by `write_binary`.
+    pub fn load_binary<P: AsRef<Path>>(path: P) -> Result<Self, Error> {
+        let path = path.as_ref();
+        let bytes =
+            fs::read(path).with_context(|_| format_err!("Failed reading save game {:?}", path))?;
+        let save = bincode::deserialize(&bytes)
+            .with_context(|_| format_err!("Failed deserializing save game {:?}", path))?;
+        Ok(save)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{env, fs, path::PathBuf, sync::Arc, thread, time::Duration};
+
+    use rayon::{ThreadPool, ThreadPoolBuilder};
+    use serde::{Deserialize, Serialize};
+
+    use crate::{
+        assets::{Asset, AssetStorage, Handle, Loader, ProcessingState, SimpleFormat, Source},
+        core::Parent,
+        ecs::prelude::{Builder, Component, DenseVecStorage, VecStorage, World},
+        error::Error,
+    };
+
+    use super::{Persist, PersistAsset, SaveGame, SaveHeader, Saveable};
+
+    #[derive(Clone, Debug, Default, Deserialize, PartialEq, Serialize)]
+    struct Score(u32);
+
+    impl Component for Score {
+        type Storage = DenseVecStorage<Self>;
+    }
+
+    /// `Score` as it was saved in version 1, in hundreds.
+    #[derive(Clone, Debug, Default, Deserialize, PartialEq, Serialize)]
+    struct OldScore(u32);
+
+    impl Component for OldScore {
+        type Storage = DenseVecStorage<Self>;
+    }
+
+    fn save_path(name: &str) -> PathBuf {
+        env::temp_dir().join(format!("amethyst_{}_{}.bin", name, std::process::id()))
+    }
+
+    #[test]
+    fn restores_binary_save_game_with_parents() {
+        let mut world = World::new();
+        world.register::<Saveable>();
+        world.register::<Parent>();
+        world.register::<Score>();
+        let root = world.create_entity().with(Saveable).with(Score(1)).build();
+        world
+            .create_entity()
+            .with(Saveable)
+            .with(Score(2))
+            .with(Parent::new(root))
+            .build();
+        world.create_entity().with(Score(3)).build();
+
+        let path = save_path("save_game");
+        SaveGame::save::<Persist<Score>>(&world, 1)
+            .unwrap()
+            .write_binary(&path)
+            .unwrap();
+        let save = SaveGame::load_binary(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+
+        let mut world = World::new();
+        world.register::<Score>();
+        let entities = save.restore::<Persist<Score>>(&mut world).unwrap();
+        assert_eq!(2, entities.len());
+        let scores = world.read_storage::<Score>();
+        assert_eq!(Some(&Score(1)), scores.get(entities[0]));
+        assert_eq!(Some(&Score(2)), scores.get(entities[1]));
+        let parents = world.read_storage::<Parent>();
+        assert!(parents.get(entities[0]).is_none());
+        assert_eq!(Some(&Parent::new(entities[0])), parents.get(entities[1]));
+    }
+
+    #[test]
+    fn migrates_save_game_of_old_version() {
+        let mut world = World::new();
+        world.register::<Saveable>();
+        world.register::<OldScore>();
+        world
+            .create_entity()
+            .with(Saveable)
+            .with(OldScore(4))
+            .build();
+
+        let path = save_path("old_save_game");
+        SaveGame::save::<Persist<OldScore>>(&world, 1)
+            .unwrap()
+            .write_binary(&path)
+            .unwrap();
+        assert_eq!(
+            SaveHeader { version: 1 },
+            SaveHeader::load_binary(&path).unwrap()
+        );
+        let old = SaveGame::<Vec<(usize, OldScore)>>::load_binary(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+
+        let save = old
+            .migrate(2, |version, scores| {
+                assert_eq!(1, version);
+                Ok(scores
+                    .into_iter()
+                    .map(|(index, OldScore(score))| (index, Score(score * 100)))
+                    .collect::<Vec<_>>())
+            })
+            .unwrap();
+        assert_eq!(2, save.header.version);
+
+        let mut world = World::new();
+        world.register::<Score>();
+        let entities = save.restore::<Persist<Score>>(&mut world).unwrap();
+        assert_eq!(
+            Some(&Score(400)),
+            world.read_storage::<Score>().get(entities[0])
+        );
+    }
+
+    struct Number(u32);
+
+    impl Asset for Number {
+        const NAME: &'static str = "test::Number";
+        type Data = u32;
+        type HandleStorage = VecStorage<Handle<Self>>;
+    }
+
+    /// Imports the length of the file, multiplied by the options.
+    #[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+    struct LengthFormat;
+
+    impl SimpleFormat<Number> for LengthFormat {
+        const NAME: &'static str = "Length";
+        type Options = u32;
+
+        fn import(&self, bytes: Vec<u8>, factor: u32) -> Result<u32, Error> {
+            Ok(bytes.len() as u32 * factor)
+        }
+    }
+
+    struct Files;
+
+    impl Source for Files {
+        fn modified(&self, _: &str) -> Result<u64, Error> {
+            Ok(0)
+        }
+
+        fn load(&self, path: &str) -> Result<Vec<u8>, Error> {
+            match path {
+                "number" => Ok(b"abc".to_vec()),
+                _ => Err(Error::from_string("Not found")),
+            }
+        }
+    }
+
+    fn asset_world() -> World {
+        let pool = Arc::new(ThreadPoolBuilder::new().num_threads(1).build().unwrap());
+        let mut world = World::new();
+        world.register::<Saveable>();
+        world.register::<Handle<Number>>();
+        world.add_resource(Loader::with_default_source(Files, pool.clone()));
+        world.add_resource(pool);
+        let mut storage = AssetStorage::<Number>::new();
+        storage.record_formats();
+        world.add_resource(storage);
+        world
+    }
+
+    /// Processes the numbers of `world` until the one `handle` points to is loaded.
+    fn wait_for(world: &World, handle: &Handle<Number>) -> u32 {
+        let pool = world.read_resource::<Arc<ThreadPool>>().clone();
+        for frame in 0..1000 {
+            let mut storage = world.write_resource::<AssetStorage<Number>>();
+            storage.process(
+                |number| Ok(ProcessingState::Loaded(Number(number))),
+                frame,
+                &pool,
+                None,
+            );
+            if let Some(number) = storage.get(handle) {
+                return number.0;
+            }
+            drop(storage);
+            thread::sleep(Duration::from_millis(1));
+        }
+        panic!("Loading the asset didn't finish");
+    }
+
+    #[test]
+    fn restores_assets_with_their_format_and_options() {
+        let mut world = asset_world();
+        let handle = {
+            let storage = world.read_resource::<AssetStorage<Number>>();
+            let handle =
+                world
+                    .read_resource::<Loader>()
+                    .load("number", LengthFormat, 2, (), &storage);
+            storage.record_format(&handle, &LengthFormat, &2);
+            handle
+        };
+        world
+            .create_entity()
+            .with(Saveable)
+            .with(handle.clone())
+            .build();
+        assert_eq!(6, wait_for(&world, &handle));
+
+        let path = save_path("asset_save_game");
+        SaveGame::save::<PersistAsset<Number, LengthFormat>>(&world, 1)
+            .unwrap()
+            .write_binary(&path)
+            .unwrap();
+        let save = SaveGame::load_binary(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+
+        let mut world = asset_world();
+        let entities = save
+            .restore::<PersistAsset<Number, LengthFormat>>(&mut world)
+            .unwrap();
+        let handle = world
+            .read_storage::<Handle<Number>>()
+            .get(entities[0])
+            .cloned()
+            .expect("Asset handle was not restored");
+        assert_eq!(6, wait_for(&world, &handle));
+        let storage = world.read_resource::<AssetStorage<Number>>();
+        assert_eq!(Some("number"), storage.name(&handle));
+        assert_eq!(
+            Some((LengthFormat, 2)),
+            storage.format::<LengthFormat>(&handle)
+        );
+    }
+}